@@ -7,50 +7,105 @@ use crate::auth::sign_out;
 
 use crate::{
     auth::use_auth_session,
-    domain::services::{check_status::poll_conversion_status, video_converter::convert_video},
+    domain::{
+        entities::{
+            audio_format::AudioFormat,
+            file_name::{FileNameTemplate, PRESETS as FILE_NAME_PRESETS},
+            job_status::JobStatus,
+            playlist::PlaylistStatus,
+            preview::{PreviewResponse, VideoPreview},
+            progress::format_duration,
+            quality::{Bitrate, Channels, QualityProfile, SampleRate},
+            quota::Usage,
+            rate_limit::RateLimited,
+            tags::TrackTags,
+            video_ref::VideoRef,
+        },
+        services::{
+            cancel_conversion::cancel_conversion,
+            check_status::{watch_conversion_status, StatusSignals},
+            get_usage::get_usage,
+            preview_video::preview_video,
+            video_converter::{convert_video, ConvertResponse},
+        },
+    },
 };
 
 /// Renders the home page of your application.
 #[component]
 pub fn HomePage() -> impl IntoView {
     let url_input = RwSignal::new(String::new());
+    let selected_format = RwSignal::new(AudioFormat::default());
+    let selected_quality = RwSignal::new(QualityProfile::default());
+    let clip_start = RwSignal::new(String::new());
+    let clip_end = RwSignal::new(String::new());
+    let tag_overrides = RwSignal::new(TrackTags::default());
+    let whole_playlist = RwSignal::new(false);
     let is_converting = RwSignal::new(false);
+    let is_previewing = RwSignal::new(false);
+    // Lookup of the video about to be converted, awaiting confirmation
+    let preview = RwSignal::new(Option::<PreviewResponse>::None);
     let download_url = RwSignal::new(Option::<String>::None);
     let error_message = RwSignal::new(Option::<String>::None);
     let conversion_id = RwSignal::new(Option::<String>::None);
+    let latest_status = RwSignal::new(Option::<ConvertResponse>::None);
+    let is_cancelling = RwSignal::new(false);
+    let rate_limit = RwSignal::new(Option::<RateLimited>::None);
+    let file_name_template = RwSignal::new(FileNameTemplate::default());
+    let video_ref = Memo::new(move |_| VideoRef::parse(&url_input.get()).ok());
+    let is_playlist_page = move || video_ref.get().is_some_and(|video_ref| video_ref.is_playlist());
+    let signals = StatusSignals {
+        is_converting,
+        download_url,
+        error_message,
+        latest_status,
+    };
 
     let (auth_session, _set_auth_session) = use_auth_session();
 
-    let on_convert = move |_| {
-        let url = url_input.get();
-        if url.is_empty() {
-            error_message.set(Some("Please enter a YouTube URL".to_string()));
-            return;
-        }
+    // Remaining quota for the navbar, refreshed whenever a conversion starts or ends
+    let usage = RwSignal::new(Option::<Usage>::None);
+    Effect::new(move |_| {
+        is_converting.track();
+        conversion_id.track();
+        leptos::task::spawn_local(async move {
+            match get_usage().await {
+                Ok(current) => usage.set(current),
+                Err(e) => leptos::logging::log!("Failed to check usage: {}", e),
+            }
+        });
+    });
 
+    let begin_conversion = move |url: String| {
         // Reset states
+        preview.set(None);
         error_message.set(None);
         download_url.set(None);
         is_converting.set(true);
         conversion_id.set(None);
+        latest_status.set(None);
+        is_cancelling.set(false);
 
         // Start conversion
         leptos::task::spawn_local(async move {
-            match convert_video(url).await {
+            let format = selected_format.get_untracked();
+            let quality = selected_quality.get_untracked();
+            let (start, end) = (clip_start.get_untracked(), clip_end.get_untracked());
+            let tags = tag_overrides.get_untracked();
+            let playlist = whole_playlist.get_untracked();
+            match convert_video(url, format, quality, start, end, tags, playlist).await {
                 Ok(response) => {
-                    if response.status == "error" {
+                    if let Some(limited) = response.rate_limit {
+                        is_converting.set(false);
+                        rate_limit.set(Some(limited));
+                        count_down(rate_limit).await;
+                    } else if response.status == JobStatus::Failed {
                         is_converting.set(false);
                         error_message.set(Some(response.message));
                     } else {
                         conversion_id.set(Some(response.id.clone()));
-                        // Poll for status
-                        poll_conversion_status(
-                            response.id,
-                            is_converting,
-                            download_url,
-                            error_message,
-                        )
-                        .await;
+                        // Follow the job until it finishes
+                        watch_conversion_status(response.id, signals).await;
                     }
                 }
                 Err(e) => {
@@ -61,6 +116,61 @@ pub fn HomePage() -> impl IntoView {
         });
     };
 
+    // Single videos are looked up first so the user can check what they are
+    // about to convert; playlists are converted right away
+    let on_convert = move |_| {
+        let url = url_input.get();
+        if url.is_empty() {
+            error_message.set(Some("Please enter a YouTube URL".to_string()));
+            return;
+        }
+        let single_video = video_ref.get().is_some_and(|video_ref| !video_ref.is_playlist()) && !whole_playlist.get();
+        if !single_video {
+            begin_conversion(url);
+            return;
+        }
+
+        error_message.set(None);
+        preview.set(None);
+        is_previewing.set(true);
+        leptos::task::spawn_local(async move {
+            match preview_video(url).await {
+                Ok(response) => {
+                    is_previewing.set(false);
+                    if let Some(limited) = response.rate_limit {
+                        rate_limit.set(Some(limited));
+                        count_down(rate_limit).await;
+                    } else {
+                        preview.set(Some(response));
+                    }
+                }
+                Err(e) => {
+                    is_previewing.set(false);
+                    error_message.set(Some(format!("Could not look the video up: {e}")));
+                }
+            }
+        });
+    };
+
+    let on_cancel = move |_| {
+        let Some(job_id) = conversion_id.get() else {
+            return;
+        };
+        is_cancelling.set(true);
+
+        leptos::task::spawn_local(async move {
+            match cancel_conversion(job_id.clone()).await {
+                Ok(response) => {
+                    signals.apply(&job_id, response);
+                }
+                Err(e) => {
+                    error_message.set(Some(format!("Failed to cancel conversion: {e}")));
+                }
+            }
+            is_cancelling.set(false);
+        });
+    };
+
     #[cfg(feature = "hydrate")]
     let _navigate = use_navigate();
 
@@ -113,7 +223,19 @@ pub fn HomePage() -> impl IntoView {
                         </span>
                     </div>
                 </div>
-                <div class="navbar-end">
+                <div class="navbar-end gap-2">
+                    {move || {
+                        usage
+                            .get()
+                            .and_then(|usage| usage.remaining_label())
+                            .map(|label| {
+                                view! {
+                                    <div class="badge badge-ghost badge-lg hidden sm:inline-flex" title="Daily quotas reset at midnight UTC">
+                                        {label}
+                                    </div>
+                                }
+                            })
+                    }}
                     <div class="dropdown dropdown-end">
                         <div tabindex="0" role="button" class="btn btn-ghost btn-circle avatar">
                             <div class="w-10 rounded-full bg-gradient-to-r from-primary to-secondary flex items-center justify-center">
@@ -176,28 +298,211 @@ pub fn HomePage() -> impl IntoView {
                                             on:input=move |ev| {
                                                 url_input.set(event_target_value(&ev));
                                                 error_message.set(None);
+                                                preview.set(None);
                                             }
                                             class="input input-bordered input-lg join-item flex-1"
                                             class:input-disabled=move || is_converting.get()
                                             disabled=move || is_converting.get()
                                         />
+                                        <select
+                                            class="select select-bordered select-lg join-item"
+                                            aria-label="Output format"
+                                            disabled=move || is_converting.get()
+                                            on:change=move |ev| {
+                                                if let Ok(format) = event_target_value(&ev).parse() {
+                                                    selected_format.set(format);
+                                                }
+                                            }
+                                        >
+                                            {AudioFormat::ALL
+                                                .into_iter()
+                                                .map(|format| {
+                                                    view! {
+                                                        <option
+                                                            value=format.as_str()
+                                                            selected=move || selected_format.get() == format
+                                                        >
+                                                            {format.label()}
+                                                        </option>
+                                                    }
+                                                })
+                                                .collect_view()}
+                                        </select>
                                         <button
                                             on:click=on_convert
                                             disabled=move || {
-                                                is_converting.get() || url_input.get().is_empty()
+                                                is_converting.get()
+                                                    || is_previewing.get()
+                                                    || url_input.get().is_empty()
+                                                    || rate_limit.get().is_some()
                                             }
                                             class="btn btn-primary btn-lg join-item"
-                                            class:loading=move || is_converting.get()
+                                            class:loading=move || is_converting.get() || is_previewing.get()
                                         >
                                             {move || {
                                                 if is_converting.get() {
-                                                    "Converting..."
+                                                    "Converting...".to_string()
+                                                } else if is_previewing.get() {
+                                                    "Looking up...".to_string()
+                                                } else if let Some(limited) = rate_limit.get() {
+                                                    format!("Wait {}s", limited.retry_after_secs)
                                                 } else {
-                                                    "Convert to MP3"
+                                                    format!("Convert to {}", selected_format.get().label())
                                                 }
                                             }}
                                         </button>
                                     </div>
+
+                                    // Quality options; the bitrate only applies to lossy formats
+                                    <div class="flex flex-wrap justify-center gap-3">
+                                        <Show when=move || !selected_format.get().is_lossless()>
+                                            <select
+                                                class="select select-bordered select-sm"
+                                                aria-label="Bitrate"
+                                                disabled=move || is_converting.get()
+                                                on:change=move |ev| {
+                                                    if let Ok(bitrate) = event_target_value(&ev).parse::<Bitrate>() {
+                                                        selected_quality.update(|quality| quality.bitrate = bitrate);
+                                                    }
+                                                }
+                                            >
+                                                {Bitrate::PRESETS
+                                                    .into_iter()
+                                                    .map(|bitrate| {
+                                                        view! {
+                                                            <option
+                                                                value=bitrate.to_string()
+                                                                selected=move || selected_quality.get().bitrate == bitrate
+                                                            >
+                                                                {bitrate.label()}
+                                                            </option>
+                                                        }
+                                                    })
+                                                    .collect_view()}
+                                            </select>
+                                        </Show>
+                                        <select
+                                            class="select select-bordered select-sm"
+                                            aria-label="Sample rate"
+                                            disabled=move || is_converting.get()
+                                            on:change=move |ev| {
+                                                let value = event_target_value(&ev);
+                                                let rate = SampleRate::ALL
+                                                    .into_iter()
+                                                    .find(|rate| rate.hz().to_string() == value);
+                                                selected_quality.update(|quality| quality.sample_rate = rate);
+                                            }
+                                        >
+                                            <option value="">"Original sample rate"</option>
+                                            {SampleRate::ALL
+                                                .into_iter()
+                                                .map(|rate| {
+                                                    view! {
+                                                        <option
+                                                            value=rate.hz().to_string()
+                                                            selected=move || selected_quality.get().sample_rate == Some(rate)
+                                                        >
+                                                            {rate.label()}
+                                                        </option>
+                                                    }
+                                                })
+                                                .collect_view()}
+                                        </select>
+                                        <select
+                                            class="select select-bordered select-sm"
+                                            aria-label="Channels"
+                                            disabled=move || is_converting.get()
+                                            on:change=move |ev| {
+                                                let value = event_target_value(&ev);
+                                                let channels = Channels::ALL
+                                                    .into_iter()
+                                                    .find(|channels| channels.label() == value);
+                                                selected_quality.update(|quality| quality.channels = channels);
+                                            }
+                                        >
+                                            <option value="">"Original channels"</option>
+                                            {Channels::ALL
+                                                .into_iter()
+                                                .map(|channels| {
+                                                    view! {
+                                                        <option
+                                                            value=channels.label()
+                                                            selected=move || selected_quality.get().channels == Some(channels)
+                                                        >
+                                                            {channels.label()}
+                                                        </option>
+                                                    }
+                                                })
+                                                .collect_view()}
+                                        </select>
+                                        <input
+                                            type="text"
+                                            placeholder="Start (e.g. 1:23)"
+                                            aria-label="Clip start"
+                                            prop:value=move || clip_start.get()
+                                            on:input=move |ev| clip_start.set(event_target_value(&ev))
+                                            disabled=move || is_converting.get()
+                                            class="input input-bordered input-sm w-36"
+                                        />
+                                        <input
+                                            type="text"
+                                            placeholder="End (e.g. 4:56)"
+                                            aria-label="Clip end"
+                                            prop:value=move || clip_end.get()
+                                            on:input=move |ev| clip_end.set(event_target_value(&ev))
+                                            disabled=move || is_converting.get()
+                                            class="input input-bordered input-sm w-36"
+                                        />
+                                    </div>
+
+                                    // Videos opened from a playlist can bring the whole playlist along
+                                    <Show when=move || video_ref.get().is_some_and(|video_ref| video_ref.playlist_id.is_some())>
+                                        <label class="label cursor-pointer justify-center gap-3">
+                                            <input
+                                                type="checkbox"
+                                                class="checkbox checkbox-primary checkbox-sm"
+                                                prop:checked=move || {
+                                                    whole_playlist.get() || is_playlist_page()
+                                                }
+                                                on:change=move |ev| whole_playlist.set(event_target_checked(&ev))
+                                                disabled=move || {
+                                                    is_converting.get() || is_playlist_page()
+                                                }
+                                            />
+                                            <span class="label-text">"Convert the whole playlist (as a ZIP)"</span>
+                                        </label>
+                                    </Show>
+
+                                    // Optional tag overrides; empty fields keep the tags taken from the video
+                                    <details class="collapse collapse-arrow bg-base-100/50 max-w-2xl mx-auto">
+                                        <summary class="collapse-title text-sm font-medium">"Edit tags"</summary>
+                                        <div class="collapse-content grid grid-cols-1 sm:grid-cols-2 gap-3">
+                                            <TagInput
+                                                label="Title"
+                                                tags=tag_overrides
+                                                disabled=is_converting
+                                                field=|tags| &mut tags.title
+                                            />
+                                            <TagInput
+                                                label="Artist"
+                                                tags=tag_overrides
+                                                disabled=is_converting
+                                                field=|tags| &mut tags.artist
+                                            />
+                                            <TagInput
+                                                label="Album"
+                                                tags=tag_overrides
+                                                disabled=is_converting
+                                                field=|tags| &mut tags.album
+                                            />
+                                            <TagInput
+                                                label="Year"
+                                                tags=tag_overrides
+                                                disabled=is_converting
+                                                field=|tags| &mut tags.year
+                                            />
+                                        </div>
+                                    </details>
                                 </div>
 
                                 // Status messages using daisyUI alerts
@@ -218,6 +523,37 @@ pub fn HomePage() -> impl IntoView {
                                             })
                                     }}
 
+                                    // The looked-up video, converted once the user confirms
+                                    {move || {
+                                        preview
+                                            .get()
+                                            .map(|response| {
+                                                view! {
+                                                    <VideoPreviewCard
+                                                        response=response
+                                                        on_confirm=move || begin_conversion(url_input.get_untracked())
+                                                        on_dismiss=move || preview.set(None)
+                                                    />
+                                                }
+                                            })
+                                    }}
+
+                                    // Rate limit notice, counting down until converting is allowed again
+                                    {move || {
+                                        rate_limit
+                                            .get()
+                                            .map(|limited| {
+                                                view! {
+                                                    <div class="alert alert-warning shadow-lg max-w-2xl mx-auto">
+                                                        <svg xmlns="http://www.w3.org/2000/svg" class="stroke-current shrink-0 h-6 w-6" fill="none" viewBox="0 0 24 24">
+                                                            <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M12 8v4l3 3m6-3a9 9 0 11-18 0 9 9 0 0118 0z" />
+                                                        </svg>
+                                                        <span>{limited.message()}</span>
+                                                    </div>
+                                                }
+                                            })
+                                    }}
+
                                     // Loading state using daisyUI loading components
                                     {move || {
                                         is_converting
@@ -227,9 +563,59 @@ pub fn HomePage() -> impl IntoView {
                                                     <div class="alert alert-info shadow-lg max-w-2xl mx-auto">
                                                         <span class="loading loading-spinner loading-md"></span>
                                                         <div class="flex flex-col items-start">
-                                                            <span class="font-semibold">"Processing your video..."</span>
-                                                            <span class="text-sm opacity-70">"This may take a few moments"</span>
+                                                            <span class="font-semibold">
+                                                                {move || {
+                                                                    latest_status
+                                                                        .get()
+                                                                        .map_or(
+                                                                            "Processing your video...".to_string(),
+                                                                            |status| status.message,
+                                                                        )
+                                                                }}
+                                                            </span>
+                                                            {move || {
+                                                                let status = latest_status.get();
+                                                                let progress = status.as_ref().and_then(|status| status.progress);
+                                                                let transcoding = status
+                                                                    .as_ref()
+                                                                    .is_some_and(|status| status.status == JobStatus::Transcoding);
+                                                                match (progress, transcoding) {
+                                                                    (Some(progress), _) => view! {
+                                                                        <progress
+                                                                            class="progress progress-primary w-64 mt-2"
+                                                                            value=progress.percent.unwrap_or(0.0)
+                                                                            max="100"
+                                                                        ></progress>
+                                                                        <span class="text-sm opacity-70">{progress.summary()}</span>
+                                                                    }
+                                                                        .into_any(),
+                                                                    (None, true) => view! {
+                                                                        <progress class="progress progress-secondary w-64 mt-2"></progress>
+                                                                        <span class="text-sm opacity-70">"Almost done"</span>
+                                                                    }
+                                                                        .into_any(),
+                                                                    (None, false) => view! {
+                                                                        <span class="text-sm opacity-70">"This may take a few moments"</span>
+                                                                    }
+                                                                        .into_any(),
+                                                                }
+                                                            }}
+                                                            {move || {
+                                                                latest_status
+                                                                    .get()
+                                                                    .and_then(|status| status.playlist)
+                                                                    .map(|playlist| view! { <PlaylistTracks playlist=playlist file_name_template=file_name_template /> })
+                                                            }}
                                                         </div>
+                                                        <button
+                                                            on:click=on_cancel
+                                                            disabled=move || {
+                                                                is_cancelling.get() || conversion_id.get().is_none()
+                                                            }
+                                                            class="btn btn-sm btn-ghost"
+                                                        >
+                                                            {move || if is_cancelling.get() { "Cancelling..." } else { "Cancel" }}
+                                                        </button>
                                                     </div>
                                                 }
                                             })
@@ -240,6 +626,11 @@ pub fn HomePage() -> impl IntoView {
                                         download_url
                                             .get()
                                             .map(|url| {
+                                                // Playlists come as a ZIP named after the playlist
+                                                let href = move || match latest_status.get().and_then(|status| status.playlist) {
+                                                    Some(_) => url.clone(),
+                                                    None => format!("{url}{}", file_name_template.get().query()),
+                                                };
                                                 view! {
                                                     <div class="alert alert-success shadow-lg max-w-md mx-auto">
                                                         <svg xmlns="http://www.w3.org/2000/svg" class="stroke-current shrink-0 h-6 w-6" fill="none" viewBox="0 0 24 24">
@@ -247,13 +638,76 @@ pub fn HomePage() -> impl IntoView {
                                                         </svg>
                                                         <div class="flex flex-col items-start">
                                                             <span class="font-semibold">"Conversion complete!"</span>
+                                                            {move || {
+                                                                latest_status
+                                                                    .get()
+                                                                    .and_then(|status| status.tags)
+                                                                    .and_then(|tags| tags.display_name())
+                                                                    .map(|name| view! { <span>{name}</span> })
+                                                            }}
+                                                            {move || {
+                                                                latest_status
+                                                                    .get()
+                                                                    .map(|status| {
+                                                                        let quality = status.quality.summary(status.format);
+                                                                        match status.clip {
+                                                                            Some(clip) if quality.is_empty() => clip.label(),
+                                                                            Some(clip) => format!("{quality} · {}", clip.label()),
+                                                                            None => quality,
+                                                                        }
+                                                                    })
+                                                                    .filter(|summary| !summary.is_empty())
+                                                                    .map(|summary| view! { <span class="text-sm opacity-70">{summary}</span> })
+                                                            }}
+                                                            <select
+                                                                class="select select-bordered select-xs mt-2"
+                                                                aria-label="File name"
+                                                                on:change=move |ev| {
+                                                                    if let Ok(template) = FileNameTemplate::parse(&event_target_value(&ev)) {
+                                                                        file_name_template.set(template);
+                                                                    }
+                                                                }
+                                                            >
+                                                                {FILE_NAME_PRESETS
+                                                                    .into_iter()
+                                                                    .map(|preset| {
+                                                                        view! {
+                                                                            <option
+                                                                                value=preset
+                                                                                selected=move || file_name_template.get().as_str() == preset
+                                                                            >
+                                                                                {format!("Name: {preset}")}
+                                                                            </option>
+                                                                        }
+                                                                    })
+                                                                    .collect_view()}
+                                                            </select>
                                                             <a
-                                                                href=url
+                                                                href=href
                                                                 download
                                                                 class="btn btn-sm btn-success mt-2"
                                                             >
-                                                                "Download MP3"
+                                                                {move || {
+                                                                    let status = latest_status.get();
+                                                                    let format = status
+                                                                        .as_ref()
+                                                                        .map(|status| status.format)
+                                                                        .unwrap_or_default();
+                                                                    match status.and_then(|status| status.playlist) {
+                                                                        Some(_) => format!("Download {} ZIP", format.label()),
+                                                                        None => format!("Download {}", format.label()),
+                                                                    }
+                                                                }}
                                                             </a>
+                                                            {move || {
+                                                                let status = latest_status.get()?;
+                                                                let playlist = status.playlist?;
+                                                                let m3u = format!("/api/playlists/{}/m3u", status.id);
+                                                                Some(view! {
+                                                                    <a href=m3u download class="link text-sm mt-1">"M3U playlist"</a>
+                                                                    <PlaylistTracks playlist=playlist file_name_template=file_name_template />
+                                                                })
+                                                            }}
                                                         </div>
                                                     </div>
                                                 }
@@ -304,3 +758,179 @@ pub fn HomePage() -> impl IntoView {
         </div>
     }
 }
+
+/// Counts the wait of a rate-limited request down once a second, clearing
+/// it when the request may be made again.
+async fn count_down(rate_limit: RwSignal<Option<RateLimited>>) {
+    while rate_limit.get_untracked().is_some() {
+        #[cfg(feature = "hydrate")]
+        gloo_timers::future::sleep(std::time::Duration::from_secs(1)).await;
+
+        rate_limit.update(|limit| {
+            *limit = limit.filter(|limited| limited.retry_after_secs > 1).map(|limited| RateLimited {
+                retry_after_secs: limited.retry_after_secs - 1,
+                ..limited
+            });
+        });
+    }
+}
+
+/// A text field editing one optional tag; an empty field clears the tag.
+#[component]
+fn TagInput(
+    label: &'static str,
+    tags: RwSignal<TrackTags>,
+    disabled: RwSignal<bool>,
+    field: fn(&mut TrackTags) -> &mut Option<String>,
+) -> impl IntoView {
+    view! {
+        <input
+            type="text"
+            placeholder=label
+            aria-label=label
+            prop:value=move || {
+                let mut current = tags.get();
+                field(&mut current).take().unwrap_or_default()
+            }
+            on:input=move |ev| {
+                let value = event_target_value(&ev);
+                tags.update(|tags| *field(tags) = Some(value).filter(|value| !value.is_empty()));
+            }
+            disabled=move || disabled.get()
+            class="input input-bordered input-sm w-full"
+        />
+    }
+}
+
+/// The tracks of a playlist job with their status; finished tracks can be
+/// downloaded on their own, named by `file_name_template`.
+#[component]
+fn PlaylistTracks(playlist: PlaylistStatus, file_name_template: RwSignal<FileNameTemplate>) -> impl IntoView {
+    let summary = match playlist.tracks.len() {
+        0 => None,
+        total => Some(format!("{} of {} tracks converted", playlist.completed(), total)),
+    };
+
+    view! {
+        <div class="mt-2 text-left w-full">
+            {playlist.title.map(|title| view! { <div class="font-semibold">{title}</div> })}
+            {summary.map(|summary| view! { <div class="text-sm opacity-70">{summary}</div> })}
+            <ol class="list-decimal list-inside text-sm max-h-64 overflow-y-auto mt-1">
+                {playlist
+                    .tracks
+                    .into_iter()
+                    .map(|track| {
+                        let badge = match track.status {
+                            JobStatus::Completed => "badge-success",
+                            JobStatus::Failed | JobStatus::Expired => "badge-error",
+                            JobStatus::Cancelled => "badge-ghost",
+                            JobStatus::Queued => "badge-outline",
+                            JobStatus::Downloading | JobStatus::Transcoding => "badge-info",
+                        };
+                        let title = track.title.unwrap_or_else(|| "Untitled video".to_string());
+                        let download = (track.status == JobStatus::Completed).then(|| {
+                            let url = format!("/api/download/{}", track.job_id);
+                            move || format!("{url}{}", file_name_template.get().query())
+                        });
+                        view! {
+                            <li class="py-0.5" title=track.message>
+                                <span class=format!("badge badge-xs mr-2 {badge}")>{track.status.as_str()}</span>
+                                {match download {
+                                    Some(href) => view! { <a href=href download class="link">{title}</a> }.into_any(),
+                                    None => view! { <span>{title}</span> }.into_any(),
+                                }}
+                            </li>
+                        }
+                    })
+                    .collect_view()}
+            </ol>
+        </div>
+    }
+}
+
+/// What a video is, with buttons to convert it or think again. Conversion
+/// is offered even when the lookup failed, as downloads retry harder, but
+/// not for videos that are known not to convert.
+#[component]
+fn VideoPreviewCard(
+    response: PreviewResponse,
+    on_confirm: impl Fn() + Copy + Send + Sync + 'static,
+    on_dismiss: impl Fn() + Copy + Send + Sync + 'static,
+) -> impl IntoView {
+    let blocker = response.preview.as_ref().and_then(VideoPreview::blocker);
+
+    view! {
+        <div class="card card-side bg-base-100 shadow-lg max-w-2xl mx-auto text-left">
+            {response
+                .preview
+                .as_ref()
+                .and_then(|preview| preview.thumbnail_url.clone())
+                .map(|thumbnail| {
+                    view! {
+                        <figure class="w-48 shrink-0">
+                            <img src=thumbnail alt="Video thumbnail" class="object-cover h-full" />
+                        </figure>
+                    }
+                })}
+            <div class="card-body p-4">
+                {response
+                    .preview
+                    .map(|preview| {
+                        let details = [preview.channel.clone(), preview.duration_label()]
+                            .into_iter()
+                            .flatten()
+                            .collect::<Vec<_>>()
+                            .join(" · ");
+                        view! {
+                            <h3 class="card-title text-base">
+                                {preview.title.clone().unwrap_or_else(|| "Untitled video".to_string())}
+                            </h3>
+                            <span class="text-sm opacity-70">{details}</span>
+                            {(!preview.chapters.is_empty())
+                                .then(|| {
+                                    view! {
+                                        <details class="text-sm">
+                                            <summary class="cursor-pointer">
+                                                {format!("{} chapters", preview.chapters.len())}
+                                            </summary>
+                                            <ol class="max-h-48 overflow-y-auto mt-1">
+                                                {preview
+                                                    .chapters
+                                                    .into_iter()
+                                                    .map(|chapter| {
+                                                        view! {
+                                                            <li>
+                                                                <span class="font-mono opacity-70 mr-2">
+                                                                    {format_duration(chapter.start_secs)}
+                                                                </span>
+                                                                {chapter.title}
+                                                            </li>
+                                                        }
+                                                    })
+                                                    .collect_view()}
+                                            </ol>
+                                        </details>
+                                    }
+                                })}
+                        }
+                    })}
+                {blocker
+                    .map(String::from)
+                    .or(response.error)
+                    .map(|problem| view! { <span class="text-sm text-warning">{problem}</span> })}
+                <div class="card-actions justify-end">
+                    <button class="btn btn-sm btn-ghost" on:click=move |_| on_dismiss()>
+                        "Cancel"
+                    </button>
+                    <button
+                        class="btn btn-sm btn-primary"
+                        disabled=blocker.is_some()
+                        on:click=move |_| on_confirm()
+                    >
+                        "Convert"
+                    </button>
+                </div>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+
+/// Container and codec of the audio file a job produces.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioFormat {
+    #[default]
+    Mp3,
+    /// AAC in an MP4 container.
+    M4a,
+    /// Opus in an Ogg container.
+    Opus,
+    /// Vorbis in an Ogg container.
+    Vorbis,
+    Flac,
+    Wav,
+}
+
+impl AudioFormat {
+    /// Every format, in the order the UI offers them.
+    pub const ALL: [AudioFormat; 6] = [
+        AudioFormat::Mp3,
+        AudioFormat::M4a,
+        AudioFormat::Opus,
+        AudioFormat::Vorbis,
+        AudioFormat::Flac,
+        AudioFormat::Wav,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::M4a => "m4a",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Vorbis => "vorbis",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Wav => "wav",
+        }
+    }
+
+    /// Extension of the file yt-dlp writes for this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            AudioFormat::Vorbis => "ogg",
+            other => other.as_str(),
+        }
+    }
+
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "audio/mpeg",
+            AudioFormat::M4a => "audio/mp4",
+            AudioFormat::Opus | AudioFormat::Vorbis => "audio/ogg",
+            AudioFormat::Flac => "audio/flac",
+            AudioFormat::Wav => "audio/wav",
+        }
+    }
+
+    /// Name shown to users, e.g. on the download button.
+    pub fn label(self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "MP3",
+            AudioFormat::M4a => "M4A (AAC)",
+            AudioFormat::Opus => "Opus",
+            AudioFormat::Vorbis => "Ogg Vorbis",
+            AudioFormat::Flac => "FLAC",
+            AudioFormat::Wav => "WAV",
+        }
+    }
+
+    /// Returns `true` for formats that keep the full audio quality.
+    pub fn is_lossless(self) -> bool {
+        matches!(self, AudioFormat::Flac | AudioFormat::Wav)
+    }
+}
+
+impl std::fmt::Display for AudioFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for AudioFormat {
+    type Err = UnknownAudioFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AudioFormat::ALL
+            .into_iter()
+            .find(|format| format.as_str() == s)
+            .ok_or_else(|| UnknownAudioFormat(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown audio format `{0}`")]
+pub struct UnknownAudioFormat(pub String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats_round_trip() {
+        for format in AudioFormat::ALL {
+            assert_eq!(format.as_str().parse::<AudioFormat>().unwrap(), format);
+            let json = serde_json::to_string(&format).unwrap();
+            assert_eq!(json, format!("\"{format}\""));
+        }
+        assert!("aiff".parse::<AudioFormat>().is_err());
+    }
+
+    #[test]
+    fn test_vorbis_is_written_as_ogg() {
+        assert_eq!(AudioFormat::Vorbis.extension(), "ogg");
+        assert_eq!(AudioFormat::Vorbis.mime_type(), "audio/ogg");
+        assert_eq!(AudioFormat::M4a.extension(), "m4a");
+        assert_eq!(AudioFormat::default(), AudioFormat::Mp3);
+    }
+}
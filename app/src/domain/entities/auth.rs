@@ -76,6 +76,17 @@ pub struct AppMetadata {
     pub providers: Vec<String>,
 }
 
+/// A signed-in user whose access token the server has verified.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifiedUser {
+    pub user_id: String,
+    pub email: Option<String>,
+    /// Supabase role claim, e.g. `authenticated`.
+    pub role: String,
+    /// Expiry of the access token, in seconds since the Unix epoch.
+    pub expires_at: i64,
+}
+
 // Simplified session struct for local storage
 #[derive(Default, Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
 pub struct AuthSession {
@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::entities::progress::format_duration;
+
+/// Section of the source video to convert, in whole seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ClipRange {
+    pub start_secs: u64,
+    /// `None` runs to the end of the video.
+    pub end_secs: Option<u64>,
+}
+
+impl ClipRange {
+    /// # Errors
+    ///
+    /// Returns an error if `end_secs` is not after `start_secs`.
+    pub fn new(start_secs: u64, end_secs: Option<u64>) -> Result<Self, InvalidClip> {
+        if let Some(end_secs) = end_secs {
+            if end_secs <= start_secs {
+                return Err(InvalidClip::EndBeforeStart);
+            }
+        }
+        Ok(Self {
+            start_secs,
+            end_secs,
+        })
+    }
+
+    /// Builds the clip from the start and end fields of the form.
+    ///
+    /// An empty start falls back to `link_start_secs`, where the video's
+    /// link starts playing. Returns `None` when the whole video was asked for.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a timestamp cannot be parsed or the end is not
+    /// after the start.
+    pub fn from_inputs(start: &str, end: &str, link_start_secs: Option<u64>) -> Result<Option<Self>, InvalidClip> {
+        let start_secs = match start.trim() {
+            "" => link_start_secs.unwrap_or(0),
+            start => parse_timestamp(start)?,
+        };
+        let end_secs = match end.trim() {
+            "" => None,
+            end => Some(parse_timestamp(end)?),
+        };
+
+        if start_secs == 0 && end_secs.is_none() {
+            return Ok(None);
+        }
+        Self::new(start_secs, end_secs).map(Some)
+    }
+
+    pub fn duration_secs(&self) -> Option<u64> {
+        self.end_secs.map(|end| end - self.start_secs)
+    }
+
+    /// Human-readable range such as `1:23 – 4:56` or `1:23 – end`.
+    pub fn label(&self) -> String {
+        let end = self.end_secs.map_or("end".to_string(), format_duration);
+        format!("{} – {}", format_duration(self.start_secs), end)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidClip {
+    #[error("`{0}` is not a valid time; use a format like 1:23 or 01:02:03")]
+    Timestamp(String),
+    #[error("The clip must end after it starts")]
+    EndBeforeStart,
+}
+
+/// Parses a timestamp into seconds.
+///
+/// Accepts plain seconds (`83`), clock times (`1:23`, `01:02:03`) and the
+/// unit form YouTube uses in `t=` links (`83s`, `1m23s`, `1h2m3s`).
+///
+/// # Errors
+///
+/// Returns an error for anything else, including minutes or seconds of 60
+/// or more in a clock time.
+pub fn parse_timestamp(input: &str) -> Result<u64, InvalidClip> {
+    let input = input.trim();
+    let invalid = || InvalidClip::Timestamp(input.to_string());
+    let number = |part: &str| -> Result<u64, InvalidClip> {
+        if part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        part.parse().map_err(|_| invalid())
+    };
+
+    if input.contains(':') {
+        let parts: Vec<&str> = input.split(':').collect();
+        let (hours, minutes, seconds) = match parts[..] {
+            [minutes, seconds] => (0, number(minutes)?, number(seconds)?),
+            [hours, minutes, seconds] => {
+                let minutes = number(minutes)?;
+                if minutes >= 60 {
+                    return Err(invalid());
+                }
+                (number(hours)?, minutes, number(seconds)?)
+            }
+            _ => return Err(invalid()),
+        };
+        if seconds >= 60 {
+            return Err(invalid());
+        }
+        return Ok(hours * 3600 + minutes * 60 + seconds);
+    }
+
+    if input.ends_with(['h', 'm', 's']) {
+        let mut total = 0;
+        let mut rest = input;
+        for (unit, factor) in [('h', 3600), ('m', 60), ('s', 1)] {
+            if let Some((value, tail)) = rest.split_once(unit) {
+                total += number(value)? * factor;
+                rest = tail;
+            }
+        }
+        return if rest.is_empty() { Ok(total) } else { Err(invalid()) };
+    }
+
+    number(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        for (input, secs) in [
+            ("83", 83),
+            ("1:23", 83),
+            ("01:02:03", 3723),
+            ("0:05", 5),
+            ("83s", 83),
+            ("1m23s", 83),
+            ("1h2m3s", 3723),
+            ("2m", 120),
+        ] {
+            assert_eq!(parse_timestamp(input), Ok(secs), "{input}");
+        }
+        for input in ["", "1:60", "1:75:00", "a:b", "1:2:3:4", "-5", "1.5", "1x", "m"] {
+            assert!(parse_timestamp(input).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_from_inputs() {
+        assert_eq!(
+            ClipRange::from_inputs("", "2:00", Some(90)),
+            Ok(Some(ClipRange { start_secs: 90, end_secs: Some(120) }))
+        );
+        assert_eq!(
+            ClipRange::from_inputs("0:10", "", Some(90)),
+            Ok(Some(ClipRange { start_secs: 10, end_secs: None }))
+        );
+        assert_eq!(ClipRange::from_inputs("", "", None), Ok(None));
+        assert_eq!(
+            ClipRange::from_inputs("2:00", "1:00", Some(90)),
+            Err(InvalidClip::EndBeforeStart)
+        );
+        assert_eq!(
+            ClipRange::new(90, Some(120)).unwrap().label(),
+            "1:30 – 2:00"
+        );
+    }
+}
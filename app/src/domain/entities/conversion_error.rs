@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+
+/// Why a conversion failed, as a code clients can act on.
+///
+/// Produced on the server by [`ConversionError::classify`] from the
+/// extractor's error output and sent to the browser next to the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConversionError {
+    /// YouTube asked us to prove we are not a bot.
+    BotDetection,
+    /// YouTube is throttling our requests.
+    RateLimited,
+    /// The video is private, deleted, blocked in our region or never existed.
+    Unavailable,
+    /// The video needs a signed-in adult account.
+    AgeRestricted,
+    /// The video is a premiere that has not started yet.
+    Premiere,
+    /// The video is a live stream, running or scheduled.
+    LiveStream,
+    /// The video is longer than the server converts.
+    TooLong,
+    /// yt-dlp, ffmpeg or ffprobe is not installed on the server.
+    ToolMissing,
+    /// YouTube or the network stopped responding.
+    Timeout,
+    Unknown,
+}
+
+/// Error output fragments, lowercased, in the order they are checked.
+///
+/// More specific patterns come first: an age gate also says "sign in to
+/// confirm", and live events mention when they "will begin".
+const PATTERNS: &[(&str, ConversionError)] = &[
+    ("ffmpeg not found", ConversionError::ToolMissing),
+    ("ffprobe and ffmpeg not found", ConversionError::ToolMissing),
+    ("ffprobe/avprobe and ffmpeg/avconv not found", ConversionError::ToolMissing),
+    ("sign in to confirm your age", ConversionError::AgeRestricted),
+    ("age-restricted", ConversionError::AgeRestricted),
+    ("age_restricted", ConversionError::AgeRestricted),
+    ("inappropriate for some users", ConversionError::AgeRestricted),
+    ("sign in to confirm you", ConversionError::BotDetection),
+    ("failed to extract any player response", ConversionError::BotDetection),
+    ("http error 429", ConversionError::RateLimited),
+    ("too many requests", ConversionError::RateLimited),
+    ("rate limit", ConversionError::RateLimited),
+    ("rate-limited", ConversionError::RateLimited),
+    ("premieres in", ConversionError::Premiere),
+    ("premiere will begin", ConversionError::Premiere),
+    ("this live event will begin", ConversionError::LiveStream),
+    ("live stream", ConversionError::LiveStream),
+    ("is currently live", ConversionError::LiveStream),
+    ("does not pass filter (duration", ConversionError::TooLong),
+    ("video unavailable", ConversionError::Unavailable),
+    ("private video", ConversionError::Unavailable),
+    ("this video has been removed", ConversionError::Unavailable),
+    ("this video is not available", ConversionError::Unavailable),
+    ("not made this video available in your country", ConversionError::Unavailable),
+    ("account associated with this video has been terminated", ConversionError::Unavailable),
+    ("incomplete youtube id", ConversionError::Unavailable),
+    ("timed out", ConversionError::Timeout),
+    ("timeouterror", ConversionError::Timeout),
+];
+
+impl ConversionError {
+    /// Classifies the error output of a failed download attempt.
+    pub fn classify(stderr: &str) -> Self {
+        let stderr = stderr.to_lowercase();
+        PATTERNS
+            .iter()
+            .find(|(pattern, _)| stderr.contains(pattern))
+            .map_or(ConversionError::Unknown, |(_, error)| *error)
+    }
+
+    /// Stable identifier sent to clients, e.g. `bot_detection`.
+    pub fn code(self) -> &'static str {
+        match self {
+            ConversionError::BotDetection => "bot_detection",
+            ConversionError::RateLimited => "rate_limited",
+            ConversionError::Unavailable => "unavailable",
+            ConversionError::AgeRestricted => "age_restricted",
+            ConversionError::Premiere => "premiere",
+            ConversionError::LiveStream => "live_stream",
+            ConversionError::TooLong => "too_long",
+            ConversionError::ToolMissing => "tool_missing",
+            ConversionError::Timeout => "timeout",
+            ConversionError::Unknown => "unknown",
+        }
+    }
+
+    /// What to tell the user.
+    pub fn message(self) -> &'static str {
+        match self {
+            ConversionError::BotDetection => "YouTube is currently blocking automated downloads. This is temporary - please try again in 10-15 minutes, or try a different video.",
+            ConversionError::RateLimited => "YouTube is rate limiting requests. Please wait a few minutes before trying again.",
+            ConversionError::Unavailable => "This video is unavailable. It may be private, deleted, or region-restricted.",
+            ConversionError::AgeRestricted => "This video is age-restricted and cannot be downloaded without authentication.",
+            ConversionError::Premiere => "This video is a premiere that hasn't started yet. Please wait until it's available.",
+            ConversionError::LiveStream => "Live streams cannot be downloaded. Please wait until the stream ends or try a regular video.",
+            ConversionError::TooLong => "This video is too long to convert. Please try a shorter video or convert a clip of it.",
+            ConversionError::ToolMissing => "The converter is not set up correctly on the server. Please try again later.",
+            ConversionError::Timeout => "YouTube took too long to respond. Please try again in a few minutes.",
+            ConversionError::Unknown => "Download failed after multiple attempts.",
+        }
+    }
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Error output captured from yt-dlp runs, with what it should classify as.
+    const CAPTURED: &[(&str, ConversionError)] = &[
+        (
+            "WARNING: [youtube] Skipping player responses from android clients\n\
+             ERROR: [youtube] dQw4w9WgXcQ: Sign in to confirm you\u{2019}re not a bot. Use --cookies-from-browser or --cookies for the authentication. See  https://github.com/yt-dlp/yt-dlp/wiki/FAQ#how-do-i-pass-cookies-to-yt-dlp  for how to manually pass cookies. Also see  https://github.com/yt-dlp/yt-dlp/wiki/Extractors#exporting-youtube-cookies  for tips on effectively exporting YouTube cookies",
+            ConversionError::BotDetection,
+        ),
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: Sign in to confirm you're not a bot. This helps protect our community. Learn more",
+            ConversionError::BotDetection,
+        ),
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: Failed to extract any player response; please report this issue on  https://github.com/yt-dlp/yt-dlp/issues?q= , filling out the appropriate issue template. Confirm you are on the latest version using  yt-dlp -U",
+            ConversionError::BotDetection,
+        ),
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: Sign in to confirm your age. This video may be inappropriate for some users. Use --cookies-from-browser or --cookies for the authentication.",
+            ConversionError::AgeRestricted,
+        ),
+        (
+            "ERROR: unable to download video data: HTTP Error 429: Too Many Requests",
+            ConversionError::RateLimited,
+        ),
+        (
+            "ERROR: [youtube] xxxxxxxxxxx: Video unavailable. This video has been removed by the uploader",
+            ConversionError::Unavailable,
+        ),
+        (
+            "ERROR: [youtube] xxxxxxxxxxx: Private video. Sign in if you've been granted access to this video",
+            ConversionError::Unavailable,
+        ),
+        (
+            "ERROR: [youtube] xxxxxxxxxxx: Video unavailable. The uploader has not made this video available in your country",
+            ConversionError::Unavailable,
+        ),
+        (
+            "ERROR: [youtube:truncated_id] dQw4w9: Incomplete YouTube ID dQw4w9. URL https://www.youtube.com/watch?v=dQw4w9 looks truncated.",
+            ConversionError::Unavailable,
+        ),
+        (
+            "ERROR: [youtube] xxxxxxxxxxx: Premieres in 3 hours",
+            ConversionError::Premiere,
+        ),
+        (
+            "ERROR: [youtube] xxxxxxxxxxx: This live event will begin in 5 hours.",
+            ConversionError::LiveStream,
+        ),
+        (
+            "[download] Lofi Radio does not pass filter (duration < 7200), skipping ..",
+            ConversionError::TooLong,
+        ),
+        (
+            // Missing files are only a missing tool when the extractor did not start
+            "Command execution failed: No such file or directory (os error 2)",
+            ConversionError::Unknown,
+        ),
+        (
+            "ERROR: Postprocessing: ffprobe and ffmpeg not found. Please install or provide the path using --ffmpeg-location",
+            ConversionError::ToolMissing,
+        ),
+        (
+            "ERROR: unable to download video data: <urlopen error _ssl.c:990: The handshake operation timed out>",
+            ConversionError::Timeout,
+        ),
+        (
+            "ERROR: [youtube] dQw4w9WgXcQ: Unable to download API page: HTTPSConnectionPool(host='www.youtube.com', port=443): Read timed out. (read timeout=30.0) (caused by TransportError(\"HTTPSConnectionPool(host='www.youtube.com', port=443): Read timed out. (read timeout=30.0)\"))",
+            ConversionError::Timeout,
+        ),
+        (
+            "ERROR: unable to download video data: HTTP Error 403: Forbidden",
+            ConversionError::Unknown,
+        ),
+        ("", ConversionError::Unknown),
+    ];
+
+    #[test]
+    fn test_classifies_captured_output() {
+        for (stderr, expected) in CAPTURED {
+            assert_eq!(ConversionError::classify(stderr), *expected, "{stderr}");
+        }
+    }
+
+    #[test]
+    fn test_code_matches_serialized_form() {
+        for (_, error) in CAPTURED {
+            assert_eq!(
+                serde_json::to_string(error).unwrap(),
+                format!("\"{}\"", error.code())
+            );
+        }
+    }
+}
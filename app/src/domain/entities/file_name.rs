@@ -0,0 +1,167 @@
+use serde::Deserialize;
+
+use crate::domain::entities::tags::TrackTags;
+
+/// Template used when the user has not picked one.
+pub const DEFAULT_TEMPLATE: &str = "{title}";
+
+/// Templates offered on the home page.
+pub const PRESETS: [&str; 3] = ["{title}", "{artist} - {title}", "{artist} - {album} - {title}"];
+
+/// Longest template accepted, in characters.
+pub const MAX_TEMPLATE_LEN: usize = 100;
+
+/// How a downloaded file is named, e.g. `{artist} - {title}`.
+///
+/// `{title}`, `{artist}`, `{album}` and `{year}` are replaced by the file's
+/// tags; everything else is kept as written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileNameTemplate(String);
+
+impl FileNameTemplate {
+    /// # Errors
+    ///
+    /// Returns an error if the template is too long, leaves a `{` open or
+    /// uses an unknown placeholder.
+    pub fn parse(template: &str) -> Result<Self, InvalidTemplate> {
+        if template.chars().count() > MAX_TEMPLATE_LEN {
+            return Err(InvalidTemplate::TooLong);
+        }
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            let Some(close) = rest[open..].find('}').map(|close| open + close) else {
+                return Err(InvalidTemplate::Unclosed);
+            };
+            let name = &rest[open + 1..close];
+            if field(&TrackTags::default(), name).is_none() {
+                return Err(InvalidTemplate::UnknownPlaceholder(name.to_string()));
+            }
+            rest = &rest[close + 1..];
+        }
+        Ok(Self(template.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The query to add to a download link for this template, e.g.
+    /// `?name=%7Bartist%7D%20-%20%7Btitle%7D`. Empty for the default.
+    pub fn query(&self) -> String {
+        if self.0 == DEFAULT_TEMPLATE {
+            return String::new();
+        }
+        let mut query = String::from("?name=");
+        for byte in self.0.bytes() {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                query.push(byte as char);
+            } else {
+                query.push_str(&format!("%{byte:02X}"));
+            }
+        }
+        query
+    }
+
+    /// The name for a file with `tags`, without extension. `None` if a
+    /// placeholder has no value, so `{artist} - {title}` never yields
+    /// ` - Title`.
+    pub fn render(&self, tags: &TrackTags) -> Option<String> {
+        let mut name = String::with_capacity(self.0.len());
+        let mut rest = self.0.as_str();
+        while let Some(open) = rest.find('{') {
+            // Parsing checked that every placeholder is closed and known
+            let close = open + rest[open..].find('}')?;
+            name.push_str(&rest[..open]);
+            name.push_str(field(tags, &rest[open + 1..close])??);
+            rest = &rest[close + 1..];
+        }
+        name.push_str(rest);
+        Some(name)
+    }
+}
+
+impl Default for FileNameTemplate {
+    fn default() -> Self {
+        Self(DEFAULT_TEMPLATE.to_string())
+    }
+}
+
+/// Query of a download link.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DownloadQuery {
+    /// A [`FileNameTemplate`] to name the file by.
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// The value of a placeholder: `None` if unknown, `Some(None)` if unset.
+fn field<'a>(tags: &'a TrackTags, name: &str) -> Option<Option<&'a str>> {
+    let value = match name {
+        "title" => &tags.title,
+        "artist" => &tags.artist,
+        "album" => &tags.album,
+        "year" => &tags.year,
+        _ => return None,
+    };
+    Some(value.as_deref())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidTemplate {
+    #[error("The file name template is too long")]
+    TooLong,
+    #[error("The file name template has a `{{` without a `}}`")]
+    Unclosed,
+    #[error("`{{{0}}}` is not a known placeholder")]
+    UnknownPlaceholder(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags() -> TrackTags {
+        TrackTags {
+            title: Some("Never Gonna Give You Up".to_string()),
+            artist: Some("Rick Astley".to_string()),
+            album: None,
+            year: Some("1987".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_render_fills_placeholders() {
+        let render = |template: &str| FileNameTemplate::parse(template).unwrap().render(&tags());
+        assert_eq!(render("{artist} - {title}").as_deref(), Some("Rick Astley - Never Gonna Give You Up"));
+        assert_eq!(render("{title} ({year})").as_deref(), Some("Never Gonna Give You Up (1987)"));
+        assert_eq!(render("mixtape").as_deref(), Some("mixtape"));
+        // A missing tag fails the whole name rather than leaving a gap
+        assert_eq!(render("{artist} - {album} - {title}"), None);
+        assert_eq!(FileNameTemplate::default().render(&TrackTags::default()), None);
+    }
+
+    #[test]
+    fn test_query_encodes_the_template() {
+        assert_eq!(FileNameTemplate::default().query(), "");
+        assert_eq!(
+            FileNameTemplate::parse("{artist} - {title}").unwrap().query(),
+            "?name=%7Bartist%7D%20-%20%7Btitle%7D"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_templates() {
+        for preset in PRESETS {
+            assert!(FileNameTemplate::parse(preset).is_ok(), "{preset}");
+        }
+        assert_eq!(
+            FileNameTemplate::parse("{artist} - {name}"),
+            Err(InvalidTemplate::UnknownPlaceholder("name".to_string()))
+        );
+        assert_eq!(FileNameTemplate::parse("{title"), Err(InvalidTemplate::Unclosed));
+        assert_eq!(
+            FileNameTemplate::parse(&"x".repeat(MAX_TEMPLATE_LEN + 1)),
+            Err(InvalidTemplate::TooLong)
+        );
+    }
+}
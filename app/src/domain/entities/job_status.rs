@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+
+/// Lifecycle state of a conversion job.
+///
+/// Shared between the server, which drives the state machine, and the
+/// browser, which only ever reads it back from `check_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Downloading,
+    Transcoding,
+    Completed,
+    Failed,
+    Cancelled,
+    Expired,
+}
+
+impl JobStatus {
+    /// Returns `true` if a job in this state may move to `next`.
+    ///
+    /// A transcoding job goes back to `Downloading` when the runner retries
+    /// with another strategy, running jobs may go back to `Queued` when they
+    /// are re-queued after the server was interrupted, and any job that is not yet `Expired` may
+    /// expire once it has been abandoned for too long.
+    pub fn can_transition_to(self, next: JobStatus) -> bool {
+        use JobStatus::*;
+
+        matches!(
+            (self, next),
+            (Queued, Downloading | Failed | Cancelled)
+                | (Downloading, Queued | Transcoding | Completed | Failed | Cancelled)
+                | (Transcoding, Queued | Downloading | Completed | Failed | Cancelled)
+                | (Queued | Downloading | Transcoding | Completed | Failed | Cancelled, Expired)
+        )
+    }
+
+    /// Returns `true` once the job has stopped doing work.
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled | JobStatus::Expired
+        )
+    }
+
+    /// Returns `true` while the job is waiting for or occupying a worker.
+    pub fn is_active(self) -> bool {
+        !self.is_finished()
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Downloading => "downloading",
+            JobStatus::Transcoding => "transcoding",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+            JobStatus::Expired => "expired",
+        }
+    }
+}
+
+impl std::fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for JobStatus {
+    type Err = UnknownJobStatus;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(JobStatus::Queued),
+            "downloading" => Ok(JobStatus::Downloading),
+            "transcoding" => Ok(JobStatus::Transcoding),
+            "completed" => Ok(JobStatus::Completed),
+            "failed" => Ok(JobStatus::Failed),
+            "cancelled" => Ok(JobStatus::Cancelled),
+            "expired" => Ok(JobStatus::Expired),
+            other => Err(UnknownJobStatus(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("unknown job status `{0}`")]
+pub struct UnknownJobStatus(pub String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("illegal job status transition from {from} to {to}")]
+pub struct InvalidTransition {
+    pub from: JobStatus,
+    pub to: JobStatus,
+}
+
+/// A single entry in a job's status history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusTransition {
+    pub status: JobStatus,
+    /// Milliseconds since the Unix epoch.
+    pub at_ms: u64,
+}
+
+/// Current status of a job together with the time it entered every state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusHistory {
+    transitions: Vec<StatusTransition>,
+}
+
+impl StatusHistory {
+    /// Starts a new history in the `Queued` state.
+    pub fn new() -> Self {
+        Self::starting_at(JobStatus::Queued, now_ms())
+    }
+
+    pub fn starting_at(status: JobStatus, at_ms: u64) -> Self {
+        Self {
+            transitions: vec![StatusTransition { status, at_ms }],
+        }
+    }
+
+    /// Rebuilds a history from previously recorded transitions.
+    ///
+    /// Returns `None` if `transitions` is empty.
+    pub fn from_transitions(transitions: Vec<StatusTransition>) -> Option<Self> {
+        if transitions.is_empty() {
+            None
+        } else {
+            Some(Self { transitions })
+        }
+    }
+
+    pub fn current(&self) -> JobStatus {
+        self.transitions
+            .last()
+            .map(|t| t.status)
+            .unwrap_or(JobStatus::Queued)
+    }
+
+    /// Time at which the job entered its current status.
+    pub fn updated_at_ms(&self) -> u64 {
+        self.transitions.last().map(|t| t.at_ms).unwrap_or_default()
+    }
+
+    /// Time at which the job first entered `status`, if it ever did.
+    pub fn entered_at_ms(&self, status: JobStatus) -> Option<u64> {
+        self.transitions
+            .iter()
+            .find(|t| t.status == status)
+            .map(|t| t.at_ms)
+    }
+
+    pub fn transitions(&self) -> &[StatusTransition] {
+        &self.transitions
+    }
+
+    /// Moves to `next`, recording the current time.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state machine does not allow moving from the
+    /// current status to `next`.
+    pub fn transition(&mut self, next: JobStatus) -> Result<(), InvalidTransition> {
+        self.transition_at(next, now_ms())
+    }
+
+    /// Moves to `next`, recording `at_ms` as the transition time.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state machine does not allow moving from the
+    /// current status to `next`.
+    pub fn transition_at(&mut self, next: JobStatus, at_ms: u64) -> Result<(), InvalidTransition> {
+        let from = self.current();
+        if !from.can_transition_to(next) {
+            return Err(InvalidTransition { from, to: next });
+        }
+        self.transitions.push(StatusTransition { status: next, at_ms });
+        Ok(())
+    }
+}
+
+impl Default for StatusHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Milliseconds since the Unix epoch according to the local clock.
+pub fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_happy_path_transitions() {
+        let mut history = StatusHistory::starting_at(JobStatus::Queued, 1);
+        history.transition_at(JobStatus::Downloading, 2).unwrap();
+        history.transition_at(JobStatus::Transcoding, 3).unwrap();
+        history.transition_at(JobStatus::Completed, 4).unwrap();
+        history.transition_at(JobStatus::Expired, 5).unwrap();
+
+        assert_eq!(history.current(), JobStatus::Expired);
+        assert_eq!(history.updated_at_ms(), 5);
+        assert_eq!(history.entered_at_ms(JobStatus::Transcoding), Some(3));
+        assert_eq!(history.transitions().len(), 5);
+    }
+
+    #[test]
+    fn test_illegal_transitions_are_rejected() {
+        let mut history = StatusHistory::starting_at(JobStatus::Queued, 1);
+        assert_eq!(
+            history.transition_at(JobStatus::Completed, 2),
+            Err(InvalidTransition {
+                from: JobStatus::Queued,
+                to: JobStatus::Completed,
+            })
+        );
+
+        history.transition_at(JobStatus::Failed, 2).unwrap();
+        assert!(history.transition_at(JobStatus::Downloading, 3).is_err());
+        assert!(history.transition_at(JobStatus::Completed, 3).is_err());
+        assert_eq!(history.current(), JobStatus::Failed);
+        assert_eq!(history.transitions().len(), 2);
+    }
+
+    #[test]
+    fn test_status_serializes_as_snake_case() {
+        for status in [
+            JobStatus::Queued,
+            JobStatus::Downloading,
+            JobStatus::Transcoding,
+            JobStatus::Completed,
+            JobStatus::Failed,
+            JobStatus::Cancelled,
+            JobStatus::Expired,
+        ] {
+            let json = serde_json::to_string(&status).unwrap();
+            assert_eq!(json, format!("\"{status}\""));
+            assert_eq!(status.as_str().parse::<JobStatus>().unwrap(), status);
+        }
+    }
+}
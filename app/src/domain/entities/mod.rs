@@ -1 +1,14 @@
+pub mod audio_format;
 pub mod auth;
+pub mod clip;
+pub mod conversion_error;
+pub mod file_name;
+pub mod job_status;
+pub mod playlist;
+pub mod preview;
+pub mod progress;
+pub mod quality;
+pub mod quota;
+pub mod rate_limit;
+pub mod tags;
+pub mod video_ref;
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::entities::job_status::JobStatus;
+
+/// One video of a playlist, as listed before anything is downloaded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaylistEntry {
+    pub video_id: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Length of the video, if the listing said.
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+}
+
+impl PlaylistEntry {
+    /// Watch URL converting just this video.
+    pub fn url(&self) -> String {
+        format!("https://www.youtube.com/watch?v={}", self.video_id)
+    }
+}
+
+/// Where one track of a playlist job stands.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaylistTrack {
+    /// ID of the child job converting this track.
+    pub job_id: String,
+    /// `Artist - Title` once tagged, the video title before that.
+    #[serde(default)]
+    pub title: Option<String>,
+    pub status: JobStatus,
+    pub message: String,
+}
+
+/// Per-track status of a playlist job.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaylistStatus {
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Empty until the playlist has been listed.
+    #[serde(default)]
+    pub tracks: Vec<PlaylistTrack>,
+}
+
+impl PlaylistStatus {
+    /// Number of tracks that produced a file.
+    pub fn completed(&self) -> usize {
+        self.count(|status| status == JobStatus::Completed)
+    }
+
+    /// Number of tracks that stopped, successfully or not.
+    pub fn finished(&self) -> usize {
+        self.count(JobStatus::is_finished)
+    }
+
+    fn count(&self, matches: impl Fn(JobStatus) -> bool) -> usize {
+        self.tracks.iter().filter(|track| matches(track.status)).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(status: JobStatus) -> PlaylistTrack {
+        PlaylistTrack {
+            job_id: status.to_string(),
+            title: None,
+            status,
+            message: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_counts_tracks_by_status() {
+        let playlist = PlaylistStatus {
+            title: Some("Mix".to_string()),
+            tracks: vec![
+                track(JobStatus::Completed),
+                track(JobStatus::Failed),
+                track(JobStatus::Downloading),
+                track(JobStatus::Queued),
+                track(JobStatus::Completed),
+            ],
+        };
+        assert_eq!(playlist.completed(), 2);
+        assert_eq!(playlist.finished(), 3);
+    }
+
+    #[test]
+    fn test_entry_url() {
+        let entry = PlaylistEntry {
+            video_id: "dQw4w9WgXcQ".to_string(),
+            title: None,
+            duration_secs: None,
+        };
+        assert_eq!(entry.url(), "https://www.youtube.com/watch?v=dQw4w9WgXcQ");
+    }
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::entities::progress::format_duration;
+use crate::domain::entities::rate_limit::RateLimited;
+
+/// Who can watch a video, as far as converting it is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Availability {
+    Public,
+    Unlisted,
+    Private,
+    /// Only for YouTube Premium members.
+    PremiumOnly,
+    /// Only for members of the channel.
+    SubscriberOnly,
+    /// Needs a signed-in account, e.g. age-restricted videos.
+    NeedsAuth,
+    /// Streaming right now.
+    Live,
+    /// A premiere or stream that has not started.
+    Upcoming,
+}
+
+impl Availability {
+    /// Why a video with this availability cannot be converted, if it cannot.
+    pub fn blocker(self) -> Option<&'static str> {
+        match self {
+            Availability::Public | Availability::Unlisted => None,
+            Availability::Private => Some("This video is private."),
+            Availability::PremiumOnly => Some("This video is only available to YouTube Premium members."),
+            Availability::SubscriberOnly => Some("This video is only available to channel members."),
+            Availability::NeedsAuth => Some("This video needs a signed-in YouTube account, so it cannot be converted."),
+            Availability::Live => Some("This is a live stream. Please wait until it ends."),
+            Availability::Upcoming => Some("This video has not started yet. Please wait until it is available."),
+        }
+    }
+}
+
+/// A chapter marked in a video's description.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chapter {
+    pub title: String,
+    pub start_secs: u64,
+    pub end_secs: u64,
+}
+
+/// What a video is, looked up before converting it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VideoPreview {
+    pub video_id: String,
+    pub title: Option<String>,
+    pub channel: Option<String>,
+    pub duration_secs: Option<u64>,
+    pub thumbnail_url: Option<String>,
+    /// `None` when YouTube did not say.
+    pub availability: Option<Availability>,
+    #[serde(default)]
+    pub chapters: Vec<Chapter>,
+}
+
+impl VideoPreview {
+    /// Why the video cannot be converted, if it cannot.
+    pub fn blocker(&self) -> Option<&'static str> {
+        self.availability.and_then(Availability::blocker)
+    }
+
+    /// Length such as `3:33`, if known.
+    pub fn duration_label(&self) -> Option<String> {
+        self.duration_secs.map(format_duration)
+    }
+}
+
+/// Answer of the preview server function.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreviewResponse {
+    pub preview: Option<VideoPreview>,
+    /// Why there is no preview.
+    pub error: Option<String>,
+    /// Set when the request was turned away for being one too many.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimited>,
+}
+
+impl PreviewResponse {
+    pub fn failed(error: impl Into<String>) -> Self {
+        Self {
+            error: Some(error.into()),
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_watchable_videos_have_no_blocker() {
+        let preview = |availability| VideoPreview {
+            video_id: "dQw4w9WgXcQ".to_string(),
+            title: None,
+            channel: None,
+            duration_secs: Some(213),
+            thumbnail_url: None,
+            availability,
+            chapters: Vec::new(),
+        };
+        assert_eq!(preview(None).blocker(), None);
+        assert_eq!(preview(Some(Availability::Unlisted)).blocker(), None);
+        assert_eq!(preview(Some(Availability::Private)).blocker(), Some("This video is private."));
+        assert!(preview(Some(Availability::Live)).blocker().is_some());
+        assert_eq!(preview(None).duration_label().as_deref(), Some("3:33"));
+    }
+}
@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+
+/// Download progress of the job's current attempt.
+///
+/// Every field is optional because the extractor does not always know the
+/// total size, speed or remaining time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobProgress {
+    pub percent: Option<f32>,
+    pub downloaded_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+    pub bytes_per_sec: Option<f64>,
+    pub eta_secs: Option<u64>,
+}
+
+impl JobProgress {
+    /// Builds progress from raw counters, deriving the percentage.
+    pub fn from_bytes(
+        downloaded_bytes: Option<u64>,
+        total_bytes: Option<u64>,
+        bytes_per_sec: Option<f64>,
+        eta_secs: Option<u64>,
+    ) -> Self {
+        let percent = match (downloaded_bytes, total_bytes) {
+            (Some(done), Some(total)) if total > 0 => {
+                Some((done as f64 / total as f64 * 100.0).clamp(0.0, 100.0) as f32)
+            }
+            _ => None,
+        };
+        Self {
+            percent,
+            downloaded_bytes,
+            total_bytes,
+            bytes_per_sec,
+            eta_secs,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.percent.is_some_and(|percent| percent >= 100.0)
+    }
+
+    /// Short human-readable line such as `1.5 MiB of 4.0 MiB · 512.0 KiB/s · 0:05 left`.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        match (self.downloaded_bytes, self.total_bytes) {
+            (Some(done), Some(total)) => {
+                parts.push(format!("{} of {}", format_bytes(done), format_bytes(total)));
+            }
+            (Some(done), None) => parts.push(format_bytes(done)),
+            _ => {}
+        }
+        if let Some(speed) = self.bytes_per_sec {
+            parts.push(format!("{}/s", format_bytes(speed as u64)));
+        }
+        if let Some(eta) = self.eta_secs {
+            parts.push(format!("{} left", format_duration(eta)));
+        }
+        parts.join(" · ")
+    }
+}
+
+/// Formats a byte count with binary units, e.g. `3.2 MiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+/// Formats seconds as `m:ss`, or `h:mm:ss` from one hour up.
+pub fn format_duration(secs: u64) -> String {
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percent_is_derived_from_bytes() {
+        let progress = JobProgress::from_bytes(Some(512), Some(2048), None, None);
+        assert_eq!(progress.percent, Some(25.0));
+        assert!(!progress.is_complete());
+
+        assert_eq!(JobProgress::from_bytes(Some(5), None, None, None).percent, None);
+        assert!(JobProgress::from_bytes(Some(10), Some(10), None, None).is_complete());
+    }
+
+    #[test]
+    fn test_summary() {
+        let progress = JobProgress::from_bytes(
+            Some(3 * 1024 * 1024 / 2),
+            Some(4 * 1024 * 1024),
+            Some(512.0 * 1024.0),
+            Some(65),
+        );
+        assert_eq!(progress.summary(), "1.5 MiB of 4.0 MiB · 512.0 KiB/s · 1:05 left");
+        assert_eq!(JobProgress::default().summary(), "");
+        assert_eq!(format_duration(3725), "1:02:05");
+        assert_eq!(format_bytes(999), "999 B");
+    }
+}
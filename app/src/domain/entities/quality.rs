@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::entities::audio_format::AudioFormat;
+
+/// Constant bitrates offered for lossy formats, in kbit/s.
+pub const CBR_KBPS: [u32; 4] = [128, 192, 256, 320];
+
+/// Best and worst VBR quality levels, as in LAME's `-V` scale.
+pub const VBR_LEVELS: std::ops::RangeInclusive<u8> = 0..=9;
+
+/// Target bitrate of a lossy encode.
+///
+/// Serialized as a single token such as `cbr192` or `vbr2` so it survives
+/// form-encoded server function arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Bitrate {
+    /// Constant bitrate in kbit/s.
+    Cbr(u32),
+    /// Variable bitrate level, 0 being the best.
+    Vbr(u8),
+}
+
+impl Default for Bitrate {
+    fn default() -> Self {
+        Bitrate::Cbr(192)
+    }
+}
+
+impl Bitrate {
+    /// The bitrates the UI offers, best first within each mode.
+    pub const PRESETS: [Bitrate; 7] = [
+        Bitrate::Cbr(128),
+        Bitrate::Cbr(192),
+        Bitrate::Cbr(256),
+        Bitrate::Cbr(320),
+        Bitrate::Vbr(0),
+        Bitrate::Vbr(2),
+        Bitrate::Vbr(5),
+    ];
+
+    pub fn label(self) -> String {
+        match self {
+            Bitrate::Cbr(kbps) => format!("{kbps} kbps"),
+            Bitrate::Vbr(level) => format!("VBR V{level}"),
+        }
+    }
+}
+
+impl std::fmt::Display for Bitrate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Bitrate::Cbr(kbps) => write!(f, "cbr{kbps}"),
+            Bitrate::Vbr(level) => write!(f, "vbr{level}"),
+        }
+    }
+}
+
+impl std::str::FromStr for Bitrate {
+    type Err = InvalidQuality;
+
+    /// Parses the token syntax only; see [`QualityProfile::validate`] for
+    /// which values are accepted.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidQuality::Syntax(s.to_string());
+        if let Some(kbps) = s.strip_prefix("cbr") {
+            kbps.parse().map(Bitrate::Cbr).map_err(|_| invalid())
+        } else if let Some(level) = s.strip_prefix("vbr") {
+            level.parse().map(Bitrate::Vbr).map_err(|_| invalid())
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+impl From<Bitrate> for String {
+    fn from(bitrate: Bitrate) -> Self {
+        bitrate.to_string()
+    }
+}
+
+impl TryFrom<String> for Bitrate {
+    type Error = InvalidQuality;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SampleRate {
+    #[serde(rename = "44100")]
+    Hz44100,
+    #[serde(rename = "48000")]
+    Hz48000,
+}
+
+impl SampleRate {
+    pub const ALL: [SampleRate; 2] = [SampleRate::Hz44100, SampleRate::Hz48000];
+
+    pub fn hz(self) -> u32 {
+        match self {
+            SampleRate::Hz44100 => 44_100,
+            SampleRate::Hz48000 => 48_000,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SampleRate::Hz44100 => "44.1 kHz",
+            SampleRate::Hz48000 => "48 kHz",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channels {
+    Mono,
+    Stereo,
+}
+
+impl Channels {
+    pub const ALL: [Channels; 2] = [Channels::Stereo, Channels::Mono];
+
+    pub fn count(self) -> u8 {
+        match self {
+            Channels::Mono => 1,
+            Channels::Stereo => 2,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Channels::Mono => "Mono",
+            Channels::Stereo => "Stereo",
+        }
+    }
+}
+
+/// Encoder settings requested for a job.
+///
+/// `None` keeps the source's sample rate or channel layout. The bitrate is
+/// ignored for lossless formats.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct QualityProfile {
+    #[serde(default)]
+    pub bitrate: Bitrate,
+    #[serde(default)]
+    pub sample_rate: Option<SampleRate>,
+    #[serde(default)]
+    pub channels: Option<Channels>,
+}
+
+impl QualityProfile {
+    /// Checks that the profile can be produced in `format`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for bitrates outside [`CBR_KBPS`] and
+    /// [`VBR_LEVELS`], and for sample rates the format cannot store.
+    pub fn validate(&self, format: AudioFormat) -> Result<(), InvalidQuality> {
+        match self.bitrate {
+            Bitrate::Cbr(kbps) if !CBR_KBPS.contains(&kbps) => {
+                return Err(InvalidQuality::Bitrate(kbps));
+            }
+            Bitrate::Vbr(level) if !VBR_LEVELS.contains(&level) => {
+                return Err(InvalidQuality::VbrLevel(level));
+            }
+            _ => {}
+        }
+        if format == AudioFormat::Opus && self.sample_rate == Some(SampleRate::Hz44100) {
+            return Err(InvalidQuality::SampleRate { format, rate: SampleRate::Hz44100 });
+        }
+        Ok(())
+    }
+
+    /// Short description such as `320 kbps · 48 kHz · Mono`.
+    pub fn summary(&self, format: AudioFormat) -> String {
+        let mut parts = Vec::new();
+        if !format.is_lossless() {
+            parts.push(self.bitrate.label());
+        }
+        if let Some(rate) = self.sample_rate {
+            parts.push(rate.label().to_string());
+        }
+        if let Some(channels) = self.channels {
+            parts.push(channels.label().to_string());
+        }
+        parts.join(" · ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidQuality {
+    #[error("`{0}` is not a valid bitrate")]
+    Syntax(String),
+    #[error("Unsupported bitrate {0} kbps; choose 128, 192, 256 or 320")]
+    Bitrate(u32),
+    #[error("Unsupported VBR level {0}; choose a level from 0 (best) to 9")]
+    VbrLevel(u8),
+    #[error("{} does not support a sample rate of {}", format.label(), rate.label())]
+    SampleRate { format: AudioFormat, rate: SampleRate },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitrate_tokens_round_trip() {
+        for bitrate in Bitrate::PRESETS {
+            assert_eq!(bitrate.to_string().parse::<Bitrate>().unwrap(), bitrate);
+        }
+        assert_eq!(serde_json::to_string(&Bitrate::Cbr(320)).unwrap(), "\"cbr320\"");
+        assert!("320k".parse::<Bitrate>().is_err());
+        assert!(serde_json::from_str::<Bitrate>("\"vbrx\"").is_err());
+    }
+
+    #[test]
+    fn test_validate_rejects_unsupported_settings() {
+        let profile = |bitrate, sample_rate| QualityProfile {
+            bitrate,
+            sample_rate,
+            channels: None,
+        };
+
+        assert_eq!(QualityProfile::default().validate(AudioFormat::Mp3), Ok(()));
+        assert_eq!(
+            profile(Bitrate::Cbr(999), None).validate(AudioFormat::Mp3),
+            Err(InvalidQuality::Bitrate(999))
+        );
+        assert_eq!(
+            profile(Bitrate::Vbr(10), None).validate(AudioFormat::Vorbis),
+            Err(InvalidQuality::VbrLevel(10))
+        );
+        assert!(profile(Bitrate::Cbr(128), Some(SampleRate::Hz44100))
+            .validate(AudioFormat::Opus)
+            .is_err());
+        assert_eq!(
+            profile(Bitrate::Cbr(128), Some(SampleRate::Hz48000)).validate(AudioFormat::Opus),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_summary_skips_bitrate_for_lossless_formats() {
+        let profile = QualityProfile {
+            bitrate: Bitrate::Cbr(320),
+            sample_rate: Some(SampleRate::Hz48000),
+            channels: Some(Channels::Mono),
+        };
+        assert_eq!(profile.summary(AudioFormat::Mp3), "320 kbps · 48 kHz · Mono");
+        assert_eq!(profile.summary(AudioFormat::Flac), "48 kHz · Mono");
+    }
+}
@@ -0,0 +1,106 @@
+use serde::{Deserialize, Serialize};
+
+/// How much of one quota a user has used.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Meter {
+    pub used: u64,
+    /// `None` when the user's role has no such quota.
+    pub limit: Option<u64>,
+}
+
+impl Meter {
+    pub fn remaining(&self) -> Option<u64> {
+        self.limit.map(|limit| limit.saturating_sub(self.used))
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining() == Some(0)
+    }
+}
+
+/// A user's usage against their quotas. Conversions and audio minutes count
+/// since midnight UTC; stored bytes are the files they can still download.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    pub conversions: Meter,
+    pub audio_minutes: Meter,
+    pub stored_bytes: Meter,
+    /// When the daily quotas start over, in milliseconds since the Unix epoch.
+    pub resets_at_ms: u64,
+}
+
+impl Usage {
+    /// Why no conversion may be started, if a quota is used up.
+    pub fn exhausted(&self) -> Option<String> {
+        if self.conversions.is_exhausted() {
+            Some(format!(
+                "You have used all {} of today's conversions. Your quota resets at midnight UTC.",
+                self.conversions.used
+            ))
+        } else if self.audio_minutes.is_exhausted() {
+            Some(format!(
+                "You have converted {} minutes of audio today, your daily limit. Your quota resets at midnight UTC.",
+                self.audio_minutes.used
+            ))
+        } else if self.stored_bytes.is_exhausted() {
+            Some("Your converted files use up all of your storage. They are removed after a while; please try again later.".to_string())
+        } else {
+            None
+        }
+    }
+
+    /// What is left, for the navbar, e.g. `7 conversions · 48 min · 460 MB left`.
+    /// `None` when nothing is limited.
+    pub fn remaining_label(&self) -> Option<String> {
+        let parts: Vec<String> = [
+            self.conversions.remaining().map(|n| {
+                format!("{n} conversion{}", if n == 1 { "" } else { "s" })
+            }),
+            self.audio_minutes.remaining().map(|n| format!("{n} min")),
+            self.stored_bytes
+                .remaining()
+                .map(|n| format!("{} MB", n / (1024 * 1024))),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        (!parts.is_empty()).then(|| format!("{} left", parts.join(" · ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meter(used: u64, limit: Option<u64>) -> Meter {
+        Meter { used, limit }
+    }
+
+    #[test]
+    fn test_remaining_label_skips_unlimited_quotas() {
+        let usage = Usage {
+            conversions: meter(9, Some(10)),
+            audio_minutes: meter(12, None),
+            stored_bytes: meter(40 * 1024 * 1024, Some(500 * 1024 * 1024)),
+            resets_at_ms: 0,
+        };
+        assert_eq!(usage.remaining_label().as_deref(), Some("1 conversion · 460 MB left"));
+        assert_eq!(usage.exhausted(), None);
+        assert_eq!(Usage::default().remaining_label(), None);
+    }
+
+    #[test]
+    fn test_exhausted_names_the_first_quota_used_up() {
+        let usage = Usage {
+            conversions: meter(3, Some(10)),
+            audio_minutes: meter(65, Some(60)),
+            stored_bytes: meter(0, Some(0)),
+            resets_at_ms: 0,
+        };
+        assert_eq!(usage.audio_minutes.remaining(), Some(0));
+        assert_eq!(
+            usage.exhausted().as_deref(),
+            Some("You have converted 65 minutes of audio today, your daily limit. Your quota resets at midnight UTC.")
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+/// Whose requests used up the allowance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitScope {
+    /// The signed-in account.
+    User,
+    /// Everyone behind the client's IP address.
+    Ip,
+}
+
+/// A request turned away because its sender has made too many lately.
+///
+/// Sent in [`ConvertResponse`](crate::domain::services::video_converter::ConvertResponse)
+/// by the convert server function and as the JSON body of a 429 by the
+/// download routes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimited {
+    pub scope: RateLimitScope,
+    /// Seconds until the request would be allowed.
+    pub retry_after_secs: u64,
+}
+
+impl RateLimited {
+    /// What to tell the user.
+    pub fn message(&self) -> String {
+        let from = match self.scope {
+            RateLimitScope::User => "your account",
+            RateLimitScope::Ip => "your network",
+        };
+        let wait = match self.retry_after_secs {
+            0 | 1 => "a second".to_string(),
+            secs if secs < 120 => format!("{secs} seconds"),
+            secs => format!("{} minutes", secs.div_ceil(60)),
+        };
+        format!("Too many requests from {from}. Please try again in {wait}.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_rounds_long_waits_to_minutes() {
+        let limited = |scope, retry_after_secs| RateLimited {
+            scope,
+            retry_after_secs,
+        };
+        assert_eq!(
+            limited(RateLimitScope::User, 1).message(),
+            "Too many requests from your account. Please try again in a second."
+        );
+        assert_eq!(
+            limited(RateLimitScope::Ip, 45).message(),
+            "Too many requests from your network. Please try again in 45 seconds."
+        );
+        assert_eq!(
+            limited(RateLimitScope::User, 301).message(),
+            "Too many requests from your account. Please try again in 6 minutes."
+        );
+    }
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+/// Longest tag value accepted from users.
+pub const MAX_TAG_LEN: usize = 200;
+
+/// Tags written into the output file.
+///
+/// Used both for the tags derived from the video and for the user's
+/// overrides, where an empty field means "keep what was derived".
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TrackTags {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub album: Option<String>,
+    /// Release year, e.g. `2009`.
+    #[serde(default)]
+    pub year: Option<String>,
+}
+
+impl TrackTags {
+    pub fn is_empty(&self) -> bool {
+        self.fields().iter().all(|(_, value)| value.is_none())
+    }
+
+    /// Trims every field and drops the empty ones.
+    pub fn normalized(self) -> Self {
+        let clean = |value: Option<String>| {
+            value
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        Self {
+            title: clean(self.title),
+            artist: clean(self.artist),
+            album: clean(self.album),
+            year: clean(self.year),
+        }
+    }
+
+    /// Replaces every field that `overrides` sets.
+    pub fn overridden_by(self, overrides: &TrackTags) -> Self {
+        Self {
+            title: overrides.title.clone().or(self.title),
+            artist: overrides.artist.clone().or(self.artist),
+            album: overrides.album.clone().or(self.album),
+            year: overrides.year.clone().or(self.year),
+        }
+    }
+
+    /// Checks user-supplied tags; call on [`TrackTags::normalized`] values.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a value is too long or the year is not a
+    /// four-digit number.
+    pub fn validate(&self) -> Result<(), InvalidTags> {
+        for (name, value) in self.fields() {
+            if value.is_some_and(|value| value.chars().count() > MAX_TAG_LEN) {
+                return Err(InvalidTags::TooLong(name));
+            }
+        }
+        if let Some(year) = &self.year {
+            if year.len() != 4 || !year.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(InvalidTags::Year(year.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// `Artist - Title`, or whichever of the two is known.
+    pub fn display_name(&self) -> Option<String> {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => Some(format!("{artist} - {title}")),
+            (None, Some(title)) => Some(title.clone()),
+            (Some(artist), None) => Some(artist.clone()),
+            (None, None) => None,
+        }
+    }
+
+    fn fields(&self) -> [(&'static str, Option<&String>); 4] {
+        [
+            ("title", self.title.as_ref()),
+            ("artist", self.artist.as_ref()),
+            ("album", self.album.as_ref()),
+            ("year", self.year.as_ref()),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidTags {
+    #[error("The {0} tag is too long")]
+    TooLong(&'static str),
+    #[error("`{0}` is not a valid year")]
+    Year(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_replace_only_set_fields() {
+        let derived = TrackTags {
+            title: Some("Never Gonna Give You Up".to_string()),
+            artist: Some("Rick Astley".to_string()),
+            album: None,
+            year: Some("1987".to_string()),
+        };
+        let overrides = TrackTags {
+            album: Some(" Whenever You Need Somebody ".to_string()),
+            year: Some("".to_string()),
+            ..TrackTags::default()
+        }
+        .normalized();
+
+        let tags = derived.overridden_by(&overrides);
+        assert_eq!(tags.album.as_deref(), Some("Whenever You Need Somebody"));
+        assert_eq!(tags.year.as_deref(), Some("1987"));
+        assert_eq!(
+            tags.display_name().as_deref(),
+            Some("Rick Astley - Never Gonna Give You Up")
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(TrackTags::default().validate(), Ok(()));
+        let year = |year: &str| TrackTags {
+            year: Some(year.to_string()),
+            ..TrackTags::default()
+        };
+        assert_eq!(year("1987").validate(), Ok(()));
+        assert!(year("87").validate().is_err());
+        let long = TrackTags {
+            title: Some("x".repeat(MAX_TAG_LEN + 1)),
+            ..TrackTags::default()
+        };
+        assert_eq!(long.validate(), Err(InvalidTags::TooLong("title")));
+    }
+}
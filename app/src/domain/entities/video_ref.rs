@@ -0,0 +1,397 @@
+use url::Url;
+
+use crate::domain::entities::clip::parse_timestamp;
+
+/// Hosts serving YouTube pages that link a video or playlist.
+const YOUTUBE_HOSTS: [&str; 4] = [
+    "youtube.com",
+    "www.youtube.com",
+    "m.youtube.com",
+    "music.youtube.com",
+];
+
+/// Hosts serving only embedded players.
+const NOCOOKIE_HOSTS: [&str; 2] = ["youtube-nocookie.com", "www.youtube-nocookie.com"];
+
+/// Host of YouTube's short links.
+const SHORT_LINK_HOST: &str = "youtu.be";
+
+/// Path prefixes followed by a video ID, e.g. `/shorts/dQw4w9WgXcQ`.
+const VIDEO_PATHS: [&str; 4] = ["shorts", "live", "embed", "v"];
+
+/// Longest playlist ID accepted. Real ones have at most 41 characters.
+const MAX_PLAYLIST_ID_LEN: usize = 64;
+
+/// What a YouTube link points at, taken apart from the link.
+///
+/// At least one of `video_id` and `playlist_id` is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoRef {
+    /// The 11-character video ID. `None` for a playlist page.
+    pub video_id: Option<String>,
+    /// The `list=` parameter, for a playlist page or a video in a playlist.
+    pub playlist_id: Option<String>,
+    /// Where the link starts playing, from its `t=` or `start=` parameter.
+    pub start_secs: Option<u64>,
+}
+
+impl VideoRef {
+    /// Parses a watch, shorts, live, embed, `/v/`, `youtu.be` or playlist
+    /// link on YouTube, YouTube Music or youtube-nocookie.com. A missing
+    /// scheme is taken to be `https://`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `url` is not a link to a YouTube video or
+    /// playlist, or its video or playlist ID is malformed.
+    pub fn parse(url: &str) -> Result<Self, InvalidVideoUrl> {
+        let url = url.trim();
+        let parsed = match Url::parse(url) {
+            Ok(parsed) => parsed,
+            Err(url::ParseError::RelativeUrlWithoutBase) => {
+                Url::parse(&format!("https://{url}")).map_err(|_| InvalidVideoUrl::NotAUrl)?
+            }
+            Err(_) => return Err(InvalidVideoUrl::NotAUrl),
+        };
+        if !matches!(parsed.scheme(), "http" | "https") || parsed.port().is_some() {
+            return Err(InvalidVideoUrl::NotAUrl);
+        }
+        let host = parsed.host_str().ok_or(InvalidVideoUrl::NotAUrl)?;
+
+        let segments: Vec<&str> = parsed
+            .path_segments()
+            .map(|segments| segments.filter(|segment| !segment.is_empty()).collect())
+            .unwrap_or_default();
+        let video_id = if host == SHORT_LINK_HOST {
+            match segments.as_slice() {
+                [id] => Some(id.to_string()),
+                _ => return Err(InvalidVideoUrl::Path),
+            }
+        } else if YOUTUBE_HOSTS.contains(&host) {
+            match segments.as_slice() {
+                ["watch"] => Some(query(&parsed, "v").ok_or(InvalidVideoUrl::Path)?),
+                ["playlist"] => None,
+                [prefix, id] if VIDEO_PATHS.contains(prefix) => Some(id.to_string()),
+                _ => return Err(InvalidVideoUrl::Path),
+            }
+        } else if NOCOOKIE_HOSTS.contains(&host) {
+            match segments.as_slice() {
+                ["embed", id] => Some(id.to_string()),
+                _ => return Err(InvalidVideoUrl::Path),
+            }
+        } else {
+            return Err(InvalidVideoUrl::Host(host.to_string()));
+        };
+        let video_id = video_id.map(validate_video_id).transpose()?;
+
+        let playlist_id = match query(&parsed, "list") {
+            Some(id) => Some(validate_playlist_id(id)?),
+            // Like a watch page without a video, a playlist page without a
+            // list points at nothing
+            None if video_id.is_none() => return Err(InvalidVideoUrl::Path),
+            None => None,
+        };
+
+        // Shared links put the time in the query, older ones in the fragment
+        let fragment = parsed.fragment().unwrap_or_default();
+        let start_secs = ["t", "start"]
+            .into_iter()
+            .filter_map(|key| {
+                query(&parsed, key).or_else(|| {
+                    url::form_urlencoded::parse(fragment.as_bytes())
+                        .find(|(name, _)| name == key)
+                        .map(|(_, value)| value.into_owned())
+                })
+            })
+            .find_map(|value| parse_timestamp(&value).ok());
+
+        Ok(Self {
+            video_id,
+            playlist_id,
+            start_secs,
+        })
+    }
+
+    /// Whether the link is a playlist page rather than a video.
+    pub fn is_playlist(&self) -> bool {
+        self.video_id.is_none()
+    }
+
+    /// The watch link of the video alone, without its playlist.
+    pub fn video_url(&self) -> Option<String> {
+        let id = self.video_id.as_ref()?;
+        Some(format!("https://www.youtube.com/watch?v={id}"))
+    }
+
+    /// A link to the whole playlist. Keeps the video so playlists that
+    /// only exist around one, such as mixes, still resolve.
+    pub fn playlist_url(&self) -> Option<String> {
+        let list = self.playlist_id.as_ref()?;
+        Some(match &self.video_id {
+            Some(id) => format!("https://www.youtube.com/watch?v={id}&list={list}"),
+            None => format!("https://www.youtube.com/playlist?list={list}"),
+        })
+    }
+}
+
+/// The first non-empty value of a query parameter.
+fn query(url: &Url, key: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(name, value)| name == key && !value.is_empty())
+        .map(|(_, value)| value.into_owned())
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+fn validate_video_id(id: String) -> Result<String, InvalidVideoUrl> {
+    if id.len() == 11 && id.chars().all(is_id_char) {
+        Ok(id)
+    } else {
+        Err(InvalidVideoUrl::VideoId(id))
+    }
+}
+
+fn validate_playlist_id(id: String) -> Result<String, InvalidVideoUrl> {
+    if (2..=MAX_PLAYLIST_ID_LEN).contains(&id.len()) && id.chars().all(is_id_char) {
+        Ok(id)
+    } else {
+        Err(InvalidVideoUrl::PlaylistId(id))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidVideoUrl {
+    #[error("This is not a web address")]
+    NotAUrl,
+    #[error("{0} is not a YouTube address")]
+    Host(String),
+    #[error("This link does not point to a video or playlist")]
+    Path,
+    #[error("`{0}` is not a valid video ID")]
+    VideoId(String),
+    #[error("`{0}` is not a valid playlist ID")]
+    PlaylistId(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const ID: &str = "dQw4w9WgXcQ";
+    const LIST: &str = "PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI";
+
+    fn video(start_secs: Option<u64>) -> VideoRef {
+        VideoRef {
+            video_id: Some(ID.to_string()),
+            playlist_id: None,
+            start_secs,
+        }
+    }
+
+    #[test]
+    fn test_parses_every_kind_of_link() {
+        let in_playlist = VideoRef {
+            playlist_id: Some(LIST.to_string()),
+            ..video(None)
+        };
+        let playlist = VideoRef {
+            video_id: None,
+            playlist_id: Some(LIST.to_string()),
+            start_secs: None,
+        };
+        let cases = [
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQ", video(None)),
+            ("http://youtube.com/watch?feature=share&v=dQw4w9WgXcQ", video(None)),
+            ("www.youtube.com/watch?v=dQw4w9WgXcQ", video(None)),
+            ("  https://m.youtube.com/watch?v=dQw4w9WgXcQ  ", video(None)),
+            ("https://m.youtube.com/watch?feature=share&v=dQw4w9WgXcQ#t=1", video(Some(1))),
+            ("https://music.youtube.com/watch?v=dQw4w9WgXcQ", video(None)),
+            ("https://WWW.YouTube.com/watch?v=dQw4w9WgXcQ", video(None)),
+            ("https://youtu.be/dQw4w9WgXcQ", video(None)),
+            ("https://youtu.be/dQw4w9WgXcQ?t=83", video(Some(83))),
+            ("https://www.youtube.com/shorts/dQw4w9WgXcQ/", video(None)),
+            ("https://www.youtube.com/live/dQw4w9WgXcQ?feature=share", video(None)),
+            ("https://www.youtube.com/embed/dQw4w9WgXcQ?start=30", video(Some(30))),
+            ("https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ", video(None)),
+            ("https://www.youtube.com/v/dQw4w9WgXcQ", video(None)),
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=1m23s", video(Some(83))),
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQ#t=1:23", video(Some(83))),
+            ("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=soon", video(None)),
+            (
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI&index=2",
+                in_playlist,
+            ),
+            (
+                "https://www.youtube.com/playlist?list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI",
+                playlist.clone(),
+            ),
+            (
+                "https://music.youtube.com/playlist?list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI",
+                playlist,
+            ),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(VideoRef::parse(url), Ok(expected), "{url}");
+        }
+    }
+
+    #[test]
+    fn test_rejects_other_links() {
+        let host = |host: &str| Err(InvalidVideoUrl::Host(host.to_string()));
+        let cases = [
+            (
+                "https://evil.example/?x=youtu.be/dQw4w9WgXcQ",
+                host("evil.example"),
+            ),
+            (
+                "https://evil.example/youtube.com/watch?v=dQw4w9WgXcQ",
+                host("evil.example"),
+            ),
+            (
+                "https://youtube.com.evil.example/watch?v=dQw4w9WgXcQ",
+                host("youtube.com.evil.example"),
+            ),
+            (
+                "https://www.youtube.com@evil.example/watch?v=dQw4w9WgXcQ",
+                host("evil.example"),
+            ),
+            (
+                "https://example.com/watch?v=dQw4w9WgXcQ",
+                host("example.com"),
+            ),
+            ("https://www.google.com", host("www.google.com")),
+            (
+                "ftp://youtube.com/watch?v=dQw4w9WgXcQ",
+                Err(InvalidVideoUrl::NotAUrl),
+            ),
+            (
+                "https://youtube.com:8080/watch?v=dQw4w9WgXcQ",
+                Err(InvalidVideoUrl::NotAUrl),
+            ),
+            ("", Err(InvalidVideoUrl::NotAUrl)),
+            (
+                "https://www.youtube.com/feed/subscriptions",
+                Err(InvalidVideoUrl::Path),
+            ),
+            (
+                "https://www.youtube.com/watch?list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI",
+                Err(InvalidVideoUrl::Path),
+            ),
+            (
+                "https://www.youtube-nocookie.com/watch?v=dQw4w9WgXcQ",
+                Err(InvalidVideoUrl::Path),
+            ),
+            (
+                "https://youtu.be/dQw4w9WgXcQ/extra",
+                Err(InvalidVideoUrl::Path),
+            ),
+            (
+                "https://www.youtube.com/watch?v=short",
+                Err(InvalidVideoUrl::VideoId("short".to_string())),
+            ),
+            (
+                "https://www.youtube.com/shorts/dQw4w9WgXc!",
+                Err(InvalidVideoUrl::VideoId("dQw4w9WgXc!".to_string())),
+            ),
+            (
+                "https://www.youtube.com/playlist?list=",
+                Err(InvalidVideoUrl::Path),
+            ),
+            (
+                "https://www.youtube.com/playlist?list=PL%20x",
+                Err(InvalidVideoUrl::PlaylistId("PL x".to_string())),
+            ),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(VideoRef::parse(url), expected, "{url}");
+        }
+    }
+
+    #[test]
+    fn test_canonical_urls_drop_everything_else() {
+        let parsed = VideoRef::parse(
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ&list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI&si=xyz",
+        )
+        .unwrap();
+        assert_eq!(
+            parsed.video_url().as_deref(),
+            Some("https://www.youtube.com/watch?v=dQw4w9WgXcQ")
+        );
+        assert_eq!(
+            parsed.playlist_url().as_deref(),
+            Some("https://www.youtube.com/watch?v=dQw4w9WgXcQ&list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI")
+        );
+
+        let playlist = VideoRef::parse(&format!(
+            "https://www.youtube.com/playlist?list={LIST}&si=xyz"
+        ))
+        .unwrap();
+        assert!(playlist.is_playlist());
+        assert_eq!(playlist.video_url(), None);
+        assert_eq!(
+            playlist.playlist_url().as_deref(),
+            Some("https://www.youtube.com/playlist?list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI")
+        );
+    }
+
+    fn id_strategy(len: usize) -> impl Strategy<Value = String> {
+        proptest::string::string_regex(&format!("[A-Za-z0-9_-]{{{len}}}")).unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn prop_every_link_form_yields_its_video_id(
+            id in id_strategy(11),
+            form in 0..6usize,
+            start in proptest::option::of(0..100_000u64),
+        ) {
+            let url = match form {
+                0 => format!("https://www.youtube.com/watch?v={id}"),
+                1 => format!("https://youtu.be/{id}"),
+                2 => format!("https://m.youtube.com/shorts/{id}"),
+                3 => format!("https://music.youtube.com/watch?v={id}"),
+                4 => format!("https://www.youtube-nocookie.com/embed/{id}"),
+                _ => format!("https://www.youtube.com/live/{id}"),
+            };
+            let url = match start {
+                Some(start) if url.contains('?') => format!("{url}&t={start}"),
+                Some(start) => format!("{url}?t={start}"),
+                None => url,
+            };
+            let parsed = VideoRef::parse(&url).unwrap();
+            prop_assert_eq!(parsed.video_id.as_deref(), Some(id.as_str()));
+            prop_assert_eq!(parsed.start_secs, start);
+            // The canonical link parses back to the same video
+            let canonical = VideoRef::parse(&parsed.video_url().unwrap()).unwrap();
+            prop_assert_eq!(canonical.video_id, parsed.video_id);
+        }
+
+        #[test]
+        fn prop_ids_of_other_lengths_are_rejected(
+            id in (0..30usize).prop_filter("not 11", |len| *len != 11).prop_flat_map(id_strategy),
+        ) {
+            let parsed = VideoRef::parse(&format!("https://www.youtube.com/watch?v={id}"));
+            prop_assert!(parsed.is_err());
+        }
+
+        #[test]
+        fn prop_other_hosts_are_rejected(
+            host in "[a-z]{1,10}\\.(com|example|net)",
+            path in "[a-z/?=.]{0,30}",
+        ) {
+            prop_assume!(
+                !YOUTUBE_HOSTS.contains(&host.as_str()) && !NOCOOKIE_HOSTS.contains(&host.as_str())
+            );
+            let parsed = VideoRef::parse(&format!("https://{host}/{path}youtu.be/{ID}?v={ID}"));
+            prop_assert!(parsed.is_err());
+        }
+
+        #[test]
+        fn prop_never_panics(input in "\\PC{0,80}") {
+            let _ = VideoRef::parse(&input);
+        }
+    }
+}
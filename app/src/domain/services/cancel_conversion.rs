@@ -0,0 +1,43 @@
+use leptos::prelude::*;
+
+use crate::domain::services::video_converter::ConvertResponse;
+
+/// Cancels a queued or running conversion and returns the job's new status.
+#[server(CancelConversion, "/api")]
+pub async fn cancel_conversion(job_id: String) -> Result<ConvertResponse, ServerFnError> {
+    #[cfg(feature = "ssr")]
+    {
+        use crate::domain::services::session::{access_denied, current_user};
+        use crate::domain::services::video_converter::server::{
+            cancel_conversion, check_access, get_job_status, CancelError,
+        };
+
+        let user_id = current_user().map(|user| user.user_id);
+        if let Err(e) = check_access(&job_id, user_id.as_deref()).await {
+            return Err(access_denied(&e));
+        }
+
+        match cancel_conversion(&job_id).await {
+            // A job that already finished reports how it ended instead
+            Ok(_) | Err(CancelError::Finished(_)) => match get_job_status(&job_id).await {
+                Ok(status) => Ok(status),
+                Err(e) => Ok(ConvertResponse::failed(
+                    job_id,
+                    format!("Failed to check status: {e}"),
+                )),
+            },
+            Err(e @ CancelError::NotFound) => Ok(ConvertResponse::failed(job_id, e.to_string())),
+            Err(e) => Ok(ConvertResponse::failed(
+                job_id,
+                format!("Failed to cancel conversion: {e}"),
+            )),
+        }
+    }
+
+    #[cfg(not(feature = "ssr"))]
+    {
+        Err(ServerFnError::new(
+            "Server function not available on client",
+        ))
+    }
+}
@@ -3,21 +3,27 @@ use leptos::prelude::*;
 #[cfg(feature = "hydrate")]
 use gloo_timers::future::sleep;
 
+use crate::domain::entities::job_status::JobStatus;
 use crate::domain::services::video_converter::ConvertResponse;
 
 #[server(CheckStatus, "/api")]
 pub async fn check_status(job_id: String) -> Result<ConvertResponse, ServerFnError> {
     #[cfg(feature = "ssr")]
     {
-        use crate::domain::services::video_converter::server::get_job_status;
+        use crate::domain::services::session::{access_denied, current_user};
+        use crate::domain::services::video_converter::server::{check_access, get_job_status};
+
+        let user_id = current_user().map(|user| user.user_id);
+        if let Err(e) = check_access(&job_id, user_id.as_deref()).await {
+            return Err(access_denied(&e));
+        }
 
         match get_job_status(&job_id).await {
             Ok(status) => Ok(status),
-            Err(e) => Ok(ConvertResponse {
-                id: job_id,
-                status: "error".to_string(),
-                message: format!("Failed to check status: {e}"),
-            }),
+            Err(e) => Ok(ConvertResponse::failed(
+                job_id,
+                format!("Failed to check status: {e}"),
+            )),
         }
     }
 
@@ -29,37 +35,126 @@ pub async fn check_status(job_id: String) -> Result<ConvertResponse, ServerFnErr
     }
 }
 
-pub async fn poll_conversion_status(
-    job_id: String,
-    is_converting: RwSignal<bool>,
-    download_url: RwSignal<Option<String>>,
-    error_message: RwSignal<Option<String>>,
-) {
+/// Page state that follows a running conversion.
+#[derive(Debug, Clone, Copy)]
+pub struct StatusSignals {
+    pub is_converting: RwSignal<bool>,
+    pub download_url: RwSignal<Option<String>>,
+    pub error_message: RwSignal<Option<String>>,
+    pub latest_status: RwSignal<Option<ConvertResponse>>,
+}
+
+impl StatusSignals {
+    /// Shows a status update. Returns `true` once the job has finished.
+    pub fn apply(&self, job_id: &str, response: ConvertResponse) -> bool {
+        self.latest_status.set(Some(response.clone()));
+        match response.status {
+            JobStatus::Completed => {
+                self.is_converting.set(false);
+                let url = match response.playlist {
+                    Some(_) => format!("/api/playlists/{job_id}/zip"),
+                    None => format!("/api/download/{job_id}"),
+                };
+                self.download_url.set(Some(url));
+                true
+            }
+            JobStatus::Failed | JobStatus::Cancelled | JobStatus::Expired => {
+                self.is_converting.set(false);
+                self.error_message.set(Some(response.message));
+                true
+            }
+            JobStatus::Queued | JobStatus::Downloading | JobStatus::Transcoding => false,
+        }
+    }
+}
+
+/// Follows a job until it finishes, through the server-sent events at
+/// `/api/jobs/{id}/events` when the browser supports them and by polling
+/// [`check_status`] otherwise.
+pub async fn watch_conversion_status(job_id: String, signals: StatusSignals) {
+    #[cfg(feature = "hydrate")]
+    match subscribe_to_status_events(&job_id, signals) {
+        Ok(()) => return,
+        Err(e) => leptos::logging::log!("Status events unavailable, polling instead: {:?}", e),
+    }
+
+    poll_conversion_status(job_id, signals).await;
+}
+
+/// Applies every `status` event of the job's event stream to `signals`.
+///
+/// If the stream fails before the job has finished, e.g. because a proxy
+/// does not pass server-sent events through, it is closed and the page falls
+/// back to [`poll_conversion_status`].
+#[cfg(feature = "hydrate")]
+fn subscribe_to_status_events(
+    job_id: &str,
+    signals: StatusSignals,
+) -> Result<(), leptos::wasm_bindgen::JsValue> {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use leptos::wasm_bindgen::{closure::Closure, JsCast};
+    use web_sys::{Event, EventSource, MessageEvent};
+
+    let source = EventSource::new(&format!("/api/jobs/{job_id}/events"))?;
+    // Set once the job finished or polling took over, so neither happens twice
+    let done = Rc::new(Cell::new(false));
+
+    let on_status = Closure::<dyn FnMut(MessageEvent)>::new({
+        let (source, done, job_id) = (source.clone(), done.clone(), job_id.to_string());
+        move |event: MessageEvent| {
+            let Some(data) = event.data().as_string() else {
+                return;
+            };
+            match serde_json::from_str::<ConvertResponse>(&data) {
+                Ok(response) => {
+                    if signals.apply(&job_id, response) {
+                        done.set(true);
+                        source.close();
+                    }
+                }
+                Err(e) => leptos::logging::log!("Ignoring malformed status event: {}", e),
+            }
+        }
+    });
+
+    let on_error = Closure::<dyn FnMut(Event)>::new({
+        let (source, done, job_id) = (source.clone(), done.clone(), job_id.to_string());
+        move |_: Event| {
+            source.close();
+            if !done.replace(true) {
+                leptos::task::spawn_local(poll_conversion_status(job_id.clone(), signals));
+            }
+        }
+    });
+
+    source.add_event_listener_with_callback("status", on_status.as_ref().unchecked_ref())?;
+    source.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+    // The source holds the only references to the handlers from here on;
+    // they are tiny and live for the rest of the page
+    on_status.forget();
+    on_error.forget();
+    Ok(())
+}
+
+/// Checks the job's status every two seconds until it finishes.
+pub async fn poll_conversion_status(job_id: String, signals: StatusSignals) {
     loop {
         #[cfg(feature = "hydrate")]
         sleep(std::time::Duration::from_secs(2)).await;
 
         match check_status(job_id.clone()).await {
             Ok(response) => {
-                match response.status.as_str() {
-                    "completed" => {
-                        is_converting.set(false);
-                        download_url.set(Some(format!("/api/download/{job_id}")));
-                        break;
-                    }
-                    "error" => {
-                        is_converting.set(false);
-                        error_message.set(Some(response.message));
-                        break;
-                    }
-                    _ => {
-                        // Still processing, continue polling
-                    }
+                if signals.apply(&job_id, response) {
+                    break;
                 }
             }
             Err(e) => {
-                is_converting.set(false);
-                error_message.set(Some(format!("Failed to check status: {e}")));
+                signals.is_converting.set(false);
+                signals
+                    .error_message
+                    .set(Some(format!("Failed to check status: {e}")));
                 break;
             }
         }
@@ -0,0 +1,60 @@
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ConfigFileError {
+    #[error("failed to read {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid TOML in {path}: {source}")]
+    Toml {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("invalid JSON in {path}: {source}")]
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("{0} must have a .toml or .json extension")]
+    UnknownFormat(PathBuf),
+}
+
+/// Reads a `.toml` or `.json` settings file, picking the format by extension.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or parsed.
+pub async fn load<T: DeserializeOwned>(path: &Path) -> Result<T, ConfigFileError> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|source| ConfigFileError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    parse(path, &contents)
+}
+
+/// Parses the contents of a settings file named `path`.
+///
+/// # Errors
+///
+/// Returns an error if `path` has another extension or the contents do not
+/// parse.
+pub fn parse<T: DeserializeOwned>(path: &Path, contents: &str) -> Result<T, ConfigFileError> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(contents).map_err(|source| ConfigFileError::Toml {
+            path: path.to_path_buf(),
+            source,
+        }),
+        Some("json") => serde_json::from_str(contents).map_err(|source| ConfigFileError::Json {
+            path: path.to_path_buf(),
+            source,
+        }),
+        _ => Err(ConfigFileError::UnknownFormat(path.to_path_buf())),
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use leptos::logging::log;
+
+use crate::domain::entities::audio_format::AudioFormat;
+use crate::domain::entities::clip::ClipRange;
+use crate::domain::entities::quality::QualityProfile;
+use crate::domain::entities::tags::TrackTags;
+
+/// Total size of cached files kept by default.
+pub const DEFAULT_MAX_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Everything that decides what a conversion produces.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub video_id: String,
+    pub format: AudioFormat,
+    pub quality: QualityProfile,
+    pub clip: Option<ClipRange>,
+}
+
+impl CacheKey {
+    /// Name of the cached file, unique per key.
+    fn file_name(&self) -> String {
+        let mut name = format!("{}-{}", self.video_id, self.quality.bitrate);
+        if let Some(rate) = self.quality.sample_rate {
+            name.push_str(&format!("-{}hz", rate.hz()));
+        }
+        if let Some(channels) = self.quality.channels {
+            name.push_str(&format!("-{}ch", channels.count()));
+        }
+        if let Some(clip) = self.clip {
+            name.push_str(&format!("-{}s", clip.start_secs));
+            if let Some(end) = clip.end_secs {
+                name.push_str(&format!("-{end}s"));
+            }
+        }
+        format!("{name}.{}", self.format.extension())
+    }
+}
+
+/// A cached conversion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedFile {
+    pub path: PathBuf,
+    /// Title of the video as published.
+    pub title: Option<String>,
+    /// Tags written into the file.
+    pub tags: Option<TrackTags>,
+    /// Length of the audio, if known.
+    pub audio_secs: Option<u64>,
+}
+
+/// Size of the cache, for operators.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+    pub max_bytes: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    file: CachedFile,
+    bytes: u64,
+    /// Value of [`CacheState::clock`] when the entry was last used.
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, Entry>,
+    bytes: u64,
+    /// Counts uses, to order entries from least to most recently used.
+    clock: u64,
+}
+
+/// Finished conversions kept on disk so the same video with the same
+/// settings is not downloaded and transcoded again.
+///
+/// Files are hard-linked in and out of the cache directory, falling back to
+/// copies across file systems, so jobs and the cache can delete their
+/// copies independently. Once the files exceed `max_bytes` the least
+/// recently used ones are evicted. The index lives in memory; the directory
+/// is emptied when the cache is opened.
+#[derive(Debug)]
+pub struct ConversionCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    state: Mutex<CacheState>,
+}
+
+impl ConversionCache {
+    /// Opens an empty cache in `dir`, creating the directory if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be emptied or created.
+    pub async fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> std::io::Result<Self> {
+        let dir = dir.into();
+        match tokio::fs::remove_dir_all(&dir).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        tokio::fs::create_dir_all(&dir).await?;
+        Ok(Self {
+            dir,
+            max_bytes,
+            state: Mutex::new(CacheState::default()),
+        })
+    }
+
+    /// Looks up a conversion and marks it as recently used.
+    pub fn get(&self, key: &CacheKey) -> Option<CachedFile> {
+        let mut state = self.lock_state();
+        state.clock += 1;
+        let clock = state.clock;
+        let entry = state.entries.get_mut(key)?;
+        entry.last_used = clock;
+        Some(entry.file.clone())
+    }
+
+    /// Links a cached file into `dir` and returns the job's own copy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file was evicted meanwhile or cannot be
+    /// linked or copied.
+    pub async fn restore(&self, cached: &CachedFile, dir: &Path) -> std::io::Result<PathBuf> {
+        let file_name = cached.path.file_name().ok_or(std::io::ErrorKind::NotFound)?;
+        let path = dir.join(file_name);
+        link_or_copy(&cached.path, &path).await?;
+        Ok(path)
+    }
+
+    /// Adds a finished conversion, evicting the least recently used
+    /// entries if the cache grows too large.
+    ///
+    /// Files larger than the whole cache are not added.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be linked or copied.
+    pub async fn insert(
+        &self,
+        key: CacheKey,
+        file: &Path,
+        title: Option<String>,
+        tags: Option<TrackTags>,
+        audio_secs: Option<u64>,
+    ) -> std::io::Result<()> {
+        let bytes = tokio::fs::metadata(file).await?.len();
+        if bytes > self.max_bytes {
+            return Ok(());
+        }
+
+        let path = self.dir.join(key.file_name());
+        // A conversion that finished meanwhile may have cached the same key
+        let _ = tokio::fs::remove_file(&path).await;
+        link_or_copy(file, &path).await?;
+
+        let evicted = {
+            let mut state = self.lock_state();
+            state.clock += 1;
+            let entry = Entry {
+                file: CachedFile {
+                    path: path.clone(),
+                    title,
+                    tags,
+                    audio_secs,
+                },
+                bytes,
+                last_used: state.clock,
+            };
+            if let Some(replaced) = state.entries.insert(key.clone(), entry) {
+                state.bytes -= replaced.bytes;
+            }
+            state.bytes += bytes;
+
+            let mut evicted = Vec::new();
+            while state.bytes > self.max_bytes {
+                let Some(oldest) = state
+                    .entries
+                    .iter()
+                    .filter(|(candidate, _)| **candidate != key)
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| key.clone())
+                else {
+                    break;
+                };
+                if let Some(entry) = state.entries.remove(&oldest) {
+                    state.bytes -= entry.bytes;
+                    evicted.push(entry.file.path);
+                }
+            }
+            evicted
+        };
+
+        for path in evicted {
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                log!("Failed to remove evicted cache file {}: {}", path.display(), e);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let state = self.lock_state();
+        CacheStats {
+            entries: state.entries.len(),
+            bytes: state.bytes,
+            max_bytes: self.max_bytes,
+        }
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        // Every update leaves the index consistent before it can panic
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Hard-links `from` to `to`, copying if they are on different file systems.
+async fn link_or_copy(from: &Path, to: &Path) -> std::io::Result<()> {
+    match tokio::fs::hard_link(from, to).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(e),
+        Err(_) => tokio::fs::copy(from, to).await.map(|_| ()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::entities::quality::{Bitrate, Channels};
+
+    fn key(video_id: &str) -> CacheKey {
+        CacheKey {
+            video_id: video_id.to_string(),
+            format: AudioFormat::Mp3,
+            quality: QualityProfile::default(),
+            clip: None,
+        }
+    }
+
+    async fn file_of(dir: &Path, name: &str, bytes: usize) -> PathBuf {
+        let path = dir.join(name);
+        tokio::fs::write(&path, vec![7u8; bytes]).await.unwrap();
+        path
+    }
+
+    #[test]
+    fn test_file_name_covers_every_setting() {
+        let mut key = key("dQw4w9WgXcQ");
+        assert_eq!(key.file_name(), "dQw4w9WgXcQ-cbr192.mp3");
+
+        key.format = AudioFormat::Vorbis;
+        key.quality.bitrate = Bitrate::Vbr(2);
+        key.quality.channels = Some(Channels::Mono);
+        key.clip = Some(ClipRange::new(83, Some(245)).unwrap());
+        assert_eq!(key.file_name(), "dQw4w9WgXcQ-vbr2-1ch-83s-245s.ogg");
+    }
+
+    #[tokio::test]
+    async fn test_restores_cached_files_independently_of_the_original() {
+        let root = tempfile::tempdir().unwrap();
+        let cache = ConversionCache::open(root.path().join("cache"), 1000).await.unwrap();
+        let job_dir = root.path().join("job");
+        tokio::fs::create_dir_all(&job_dir).await.unwrap();
+        let original = file_of(root.path(), "song.mp3", 100).await;
+        let tags = TrackTags {
+            title: Some("Song".to_string()),
+            ..TrackTags::default()
+        };
+
+        assert_eq!(cache.get(&key("aaaaaaaaaaa")), None);
+        cache.insert(key("aaaaaaaaaaa"), &original, Some("Song".to_string()), Some(tags.clone()), Some(212)).await.unwrap();
+        tokio::fs::remove_file(&original).await.unwrap();
+
+        let cached = cache.get(&key("aaaaaaaaaaa")).unwrap();
+        assert_eq!(cached.title.as_deref(), Some("Song"));
+        assert_eq!(cached.tags, Some(tags));
+        assert_eq!(cached.audio_secs, Some(212));
+        let restored = cache.restore(&cached, &job_dir).await.unwrap();
+        assert_eq!(tokio::fs::read(&restored).await.unwrap(), vec![7u8; 100]);
+
+        let mut other_format = key("aaaaaaaaaaa");
+        other_format.format = AudioFormat::Flac;
+        assert_eq!(cache.get(&other_format), None);
+        assert_eq!(cache.stats(), CacheStats { entries: 1, bytes: 100, max_bytes: 1000 });
+    }
+
+    #[tokio::test]
+    async fn test_evicts_least_recently_used_files() {
+        let root = tempfile::tempdir().unwrap();
+        let cache = ConversionCache::open(root.path().join("cache"), 250).await.unwrap();
+        for id in ["aaaaaaaaaaa", "bbbbbbbbbbb"] {
+            let file = file_of(root.path(), id, 100).await;
+            cache.insert(key(id), &file, None, None, None).await.unwrap();
+        }
+
+        // Using the first entry makes the second one the oldest
+        let first = cache.get(&key("aaaaaaaaaaa")).unwrap();
+        let second = cache.get(&key("bbbbbbbbbbb")).unwrap();
+        cache.get(&key("aaaaaaaaaaa")).unwrap();
+        let third = file_of(root.path(), "ccccccccccc", 100).await;
+        cache.insert(key("ccccccccccc"), &third, None, None, None).await.unwrap();
+
+        assert!(cache.get(&key("bbbbbbbbbbb")).is_none());
+        assert!(!second.path.exists());
+        assert!(first.path.exists());
+        assert!(cache.get(&key("ccccccccccc")).is_some());
+        assert_eq!(cache.stats().bytes, 200);
+
+        // Files that could never fit are not cached at all
+        let huge = file_of(root.path(), "huge", 300).await;
+        cache.insert(key("ddddddddddd"), &huge, None, None, None).await.unwrap();
+        assert!(cache.get(&key("ddddddddddd")).is_none());
+        assert_eq!(cache.stats().entries, 2);
+    }
+
+    #[tokio::test]
+    async fn test_open_empties_the_directory() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("cache");
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let stale = file_of(&dir, "stale.mp3", 10).await;
+
+        let cache = ConversionCache::open(&dir, 1000).await.unwrap();
+        assert!(!stale.exists());
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
@@ -0,0 +1,1006 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::domain::entities::audio_format::AudioFormat;
+use crate::domain::entities::clip::ClipRange;
+use crate::domain::entities::conversion_error::ConversionError;
+use crate::domain::entities::playlist::PlaylistEntry;
+use crate::domain::entities::preview::{Availability, Chapter, VideoPreview};
+use crate::domain::entities::progress::JobProgress;
+use crate::domain::entities::quality::{Bitrate, QualityProfile};
+use crate::domain::services::tagging::VideoMetadata;
+
+/// A named set of extra extractor arguments tried as one download attempt.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Strategy {
+    pub name: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Sent as `--user-agent`.
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Random pause before each download, to look less like a bot.
+    #[serde(default)]
+    pub sleep: Option<SleepInterval>,
+    /// Error output that makes the runner pause longer before the next
+    /// strategy, because YouTube is throttling or bot-checking us.
+    #[serde(default = "default_backoff_on")]
+    pub backoff_on: Vec<String>,
+}
+
+/// Bounds of yt-dlp's random `--sleep-interval`, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SleepInterval {
+    pub min_secs: u32,
+    pub max_secs: u32,
+}
+
+fn default_backoff_on() -> Vec<String> {
+    ["Sign in to confirm", "rate limit", "429"]
+        .map(String::from)
+        .to_vec()
+}
+
+impl Strategy {
+    pub fn new(name: &str, args: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            args: args.iter().map(|arg| (*arg).to_string()).collect(),
+            user_agent: None,
+            sleep: None,
+            backoff_on: default_backoff_on(),
+        }
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    pub fn with_sleep(mut self, min_secs: u32, max_secs: u32) -> Self {
+        self.sleep = Some(SleepInterval { min_secs, max_secs });
+        self
+    }
+
+    /// Every yt-dlp argument this strategy adds to a download.
+    pub fn command_args(&self) -> Vec<String> {
+        let mut args = self.args.clone();
+        if let Some(user_agent) = &self.user_agent {
+            args.extend(["--user-agent".to_string(), user_agent.clone()]);
+        }
+        if let Some(sleep) = self.sleep {
+            args.extend([
+                "--sleep-interval".to_string(),
+                sleep.min_secs.to_string(),
+                "--max-sleep-interval".to_string(),
+                sleep.max_secs.to_string(),
+            ]);
+        }
+        args
+    }
+
+    /// Whether a failed attempt's error output calls for backing off.
+    pub fn should_back_off(&self, error: &str) -> bool {
+        self.backoff_on.iter().any(|pattern| error.contains(pattern.as_str()))
+    }
+
+    /// The yt-dlp retry strategies used when no strategy file is
+    /// configured, in the order they are attempted.
+    pub fn defaults() -> Vec<Strategy> {
+        vec![
+            // Strategy 1: Android client (Docker-optimized)
+            Strategy::new(
+                "android",
+                &[
+                    "--extractor-args", "youtube:player_client=android",
+                    "--no-check-certificates",
+                ],
+            )
+            .with_user_agent("com.google.android.youtube/17.31.35 (Linux; U; Android 11) gzip"),
+            // Strategy 2: Android TV client (often works well in containers)
+            Strategy::new(
+                "android_embedded",
+                &["--extractor-args", "youtube:player_client=android_embedded"],
+            )
+            .with_user_agent("com.google.android.youtube/17.31.35 (Linux; U; Android 11) gzip")
+            .with_sleep(1, 3),
+            // Strategy 3: iOS client
+            Strategy::new("ios", &["--extractor-args", "youtube:player_client=ios"])
+                .with_user_agent("com.google.ios.youtube/17.31.4 (iPhone14,3; U; CPU iOS 15_6 like Mac OS X)")
+                .with_sleep(1, 3),
+            // Strategy 4: Web client without cookies (Docker-safe)
+            Strategy::new(
+                "web",
+                &[
+                    "--extractor-args", "youtube:player_client=web",
+                    "--add-header", "Accept-Language:en-US,en;q=0.9",
+                ],
+            )
+            .with_user_agent("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
+            .with_sleep(1, 3),
+            // Strategy 5: Legacy method with Docker optimizations
+            Strategy::new(
+                "legacy",
+                &[
+                    "--extractor-args", "youtube:player_client=web",
+                    "--compat-options", "prefer-legacy-http-handler",
+                    "--no-check-certificates",
+                    "--prefer-insecure",
+                ],
+            )
+            .with_sleep(1, 3),
+            // Strategy 6: Minimal approach for containers
+            Strategy::new(
+                "mediaconnect",
+                &[
+                    "--extractor-args", "youtube:player_client=mediaconnect",
+                    "--socket-timeout", "30",
+                ],
+            )
+            .with_sleep(1, 3),
+        ]
+    }
+}
+
+/// Something a downloader reports while an attempt is running.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProgressEvent {
+    /// Bytes of the source media have arrived.
+    Downloading(JobProgress),
+    /// The download finished and ffmpeg started converting the audio.
+    Transcoding,
+}
+
+/// Everything a downloader needs to make one attempt at producing a file.
+#[derive(Debug, Clone)]
+pub struct DownloadRequest<'a> {
+    pub job_id: &'a str,
+    pub url: &'a str,
+    /// Directory the output file must be written to.
+    pub output_dir: &'a Path,
+    /// Format the audio must be extracted to.
+    pub format: AudioFormat,
+    /// Encoder settings for the extracted audio.
+    pub quality: QualityProfile,
+    /// Section of the video to keep, or `None` for all of it.
+    pub clip: Option<ClipRange>,
+    pub strategy: &'a Strategy,
+    /// Receives progress updates; dropped when the attempt ends.
+    pub progress: UnboundedSender<ProgressEvent>,
+}
+
+/// What a successful attempt left in the output directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Download {
+    pub audio_path: PathBuf,
+    /// The video's metadata, if the extractor reported it.
+    pub metadata: Option<VideoMetadata>,
+    /// The video's thumbnail as a JPEG, if one could be fetched.
+    pub thumbnail_path: Option<PathBuf>,
+}
+
+/// The videos of a playlist, as listed by the extractor.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlaylistListing {
+    pub title: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+/// The parts of yt-dlp's `--flat-playlist --dump-single-json` output we use.
+#[derive(Debug, Deserialize)]
+struct FlatPlaylist {
+    title: Option<String>,
+    #[serde(default)]
+    entries: Vec<FlatPlaylistEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FlatPlaylistEntry {
+    id: Option<String>,
+    title: Option<String>,
+    duration: Option<f64>,
+}
+
+/// Reads the playlist yt-dlp printed with `--flat-playlist --dump-single-json`.
+///
+/// Entries without a video ID cannot be converted and are left out.
+///
+/// # Errors
+///
+/// Returns an error if `json` is not a playlist dump.
+pub fn parse_flat_playlist(json: &str) -> Result<PlaylistListing, serde_json::Error> {
+    let playlist: FlatPlaylist = serde_json::from_str(json)?;
+    Ok(PlaylistListing {
+        title: playlist.title,
+        entries: playlist
+            .entries
+            .into_iter()
+            .filter_map(|entry| {
+                Some(PlaylistEntry {
+                    video_id: entry.id?,
+                    title: entry.title,
+                    duration_secs: entry.duration.map(|secs| secs.max(0.0).round() as u64),
+                })
+            })
+            .collect(),
+    })
+}
+
+/// The parts of yt-dlp's `--dump-json` output shown in a preview.
+#[derive(Debug, Deserialize)]
+struct VideoDump {
+    id: String,
+    title: Option<String>,
+    channel: Option<String>,
+    uploader: Option<String>,
+    duration: Option<f64>,
+    thumbnail: Option<String>,
+    availability: Option<String>,
+    live_status: Option<String>,
+    #[serde(default)]
+    chapters: Option<Vec<DumpChapter>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DumpChapter {
+    title: Option<String>,
+    start_time: f64,
+    end_time: f64,
+}
+
+/// Reads the video yt-dlp printed with `--dump-json`.
+///
+/// # Errors
+///
+/// Returns an error if `json` is not a video dump.
+pub fn parse_video_dump(json: &str) -> Result<VideoPreview, serde_json::Error> {
+    let video: VideoDump = serde_json::from_str(json)?;
+    let availability = match video.live_status.as_deref() {
+        Some("is_live") => Some(Availability::Live),
+        Some("is_upcoming") => Some(Availability::Upcoming),
+        _ => match video.availability.as_deref() {
+            Some("public") => Some(Availability::Public),
+            Some("unlisted") => Some(Availability::Unlisted),
+            Some("private") => Some(Availability::Private),
+            Some("premium_only") => Some(Availability::PremiumOnly),
+            Some("subscriber_only") => Some(Availability::SubscriberOnly),
+            Some("needs_auth") => Some(Availability::NeedsAuth),
+            _ => None,
+        },
+    };
+    let chapters = video
+        .chapters
+        .unwrap_or_default()
+        .into_iter()
+        .enumerate()
+        .map(|(index, chapter)| Chapter {
+            title: chapter.title.unwrap_or_else(|| format!("Chapter {}", index + 1)),
+            start_secs: chapter.start_time.max(0.0).round() as u64,
+            end_secs: chapter.end_time.max(0.0).round() as u64,
+        })
+        .collect();
+
+    Ok(VideoPreview {
+        video_id: video.id,
+        title: video.title,
+        channel: video.channel.or(video.uploader),
+        duration_secs: video.duration.map(|secs| secs.max(0.0).round() as u64),
+        thumbnail_url: video.thumbnail,
+        availability,
+        chapters,
+    })
+}
+
+/// Marker that starts every line printed by [`YTDLP_PROGRESS_TEMPLATE`].
+const PROGRESS_MARKER: &str = "ytmp3-progress";
+
+/// Makes yt-dlp print raw progress counters on their own line, `NA` when unknown.
+const YTDLP_PROGRESS_TEMPLATE: &str = "download:ytmp3-progress %(progress.downloaded_bytes)s %(progress.total_bytes)s %(progress.total_bytes_estimate)s %(progress.speed)s %(progress.eta)s";
+
+/// Translates a quality profile into yt-dlp arguments.
+///
+/// The bitrate maps onto `--audio-quality`; sample rate and channel count
+/// are passed to ffmpeg through the audio extraction postprocessor.
+pub fn quality_args(quality: &QualityProfile) -> Vec<String> {
+    let mut args = vec!["--audio-quality".to_string()];
+    args.push(match quality.bitrate {
+        Bitrate::Cbr(kbps) => format!("{kbps}K"),
+        Bitrate::Vbr(level) => level.to_string(),
+    });
+
+    let mut ffmpeg_args = Vec::new();
+    if let Some(rate) = quality.sample_rate {
+        ffmpeg_args.push(format!("-ar {}", rate.hz()));
+    }
+    if let Some(channels) = quality.channels {
+        ffmpeg_args.push(format!("-ac {}", channels.count()));
+    }
+    if !ffmpeg_args.is_empty() {
+        args.push("--postprocessor-args".to_string());
+        args.push(format!("ExtractAudio+ffmpeg_o:{}", ffmpeg_args.join(" ")));
+    }
+    args
+}
+
+/// Translates a clip into yt-dlp arguments that download only that section.
+///
+/// Cuts are forced onto keyframes so the audio starts exactly at the
+/// requested time rather than at the nearest earlier keyframe.
+pub fn clip_args(clip: Option<&ClipRange>) -> Vec<String> {
+    let Some(clip) = clip else {
+        return Vec::new();
+    };
+    let end = clip.end_secs.map_or("inf".to_string(), |end| end.to_string());
+    vec![
+        "--download-sections".to_string(),
+        format!("*{}-{}", clip.start_secs, end),
+        "--force-keyframes-at-cuts".to_string(),
+    ]
+}
+
+/// Interprets one line of yt-dlp stdout, if it reports progress.
+pub fn parse_progress_line(line: &str) -> Option<ProgressEvent> {
+    let line = line.trim();
+
+    if let Some(fields) = line.strip_prefix(PROGRESS_MARKER) {
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        let [downloaded, total, estimate, speed, eta] = fields[..] else {
+            return None;
+        };
+        let number = |field: &str| field.parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0);
+
+        let downloaded = number(downloaded).map(|n| n as u64);
+        let total = number(total).or_else(|| number(estimate)).map(|n| n as u64);
+        let eta = number(eta).map(|n| n as u64);
+        return Some(ProgressEvent::Downloading(JobProgress::from_bytes(
+            downloaded,
+            total,
+            number(speed),
+            eta,
+        )));
+    }
+
+    if line.starts_with("[ExtractAudio]") {
+        return Some(ProgressEvent::Transcoding);
+    }
+
+    None
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DownloadError {
+    /// The extractor is not installed where it is looked for.
+    #[error("{} could not be found", .0.display())]
+    ToolMissing(PathBuf),
+    /// The extractor could not be started at all.
+    #[error("Command execution failed: {0}")]
+    Spawn(std::io::Error),
+    /// Reading the extractor's output or files failed.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// The extractor ran and reported a failure.
+    #[error("{stderr}")]
+    Failed { stderr: String },
+    /// The extractor reported success but left no audio file behind.
+    #[error("No audio file was produced")]
+    NoOutput,
+    /// The extractor described a playlist or video in a form we do not understand.
+    #[error("Unreadable extractor output: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+impl DownloadError {
+    /// What the failure means for the user.
+    pub fn code(&self) -> ConversionError {
+        match self {
+            DownloadError::ToolMissing(_) => ConversionError::ToolMissing,
+            DownloadError::Failed { stderr } => ConversionError::classify(stderr),
+            DownloadError::Spawn(_) | DownloadError::Io(_) | DownloadError::NoOutput | DownloadError::Json(_) => {
+                ConversionError::Unknown
+            }
+        }
+    }
+}
+
+/// Fetches a video and extracts its audio track into a local file.
+///
+/// Implementations perform a single attempt; retrying with different
+/// strategies is the job runner's responsibility. The runner cancels an
+/// attempt by dropping its future, so implementations must stop any work
+/// they started, including child processes, when dropped.
+#[async_trait]
+pub trait Downloader: Send + Sync + std::fmt::Debug {
+    /// Downloads `request.url` into `request.output_dir` and returns the
+    /// produced audio file together with the video's metadata.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the extractor cannot be started, exits with a
+    /// failure, or does not produce an audio file.
+    async fn download(&self, request: DownloadRequest<'_>) -> Result<Download, DownloadError>;
+
+    /// Lists the first `max_entries` videos of the playlist `url` belongs
+    /// to, without downloading any of them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the extractor cannot be started, fails, or does
+    /// not describe a playlist.
+    async fn list_playlist(&self, url: &str, max_entries: usize) -> Result<PlaylistListing, DownloadError>;
+
+    /// Looks up the video `url` points at without downloading it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the extractor cannot be started, fails, or does
+    /// not describe a video.
+    async fn preview(&self, url: &str) -> Result<VideoPreview, DownloadError>;
+}
+
+/// Downloads through the `yt-dlp` command-line tool.
+#[derive(Debug, Clone)]
+pub struct YtDlpDownloader {
+    program: PathBuf,
+}
+
+impl YtDlpDownloader {
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+        }
+    }
+
+    /// Tells a missing extractor apart from other reasons it did not start.
+    /// A missing working directory `cwd` fails the same way, e.g. when the
+    /// job's directory was already removed.
+    fn spawn_error(&self, e: std::io::Error, cwd: Option<&Path>) -> DownloadError {
+        if e.kind() == std::io::ErrorKind::NotFound && cwd.is_none_or(Path::exists) {
+            DownloadError::ToolMissing(self.program.clone())
+        } else {
+            DownloadError::Spawn(e)
+        }
+    }
+}
+
+impl Default for YtDlpDownloader {
+    fn default() -> Self {
+        Self::new("yt-dlp")
+    }
+}
+
+#[async_trait]
+impl Downloader for YtDlpDownloader {
+    async fn download(&self, request: DownloadRequest<'_>) -> Result<Download, DownloadError> {
+        let mut cmd = Command::new(&self.program);
+        cmd.arg(request.url)
+            .arg("-x")
+            .arg("--audio-format")
+            .arg(request.format.as_str())
+            .args(quality_args(&request.quality))
+            .args(clip_args(request.clip.as_ref()))
+            .arg("-o")
+            .arg("%(title)s.%(ext)s")
+            .arg("--restrict-filenames")
+            // Metadata and thumbnail for tagging the file afterwards
+            .arg("--write-info-json")
+            .arg("--write-thumbnail")
+            .arg("--convert-thumbnails")
+            .arg("jpg")
+            .current_dir(request.output_dir)
+            .arg("--retries")
+            .arg("2")
+            .arg("--retry-sleep")
+            .arg("3")
+            .arg("--newline")
+            .arg("--progress-template")
+            .arg(YTDLP_PROGRESS_TEMPLATE)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        // Run yt-dlp in its own process group so ffmpeg dies with it
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        // Add strategy-specific arguments
+        cmd.args(request.strategy.command_args());
+
+        let mut child = cmd.spawn().map_err(|e| self.spawn_error(e, Some(request.output_dir)))?;
+        let mut process_group = ProcessGroupGuard::new(child.id());
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+
+        // Drain stderr concurrently so a chatty extractor cannot block on a full pipe
+        let stderr_task = tokio::spawn(async move {
+            let mut buffer = Vec::new();
+            if let Some(mut stderr) = stderr {
+                let _ = stderr.read_to_end(&mut buffer).await;
+            }
+            String::from_utf8_lossy(&buffer).to_string()
+        });
+
+        if let Some(stdout) = stdout {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                match parse_progress_line(&line) {
+                    Some(event) => {
+                        let _ = request.progress.send(event);
+                    }
+                    None => leptos::logging::log!("Job {} yt-dlp: {}", request.job_id, line),
+                }
+            }
+        }
+
+        let status = child.wait().await?;
+        process_group.disarm();
+        let stderr = stderr_task.await.unwrap_or_default();
+        leptos::logging::log!(
+            "Job {} yt-dlp exited with status: {}",
+            request.job_id,
+            status
+        );
+
+        if !status.success() {
+            return Err(DownloadError::Failed { stderr });
+        }
+
+        let audio_path = find_file_with_extension(request.output_dir, request.format.extension())
+            .await
+            .ok_or(DownloadError::NoOutput)?;
+
+        let metadata = match find_file_with_extension(request.output_dir, "json").await {
+            Some(path) => match VideoMetadata::read(&path).await {
+                Ok(metadata) => Some(metadata),
+                Err(e) => {
+                    leptos::logging::log!("Job {} has unreadable metadata: {}", request.job_id, e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        Ok(Download {
+            audio_path,
+            metadata,
+            thumbnail_path: find_file_with_extension(request.output_dir, "jpg").await,
+        })
+    }
+
+    async fn list_playlist(&self, url: &str, max_entries: usize) -> Result<PlaylistListing, DownloadError> {
+        let output = Command::new(&self.program)
+            .arg(url)
+            .arg("--flat-playlist")
+            .arg("--dump-single-json")
+            .arg("--yes-playlist")
+            .arg("--playlist-end")
+            .arg(max_entries.to_string())
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| self.spawn_error(e, None))?;
+
+        if !output.status.success() {
+            return Err(DownloadError::Failed {
+                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            });
+        }
+        Ok(parse_flat_playlist(&String::from_utf8_lossy(&output.stdout))?)
+    }
+
+    async fn preview(&self, url: &str) -> Result<VideoPreview, DownloadError> {
+        let output = Command::new(&self.program)
+            .arg(url)
+            .arg("--dump-json")
+            .arg("--skip-download")
+            .arg("--no-playlist")
+            .arg("--no-warnings")
+            .kill_on_drop(true)
+            .output()
+            .await
+            .map_err(|e| self.spawn_error(e, None))?;
+
+        if !output.status.success() {
+            return Err(DownloadError::Failed {
+                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            });
+        }
+        Ok(parse_video_dump(&String::from_utf8_lossy(&output.stdout))?)
+    }
+}
+
+/// Kills a child's whole process group when dropped, unless disarmed.
+///
+/// `kill_on_drop` only reaches yt-dlp itself; the ffmpeg it spawns for the
+/// audio extraction would otherwise keep running after a cancellation.
+#[derive(Debug)]
+struct ProcessGroupGuard {
+    pgid: Option<u32>,
+}
+
+impl ProcessGroupGuard {
+    fn new(pgid: Option<u32>) -> Self {
+        Self { pgid }
+    }
+
+    /// Leaves the group alone, once the child has exited on its own.
+    fn disarm(&mut self) {
+        self.pgid = None;
+    }
+}
+
+impl Drop for ProcessGroupGuard {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(pgid) = self.pgid.and_then(|pgid| libc::pid_t::try_from(pgid).ok()) {
+            // SAFETY: killpg has no memory-safety preconditions; a group that
+            // already exited only makes it fail with ESRCH
+            unsafe {
+                libc::killpg(pgid, libc::SIGKILL);
+            }
+        }
+    }
+}
+
+/// Returns the first file in `dir` with the given extension.
+pub async fn find_file_with_extension(dir: &Path, extension: &str) -> Option<PathBuf> {
+    let mut entries = tokio::fs::read_dir(dir).await.ok()?;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) == Some(extension) {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// A tiny but well-formed MP3: an empty ID3v2 tag followed by one silent
+/// MPEG-1 Layer III frame (128 kbps, 44.1 kHz).
+pub fn fixture_mp3() -> Vec<u8> {
+    const FRAME_LEN: usize = 417;
+    let mut bytes = b"ID3\x03\x00\x00\x00\x00\x00\x00".to_vec();
+    bytes.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+    bytes.resize(bytes.len() + FRAME_LEN - 4, 0);
+    bytes
+}
+
+/// What a [`FakeDownloader`] does on one attempt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FakeOutcome {
+    /// Write the fixture MP3 under the requested format's extension and succeed.
+    Succeed,
+    /// Fail as if the extractor printed `stderr`.
+    Fail(String),
+}
+
+/// An in-process downloader that never touches the network.
+///
+/// Each attempt consumes the next scripted [`FakeOutcome`]; once the script
+/// runs out every further attempt succeeds.
+#[derive(Debug, Default)]
+pub struct FakeDownloader {
+    script: Mutex<VecDeque<FakeOutcome>>,
+    attempts: Mutex<Vec<String>>,
+    delay: Duration,
+    running: AtomicUsize,
+    max_running: AtomicUsize,
+    metadata: Option<VideoMetadata>,
+    playlist: Option<PlaylistListing>,
+    preview: Option<VideoPreview>,
+    previews: AtomicUsize,
+}
+
+impl FakeDownloader {
+    pub fn new(script: impl IntoIterator<Item = FakeOutcome>) -> Self {
+        Self {
+            script: Mutex::new(script.into_iter().collect()),
+            ..Self::default()
+        }
+    }
+
+    /// Makes every attempt take `delay` before it finishes.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Makes successful attempts report `metadata` and a thumbnail.
+    pub fn with_metadata(mut self, metadata: VideoMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Makes playlist listings return `playlist`; without one every URL
+    /// is treated as not being a playlist.
+    pub fn with_playlist(mut self, playlist: PlaylistListing) -> Self {
+        self.playlist = Some(playlist);
+        self
+    }
+
+    /// Makes previews return `preview`; without one every video is
+    /// reported unavailable.
+    pub fn with_preview(mut self, preview: VideoPreview) -> Self {
+        self.preview = Some(preview);
+        self
+    }
+
+    /// Number of previews looked up so far.
+    pub fn preview_count(&self) -> usize {
+        self.previews.load(Ordering::SeqCst)
+    }
+
+    /// Highest number of attempts that were in flight at the same time.
+    pub fn max_concurrent(&self) -> usize {
+        self.max_running.load(Ordering::SeqCst)
+    }
+
+    /// A downloader whose every attempt fails with `stderr`.
+    pub fn always_failing(stderr: &str, attempts: usize) -> Self {
+        Self::new(std::iter::repeat_n(
+            FakeOutcome::Fail(stderr.to_string()),
+            attempts,
+        ))
+    }
+
+    /// Names of the strategies attempted so far, in order.
+    pub fn attempts(&self) -> Vec<String> {
+        self.attempts
+            .lock()
+            .map(|attempts| attempts.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl Downloader for FakeDownloader {
+    async fn download(&self, request: DownloadRequest<'_>) -> Result<Download, DownloadError> {
+        if let Ok(mut attempts) = self.attempts.lock() {
+            attempts.push(request.strategy.name.clone());
+        }
+
+        let outcome = self
+            .script
+            .lock()
+            .ok()
+            .and_then(|mut script| script.pop_front())
+            .unwrap_or(FakeOutcome::Succeed);
+
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_running.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+
+        match outcome {
+            FakeOutcome::Succeed => {
+                let total = fixture_mp3().len() as u64;
+                for downloaded in [total / 2, total] {
+                    let _ = request.progress.send(ProgressEvent::Downloading(
+                        JobProgress::from_bytes(Some(downloaded), Some(total), Some(1024.0), Some(0)),
+                    ));
+                }
+                let _ = request.progress.send(ProgressEvent::Transcoding);
+
+                let path = request
+                    .output_dir
+                    .join(format!("fixture.{}", request.format.extension()));
+                tokio::fs::write(&path, fixture_mp3()).await?;
+
+                let thumbnail_path = match self.metadata {
+                    Some(_) => {
+                        let thumbnail = request.output_dir.join("fixture.jpg");
+                        tokio::fs::write(&thumbnail, b"not really a jpeg").await?;
+                        Some(thumbnail)
+                    }
+                    None => None,
+                };
+                Ok(Download {
+                    audio_path: path,
+                    metadata: self.metadata.clone(),
+                    thumbnail_path,
+                })
+            }
+            FakeOutcome::Fail(stderr) => Err(DownloadError::Failed { stderr }),
+        }
+    }
+
+    async fn list_playlist(&self, url: &str, max_entries: usize) -> Result<PlaylistListing, DownloadError> {
+        match &self.playlist {
+            Some(playlist) => Ok(PlaylistListing {
+                title: playlist.title.clone(),
+                entries: playlist.entries.iter().take(max_entries).cloned().collect(),
+            }),
+            None => Err(DownloadError::Failed {
+                stderr: format!("ERROR: {url} is not a playlist"),
+            }),
+        }
+    }
+
+    async fn preview(&self, url: &str) -> Result<VideoPreview, DownloadError> {
+        self.previews.fetch_add(1, Ordering::SeqCst);
+        self.preview.clone().ok_or_else(|| DownloadError::Failed {
+            stderr: format!("ERROR: [youtube] {url}: Video unavailable"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quality_args() {
+        use crate::domain::entities::quality::{Channels, SampleRate};
+
+        assert_eq!(quality_args(&QualityProfile::default()), ["--audio-quality", "192K"]);
+        assert_eq!(
+            quality_args(&QualityProfile {
+                bitrate: Bitrate::Vbr(2),
+                sample_rate: Some(SampleRate::Hz48000),
+                channels: Some(Channels::Mono),
+            }),
+            [
+                "--audio-quality",
+                "2",
+                "--postprocessor-args",
+                "ExtractAudio+ffmpeg_o:-ar 48000 -ac 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clip_args() {
+        assert!(clip_args(None).is_empty());
+        assert_eq!(
+            clip_args(Some(&ClipRange::new(83, Some(245)).unwrap())),
+            ["--download-sections", "*83-245", "--force-keyframes-at-cuts"]
+        );
+        assert_eq!(
+            clip_args(Some(&ClipRange::new(60, None).unwrap()))[1],
+            "*60-inf"
+        );
+    }
+
+    #[test]
+    fn test_parse_progress_line() {
+        assert_eq!(
+            parse_progress_line("ytmp3-progress 1048576 4194304 NA 524288.5 6"),
+            Some(ProgressEvent::Downloading(JobProgress {
+                percent: Some(25.0),
+                downloaded_bytes: Some(1_048_576),
+                total_bytes: Some(4_194_304),
+                bytes_per_sec: Some(524_288.5),
+                eta_secs: Some(6),
+            }))
+        );
+
+        // Falls back to the size estimate when the exact size is unknown
+        let Some(ProgressEvent::Downloading(progress)) =
+            parse_progress_line("ytmp3-progress 2000 NA 4000.0 NA NA")
+        else {
+            panic!("expected download progress");
+        };
+        assert_eq!(progress.percent, Some(50.0));
+        assert_eq!(progress.bytes_per_sec, None);
+        assert_eq!(progress.eta_secs, None);
+
+        assert_eq!(
+            parse_progress_line("[ExtractAudio] Destination: Rick_Astley_-_Never_Gonna_Give_You_Up.mp3"),
+            Some(ProgressEvent::Transcoding)
+        );
+        assert_eq!(parse_progress_line("[youtube] dQw4w9WgXcQ: Downloading webpage"), None);
+        assert_eq!(parse_progress_line("ytmp3-progress 1 2"), None);
+    }
+
+    #[test]
+    fn test_parse_flat_playlist() {
+        let json = r#"{
+            "_type": "playlist",
+            "id": "PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI",
+            "title": "Greatest Hits",
+            "entries": [
+                {"_type": "url", "id": "dQw4w9WgXcQ", "title": "Never Gonna Give You Up", "duration": 213.0},
+                {"_type": "url", "id": "yPYZpwSpKmA", "title": null},
+                {"_type": "url", "title": "[Deleted video]"}
+            ]
+        }"#;
+        assert_eq!(
+            parse_flat_playlist(json).unwrap(),
+            PlaylistListing {
+                title: Some("Greatest Hits".to_string()),
+                entries: vec![
+                    PlaylistEntry {
+                        video_id: "dQw4w9WgXcQ".to_string(),
+                        title: Some("Never Gonna Give You Up".to_string()),
+                        duration_secs: Some(213),
+                    },
+                    PlaylistEntry {
+                        video_id: "yPYZpwSpKmA".to_string(),
+                        title: None,
+                        duration_secs: None,
+                    },
+                ],
+            }
+        );
+        assert!(parse_flat_playlist("not json").is_err());
+    }
+
+    #[test]
+    fn test_parse_video_dump() {
+        let json = r#"{
+            "id": "dQw4w9WgXcQ",
+            "title": "Rick Astley - Never Gonna Give You Up (Official Music Video)",
+            "channel": null,
+            "uploader": "Rick Astley",
+            "duration": 212.6,
+            "thumbnail": "https://i.ytimg.com/vi/dQw4w9WgXcQ/maxresdefault.jpg",
+            "availability": "public",
+            "live_status": "not_live",
+            "chapters": [
+                {"start_time": 0.0, "end_time": 42.5, "title": "Intro"},
+                {"start_time": 42.5, "end_time": 212.6, "title": null}
+            ],
+            "formats": []
+        }"#;
+        assert_eq!(
+            parse_video_dump(json).unwrap(),
+            VideoPreview {
+                video_id: "dQw4w9WgXcQ".to_string(),
+                title: Some("Rick Astley - Never Gonna Give You Up (Official Music Video)".to_string()),
+                channel: Some("Rick Astley".to_string()),
+                duration_secs: Some(213),
+                thumbnail_url: Some("https://i.ytimg.com/vi/dQw4w9WgXcQ/maxresdefault.jpg".to_string()),
+                availability: Some(Availability::Public),
+                chapters: vec![
+                    Chapter {
+                        title: "Intro".to_string(),
+                        start_secs: 0,
+                        end_secs: 43,
+                    },
+                    Chapter {
+                        title: "Chapter 2".to_string(),
+                        start_secs: 43,
+                        end_secs: 213,
+                    },
+                ],
+            }
+        );
+
+        let live = parse_video_dump(r#"{"id": "jfKfPfyJRdk", "availability": "public", "live_status": "is_live", "chapters": null}"#)
+            .unwrap();
+        assert_eq!(live.availability, Some(Availability::Live));
+        assert!(live.chapters.is_empty());
+        assert!(parse_video_dump("{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_missing_extractor_is_told_apart_from_missing_files() {
+        let downloader = YtDlpDownloader::new("/nonexistent/yt-dlp");
+        let err = downloader
+            .preview("https://www.youtube.com/watch?v=dQw4w9WgXcQ")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DownloadError::ToolMissing(_)));
+        assert_eq!(err.code(), ConversionError::ToolMissing);
+
+        let not_found = || std::io::Error::from(std::io::ErrorKind::NotFound);
+        let gone = downloader.spawn_error(not_found(), Some(Path::new("/nonexistent/job")));
+        assert!(matches!(gone, DownloadError::Spawn(_)));
+        assert_eq!(gone.code(), ConversionError::Unknown);
+
+        let failed = DownloadError::Failed {
+            stderr: "ERROR: [youtube] abc: Private video".to_string(),
+        };
+        assert_eq!(failed.code(), ConversionError::classify("ERROR: [youtube] abc: Private video"));
+    }
+}
@@ -0,0 +1,287 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use http::{header, HeaderMap, Method};
+
+/// What identifies one version of a file served for download.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileVersion {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl FileVersion {
+    pub fn from_metadata(metadata: &std::fs::Metadata) -> Self {
+        Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        }
+    }
+
+    /// A strong entity tag from the size and modification time. Job files
+    /// are written once, so either changing means different contents.
+    pub fn etag(&self) -> String {
+        let modified = self
+            .modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        format!("\"{:x}-{:x}.{:x}\"", self.len, modified.as_secs(), modified.subsec_nanos())
+    }
+
+    /// The `Last-Modified` header value, if the file system reports one.
+    pub fn last_modified(&self) -> Option<String> {
+        self.modified.map(httpdate::fmt_http_date)
+    }
+
+    fn modified_secs(&self) -> Option<u64> {
+        self.modified?.duration_since(UNIX_EPOCH).ok().map(|since| since.as_secs())
+    }
+}
+
+/// How to answer a request for a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileResponse {
+    /// 304: the client's copy is current.
+    NotModified,
+    /// 200 with the whole file.
+    Full,
+    /// 206 with the bytes from `start` to `end`, both included.
+    Partial { start: u64, end: u64 },
+    /// 416: the requested range lies outside the file.
+    Unsatisfiable,
+}
+
+/// Decides how to answer a `GET` or `HEAD` for `file` from the request's
+/// conditional and `Range` headers.
+///
+/// Only single byte ranges are served; a request for several gets the whole
+/// file, which clients must accept. Malformed `Range` headers are ignored.
+pub fn respond(method: &Method, headers: &HeaderMap, file: &FileVersion) -> FileResponse {
+    if is_not_modified(headers, file) {
+        return FileResponse::NotModified;
+    }
+    if method != Method::GET || !if_range_matches(headers, file) {
+        return FileResponse::Full;
+    }
+    match headers.get(header::RANGE).and_then(|value| value.to_str().ok()) {
+        Some(range) => parse_range(range, file.len),
+        None => FileResponse::Full,
+    }
+}
+
+/// `If-None-Match` decides when present; `If-Modified-Since` only without it.
+fn is_not_modified(headers: &HeaderMap, file: &FileVersion) -> bool {
+    let if_none_match: Vec<&str> = headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect();
+    if !if_none_match.is_empty() {
+        let etag = file.etag();
+        // The weak comparison applies here
+        return if_none_match
+            .iter()
+            .any(|tag| *tag == "*" || tag.trim_start_matches("W/") == etag);
+    }
+
+    let since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok())
+        .and_then(|since| since.duration_since(UNIX_EPOCH).ok());
+    match (since, file.modified_secs()) {
+        (Some(since), Some(modified)) => modified <= since.as_secs(),
+        _ => false,
+    }
+}
+
+/// Whether a range may be served: without `If-Range`, or when it names the
+/// current version by a strong entity tag or its exact modification date.
+fn if_range_matches(headers: &HeaderMap, file: &FileVersion) -> bool {
+    let Some(if_range) = headers.get(header::IF_RANGE).and_then(|value| value.to_str().ok()) else {
+        return true;
+    };
+    let if_range = if_range.trim();
+    if if_range.starts_with('"') {
+        return if_range == file.etag();
+    }
+    match httpdate::parse_http_date(if_range) {
+        Ok(date) => date.duration_since(UNIX_EPOCH).ok().map(|date| date.as_secs()) == file.modified_secs(),
+        Err(_) => false,
+    }
+}
+
+/// A `Content-Disposition` for downloading a file as `file_name`: an ASCII
+/// `filename` for old clients and the exact name as RFC 5987 `filename*`.
+pub fn content_disposition(file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    let mut encoded = String::with_capacity(file_name.len());
+    for byte in file_name.bytes() {
+        // RFC 5987 `attr-char`s go as they are
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+fn parse_range(range: &str, len: u64) -> FileResponse {
+    let Some(spec) = range.trim().strip_prefix("bytes=") else {
+        return FileResponse::Full;
+    };
+    if spec.contains(',') {
+        return FileResponse::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return FileResponse::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    if start.is_empty() {
+        // The last `end` bytes
+        return match end.parse::<u64>() {
+            Ok(0) => FileResponse::Unsatisfiable,
+            Ok(_) if len == 0 => FileResponse::Unsatisfiable,
+            Ok(suffix) => FileResponse::Partial {
+                start: len.saturating_sub(suffix),
+                end: len - 1,
+            },
+            Err(_) => FileResponse::Full,
+        };
+    }
+
+    let Ok(start) = start.parse::<u64>() else {
+        return FileResponse::Full;
+    };
+    let end = match end {
+        "" => None,
+        end => match end.parse::<u64>() {
+            Ok(end) if end >= start => Some(end),
+            _ => return FileResponse::Full,
+        },
+    };
+    if start >= len {
+        return FileResponse::Unsatisfiable;
+    }
+    FileResponse::Partial {
+        start,
+        end: end.map_or(len - 1, |end| end.min(len - 1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+    use std::time::Duration;
+
+    fn file() -> FileVersion {
+        FileVersion {
+            len: 1000,
+            modified: Some(UNIX_EPOCH + Duration::from_millis(1_750_000_000_500)),
+        }
+    }
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn get(pairs: &[(header::HeaderName, &str)]) -> FileResponse {
+        respond(&Method::GET, &headers(pairs), &file())
+    }
+
+    #[test]
+    fn test_serves_single_byte_ranges() {
+        let cases = [
+            ("bytes=0-499", FileResponse::Partial { start: 0, end: 499 }),
+            ("bytes=500-", FileResponse::Partial { start: 500, end: 999 }),
+            ("bytes=900-5000", FileResponse::Partial { start: 900, end: 999 }),
+            ("bytes=-100", FileResponse::Partial { start: 900, end: 999 }),
+            ("bytes=-5000", FileResponse::Partial { start: 0, end: 999 }),
+            ("bytes=999-999", FileResponse::Partial { start: 999, end: 999 }),
+            ("bytes=1000-", FileResponse::Unsatisfiable),
+            ("bytes=-0", FileResponse::Unsatisfiable),
+            // Several ranges, other units and nonsense get the whole file
+            ("bytes=0-1,5-6", FileResponse::Full),
+            ("items=0-1", FileResponse::Full),
+            ("bytes=5-1", FileResponse::Full),
+            ("bytes=abc", FileResponse::Full),
+        ];
+        for (range, expected) in cases {
+            assert_eq!(get(&[(header::RANGE, range)]), expected, "{range}");
+        }
+        assert_eq!(
+            respond(&Method::HEAD, &headers(&[(header::RANGE, "bytes=0-1")]), &file()),
+            FileResponse::Full
+        );
+    }
+
+    #[test]
+    fn test_answers_conditional_requests() {
+        let etag = file().etag();
+        let last_modified = file().last_modified().unwrap();
+        assert_eq!(last_modified, "Sun, 15 Jun 2025 15:06:40 GMT");
+
+        assert_eq!(get(&[(header::IF_NONE_MATCH, &etag)]), FileResponse::NotModified);
+        assert_eq!(get(&[(header::IF_NONE_MATCH, &format!("\"x\", W/{etag}"))]), FileResponse::NotModified);
+        assert_eq!(get(&[(header::IF_NONE_MATCH, "*")]), FileResponse::NotModified);
+        assert_eq!(get(&[(header::IF_NONE_MATCH, "\"other\"")]), FileResponse::Full);
+
+        assert_eq!(get(&[(header::IF_MODIFIED_SINCE, &last_modified)]), FileResponse::NotModified);
+        assert_eq!(
+            get(&[(header::IF_MODIFIED_SINCE, "Sat, 14 Jun 2025 00:00:00 GMT")]),
+            FileResponse::Full
+        );
+        // An entity tag that does not match wins over a date that does
+        assert_eq!(
+            get(&[(header::IF_NONE_MATCH, "\"other\""), (header::IF_MODIFIED_SINCE, &last_modified)]),
+            FileResponse::Full
+        );
+    }
+
+    #[test]
+    fn test_content_disposition_encodes_non_ascii_names() {
+        assert_eq!(
+            content_disposition("Rick Astley - Up.mp3"),
+            "attachment; filename=\"Rick Astley - Up.mp3\"; filename*=UTF-8''Rick%20Astley%20-%20Up.mp3"
+        );
+        assert_eq!(
+            content_disposition("Sigur Rós - Hoppípolla.mp3"),
+            "attachment; filename=\"Sigur R_s - Hopp_polla.mp3\"; filename*=UTF-8''Sigur%20R%C3%B3s%20-%20Hopp%C3%ADpolla.mp3"
+        );
+        assert_eq!(
+            content_disposition("a\"b.mp3"),
+            "attachment; filename=\"a_b.mp3\"; filename*=UTF-8''a%22b.mp3"
+        );
+    }
+
+    #[test]
+    fn test_if_range_only_serves_ranges_of_the_same_version() {
+        let range = (header::RANGE, "bytes=0-9");
+        let partial = FileResponse::Partial { start: 0, end: 9 };
+        let etag = file().etag();
+        let last_modified = file().last_modified().unwrap();
+
+        assert_eq!(get(&[range.clone(), (header::IF_RANGE, &etag)]), partial);
+        assert_eq!(get(&[range.clone(), (header::IF_RANGE, &last_modified)]), partial);
+        assert_eq!(get(&[range.clone(), (header::IF_RANGE, "\"other\"")]), FileResponse::Full);
+        assert_eq!(get(&[range.clone(), (header::IF_RANGE, &format!("W/{etag}"))]), FileResponse::Full);
+        assert_eq!(
+            get(&[range, (header::IF_RANGE, "Sat, 14 Jun 2025 00:00:00 GMT")]),
+            FileResponse::Full
+        );
+    }
+}
@@ -0,0 +1,29 @@
+use leptos::prelude::*;
+
+use crate::domain::entities::quota::Usage;
+
+/// How much of their quotas the signed-in user has used, or `None` when
+/// nobody is signed in.
+#[server(GetUsage, "/api")]
+pub async fn get_usage() -> Result<Option<Usage>, ServerFnError> {
+    #[cfg(feature = "ssr")]
+    {
+        use crate::domain::services::session::current_user;
+        use crate::domain::services::video_converter::server::usage;
+
+        let Some(user) = current_user() else {
+            return Ok(None);
+        };
+        usage(&user.user_id, &user.role)
+            .await
+            .map(Some)
+            .map_err(|e| ServerFnError::new(format!("Failed to check usage: {e}")))
+    }
+
+    #[cfg(not(feature = "ssr"))]
+    {
+        Err(ServerFnError::new(
+            "Server function not available on client",
+        ))
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use tokio::sync::Notify;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("The server is busy converting other videos. Please try again in a few minutes.")]
+pub struct QueueFull;
+
+/// FIFO of job IDs waiting for a worker.
+///
+/// Unlike a channel, the queue can report where a job currently stands and
+/// drop a job that is no longer wanted.
+#[derive(Debug)]
+pub struct JobQueue {
+    waiting: Mutex<VecDeque<String>>,
+    notify: Notify,
+    max_len: usize,
+}
+
+impl JobQueue {
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            waiting: Mutex::new(VecDeque::new()),
+            notify: Notify::new(),
+            max_len,
+        }
+    }
+
+    /// Appends a job to the back of the queue.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`QueueFull`] if `max_len` jobs are already waiting.
+    pub fn push(&self, job_id: String) -> Result<(), QueueFull> {
+        {
+            let mut waiting = self.lock();
+            if waiting.len() >= self.max_len {
+                return Err(QueueFull);
+            }
+            waiting.push_back(job_id);
+        }
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Appends a job even if the queue is full, e.g. when recovering jobs
+    /// that were accepted before a restart.
+    pub fn push_unbounded(&self, job_id: String) {
+        self.lock().push_back(job_id);
+        self.notify.notify_one();
+    }
+
+    /// Waits for and removes the job at the front of the queue.
+    pub async fn pop(&self) -> String {
+        loop {
+            let notified = self.notify.notified();
+            if let Some(job_id) = self.lock().pop_front() {
+                return job_id;
+            }
+            notified.await;
+        }
+    }
+
+    /// Removes a waiting job. Returns `false` if it was not in the queue.
+    pub fn remove(&self, job_id: &str) -> bool {
+        let mut waiting = self.lock();
+        match waiting.iter().position(|id| id == job_id) {
+            Some(index) => {
+                waiting.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// One-based position of a waiting job, or `None` if it is not waiting.
+    pub fn position(&self, job_id: &str) -> Option<usize> {
+        self.lock()
+            .iter()
+            .position(|id| id == job_id)
+            .map(|index| index + 1)
+    }
+
+    /// IDs of the waiting jobs, front first.
+    pub fn waiting(&self) -> Vec<String> {
+        self.lock().iter().cloned().collect()
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<String>> {
+        // The queue holds plain IDs, so a panic elsewhere cannot leave it inconsistent
+        self.waiting
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_queue_reports_positions_and_rejects_when_full() {
+        let queue = JobQueue::new(2);
+        queue.push("a".to_string()).unwrap();
+        queue.push("b".to_string()).unwrap();
+        assert_eq!(queue.push("c".to_string()), Err(QueueFull));
+
+        assert_eq!(queue.position("a"), Some(1));
+        assert_eq!(queue.position("b"), Some(2));
+        assert_eq!(queue.position("c"), None);
+
+        assert_eq!(queue.pop().await, "a");
+        assert_eq!(queue.position("b"), Some(1));
+
+        assert!(queue.remove("b"));
+        assert!(!queue.remove("b"));
+        assert!(queue.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pop_waits_for_push() {
+        let queue = std::sync::Arc::new(JobQueue::new(1));
+        let waiter = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.pop().await }
+        });
+        tokio::task::yield_now().await;
+
+        queue.push("late".to_string()).unwrap();
+        assert_eq!(waiter.await.unwrap(), "late");
+    }
+}
@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use tokio::sync::RwLock;
+
+use crate::domain::entities::job_status::InvalidTransition;
+use crate::domain::services::video_converter::server::ConversionJob;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("Job not found")]
+    NotFound,
+    #[error(transparent)]
+    Transition(#[from] InvalidTransition),
+    #[error("job store database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("failed to encode job: {0}")]
+    Encoding(#[from] serde_json::Error),
+    #[error("job store task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    #[error("job store lock was poisoned")]
+    Poisoned,
+}
+
+/// A change applied atomically to a single stored job.
+///
+/// If the closure returns an error the stored job is left untouched.
+pub type JobUpdate = Box<dyn FnOnce(&mut ConversionJob) -> Result<(), InvalidTransition> + Send>;
+
+/// Where conversion jobs are kept between status checks.
+#[async_trait]
+pub trait JobStore: Send + Sync + std::fmt::Debug {
+    /// Adds a new job, replacing any job with the same ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backing storage cannot be written.
+    async fn insert(&self, job: ConversionJob) -> Result<(), StoreError>;
+
+    /// Returns a snapshot of the job with the given ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backing storage cannot be read.
+    async fn get(&self, job_id: &str) -> Result<Option<ConversionJob>, StoreError>;
+
+    /// Applies `update` to the stored job and returns the updated job.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StoreError::NotFound`] if there is no such job,
+    /// [`StoreError::Transition`] if `update` rejected the change, or an
+    /// error if the backing storage fails.
+    async fn update(&self, job_id: &str, update: JobUpdate) -> Result<ConversionJob, StoreError>;
+
+    /// Removes a job and returns it, if it existed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backing storage cannot be written.
+    async fn remove(&self, job_id: &str) -> Result<Option<ConversionJob>, StoreError>;
+
+    /// Returns a snapshot of every stored job.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backing storage cannot be read.
+    async fn list(&self) -> Result<Vec<ConversionJob>, StoreError>;
+
+    /// Returns a snapshot of every job started by `owner_id`, including the
+    /// tracks of their playlists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the backing storage cannot be read.
+    async fn list_by_owner(&self, owner_id: &str) -> Result<Vec<ConversionJob>, StoreError>;
+}
+
+/// Keeps jobs in process memory; everything is lost on restart.
+#[derive(Debug, Default)]
+pub struct MemoryJobStore {
+    jobs: RwLock<HashMap<String, ConversionJob>>,
+}
+
+#[async_trait]
+impl JobStore for MemoryJobStore {
+    async fn insert(&self, job: ConversionJob) -> Result<(), StoreError> {
+        self.jobs.write().await.insert(job.id.clone(), job);
+        Ok(())
+    }
+
+    async fn get(&self, job_id: &str) -> Result<Option<ConversionJob>, StoreError> {
+        Ok(self.jobs.read().await.get(job_id).cloned())
+    }
+
+    async fn update(&self, job_id: &str, update: JobUpdate) -> Result<ConversionJob, StoreError> {
+        let mut jobs = self.jobs.write().await;
+        let job = jobs.get_mut(job_id).ok_or(StoreError::NotFound)?;
+        let mut updated = job.clone();
+        update(&mut updated)?;
+        *job = updated.clone();
+        Ok(updated)
+    }
+
+    async fn remove(&self, job_id: &str) -> Result<Option<ConversionJob>, StoreError> {
+        Ok(self.jobs.write().await.remove(job_id))
+    }
+
+    async fn list(&self) -> Result<Vec<ConversionJob>, StoreError> {
+        Ok(self.jobs.read().await.values().cloned().collect())
+    }
+
+    async fn list_by_owner(&self, owner_id: &str) -> Result<Vec<ConversionJob>, StoreError> {
+        let jobs = self.jobs.read().await;
+        Ok(jobs
+            .values()
+            .filter(|job| job.owner_id.as_deref() == Some(owner_id))
+            .cloned()
+            .collect())
+    }
+}
+
+/// Persists jobs to a SQLite database so they survive restarts.
+///
+/// Each job is stored as a JSON document next to a few indexed columns.
+#[derive(Debug, Clone)]
+pub struct SqliteJobStore {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteJobStore {
+    /// Opens (or creates) the database at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened or migrated.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StoreError> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Opens a private in-memory database, mainly for tests.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the schema cannot be created.
+    pub fn open_in_memory() -> Result<Self, StoreError> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> Result<Self, StoreError> {
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS jobs (
+                 id            TEXT PRIMARY KEY NOT NULL,
+                 status        TEXT NOT NULL,
+                 updated_at_ms INTEGER NOT NULL,
+                 data          TEXT NOT NULL,
+                 owner_id      TEXT
+             );
+             CREATE INDEX IF NOT EXISTS jobs_status ON jobs (status);",
+        )?;
+        // Databases from before owners were indexed get the column filled in
+        // from the stored jobs
+        let has_owner: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('jobs') WHERE name = 'owner_id'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_owner {
+            conn.execute_batch(
+                "ALTER TABLE jobs ADD COLUMN owner_id TEXT;
+                 UPDATE jobs SET owner_id = json_extract(data, '$.owner_id');",
+            )?;
+        }
+        conn.execute_batch("CREATE INDEX IF NOT EXISTS jobs_owner ON jobs (owner_id);")?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    async fn with_conn<T, F>(&self, f: F) -> Result<T, StoreError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, StoreError> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = conn.lock().map_err(|_| StoreError::Poisoned)?;
+            f(&mut conn)
+        })
+        .await?
+    }
+}
+
+fn write_job(conn: &Connection, job: &ConversionJob) -> Result<(), StoreError> {
+    conn.execute(
+        "INSERT INTO jobs (id, status, updated_at_ms, data, owner_id) VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (id) DO UPDATE SET
+             status = excluded.status,
+             updated_at_ms = excluded.updated_at_ms,
+             data = excluded.data,
+             owner_id = excluded.owner_id",
+        params![
+            job.id,
+            job.status().as_str(),
+            job.history.updated_at_ms() as i64,
+            serde_json::to_string(job)?,
+            job.owner_id,
+        ],
+    )?;
+    Ok(())
+}
+
+fn read_job(conn: &Connection, job_id: &str) -> Result<Option<ConversionJob>, StoreError> {
+    let data: Option<String> = conn
+        .query_row("SELECT data FROM jobs WHERE id = ?1", [job_id], |row| row.get(0))
+        .optional()?;
+    Ok(data.map(|data| serde_json::from_str(&data)).transpose()?)
+}
+
+fn read_jobs(
+    conn: &Connection,
+    sql: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<ConversionJob>, StoreError> {
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map(params, |row| row.get::<_, String>(0))?;
+    let mut jobs = Vec::new();
+    for data in rows {
+        jobs.push(serde_json::from_str(&data?)?);
+    }
+    Ok(jobs)
+}
+
+#[async_trait]
+impl JobStore for SqliteJobStore {
+    async fn insert(&self, job: ConversionJob) -> Result<(), StoreError> {
+        self.with_conn(move |conn| write_job(conn, &job)).await
+    }
+
+    async fn get(&self, job_id: &str) -> Result<Option<ConversionJob>, StoreError> {
+        let job_id = job_id.to_string();
+        self.with_conn(move |conn| read_job(conn, &job_id)).await
+    }
+
+    async fn update(&self, job_id: &str, update: JobUpdate) -> Result<ConversionJob, StoreError> {
+        let job_id = job_id.to_string();
+        self.with_conn(move |conn| {
+            let tx = conn.transaction()?;
+            let mut job = read_job(&tx, &job_id)?.ok_or(StoreError::NotFound)?;
+            update(&mut job)?;
+            write_job(&tx, &job)?;
+            tx.commit()?;
+            Ok(job)
+        })
+        .await
+    }
+
+    async fn remove(&self, job_id: &str) -> Result<Option<ConversionJob>, StoreError> {
+        let job_id = job_id.to_string();
+        self.with_conn(move |conn| {
+            let job = read_job(conn, &job_id)?;
+            conn.execute("DELETE FROM jobs WHERE id = ?1", [&job_id])?;
+            Ok(job)
+        })
+        .await
+    }
+
+    async fn list(&self) -> Result<Vec<ConversionJob>, StoreError> {
+        self.with_conn(|conn| read_jobs(conn, "SELECT data FROM jobs ORDER BY updated_at_ms", []))
+            .await
+    }
+
+    async fn list_by_owner(&self, owner_id: &str) -> Result<Vec<ConversionJob>, StoreError> {
+        let owner_id = owner_id.to_string();
+        self.with_conn(move |conn| {
+            read_jobs(
+                conn,
+                "SELECT data FROM jobs WHERE owner_id = ?1 ORDER BY updated_at_ms",
+                [&owner_id],
+            )
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::entities::job_status::JobStatus;
+
+    fn job(id: &str) -> ConversionJob {
+        ConversionJob::new(
+            id.to_string(),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+            std::env::temp_dir().join(format!("ytmp3_{id}")),
+        )
+    }
+
+    async fn exercise(store: &dyn JobStore) {
+        store.insert(job("a")).await.unwrap();
+        store.insert(job("b")).await.unwrap();
+
+        let updated = store
+            .update("a", Box::new(|job| job.transition(JobStatus::Downloading)))
+            .await
+            .unwrap();
+        assert_eq!(updated.status(), JobStatus::Downloading);
+        assert_eq!(
+            store.get("a").await.unwrap().unwrap().status(),
+            JobStatus::Downloading
+        );
+
+        let rejected = store
+            .update("b", Box::new(|job| job.transition(JobStatus::Completed)))
+            .await;
+        assert!(matches!(rejected, Err(StoreError::Transition(_))));
+        assert_eq!(
+            store.get("b").await.unwrap().unwrap().status(),
+            JobStatus::Queued
+        );
+
+        assert!(matches!(
+            store.update("missing", Box::new(|_| Ok(()))).await,
+            Err(StoreError::NotFound)
+        ));
+
+        assert_eq!(store.list().await.unwrap().len(), 2);
+        let mut owned = job("c");
+        owned.owner_id = Some("alice".to_string());
+        store.insert(owned).await.unwrap();
+        let owned: Vec<_> = store
+            .list_by_owner("alice")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|job| job.id)
+            .collect();
+        assert_eq!(owned, ["c"]);
+        assert!(store.list_by_owner("bob").await.unwrap().is_empty());
+        store.remove("c").await.unwrap();
+
+        assert_eq!(store.remove("b").await.unwrap().unwrap().id, "b");
+        assert!(store.get("b").await.unwrap().is_none());
+        assert_eq!(store.list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        exercise(&MemoryJobStore::default()).await;
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store() {
+        exercise(&SqliteJobStore::open_in_memory().unwrap()).await;
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jobs.sqlite3");
+
+        {
+            let store = SqliteJobStore::open(&path).unwrap();
+            let mut job = job("persisted");
+            job.transition(JobStatus::Downloading).unwrap();
+            store.insert(job).await.unwrap();
+        }
+
+        let store = SqliteJobStore::open(&path).unwrap();
+        let job = store.get("persisted").await.unwrap().unwrap();
+        assert_eq!(job.status(), JobStatus::Downloading);
+        assert_eq!(job.history.transitions().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_store_indexes_owners_of_older_databases() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jobs.sqlite3");
+
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE jobs (
+                     id            TEXT PRIMARY KEY NOT NULL,
+                     status        TEXT NOT NULL,
+                     updated_at_ms INTEGER NOT NULL,
+                     data          TEXT NOT NULL
+                 );",
+            )
+            .unwrap();
+            let mut job = job("old");
+            job.owner_id = Some("alice".to_string());
+            conn.execute(
+                "INSERT INTO jobs (id, status, updated_at_ms, data) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    job.id,
+                    job.status().as_str(),
+                    0,
+                    serde_json::to_string(&job).unwrap()
+                ],
+            )
+            .unwrap();
+        }
+
+        let store = SqliteJobStore::open(&path).unwrap();
+        assert_eq!(store.list_by_owner("alice").await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,245 @@
+use std::str::FromStr;
+
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::domain::entities::auth::VerifiedUser;
+
+/// Audience Supabase issues access tokens for.
+pub const DEFAULT_AUDIENCE: &str = "authenticated";
+
+/// Clock skew allowed when checking expiry, in seconds.
+const LEEWAY_SECS: u64 = 30;
+
+#[derive(Debug, Error)]
+pub enum JwtError {
+    #[error("invalid access token: {0}")]
+    Invalid(#[from] jsonwebtoken::errors::Error),
+    #[error("no key in the JWKS matches the token")]
+    UnknownKey,
+    #[error("token algorithm {0:?} does not match its key")]
+    WrongAlgorithm(Algorithm),
+    #[error("invalid JWKS: {0}")]
+    Jwks(#[from] serde_json::Error),
+    #[error("the JWKS has no usable keys")]
+    NoKeys,
+}
+
+/// Claims of a Supabase access token that make up a [`VerifiedUser`].
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    exp: i64,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    role: Option<String>,
+}
+
+struct Key {
+    id: Option<String>,
+    /// Algorithm the key is meant for, if the JWKS says.
+    algorithm: Option<Algorithm>,
+    key: DecodingKey,
+}
+
+enum Keys {
+    /// The project's shared JWT secret, for HMAC-signed tokens.
+    Secret(DecodingKey),
+    /// The project's published signing keys.
+    Jwks(Vec<Key>),
+}
+
+/// Checks the signature, expiry and audience of Supabase access tokens.
+pub struct JwtVerifier {
+    keys: Keys,
+    audience: String,
+}
+
+impl JwtVerifier {
+    /// Verifies HMAC-signed tokens with the project's JWT secret.
+    pub fn from_secret(secret: &[u8]) -> Self {
+        Self {
+            keys: Keys::Secret(DecodingKey::from_secret(secret)),
+            audience: DEFAULT_AUDIENCE.to_string(),
+        }
+    }
+
+    /// Verifies tokens with the keys of a JWKS document, as served at
+    /// `/auth/v1/.well-known/jwks.json`. Keys that cannot be used for
+    /// verification are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document cannot be parsed or has no usable key.
+    pub fn from_jwks(json: &str) -> Result<Self, JwtError> {
+        let jwks: JwkSet = serde_json::from_str(json)?;
+        let keys: Vec<Key> = jwks
+            .keys
+            .iter()
+            .filter_map(|jwk| {
+                Some(Key {
+                    id: jwk.common.key_id.clone(),
+                    algorithm: match jwk.common.key_algorithm {
+                        Some(algorithm) => Some(Algorithm::from_str(&algorithm.to_string()).ok()?),
+                        None => None,
+                    },
+                    key: DecodingKey::from_jwk(jwk).ok()?,
+                })
+            })
+            .collect();
+        if keys.is_empty() {
+            return Err(JwtError::NoKeys);
+        }
+        Ok(Self {
+            keys: Keys::Jwks(keys),
+            audience: DEFAULT_AUDIENCE.to_string(),
+        })
+    }
+
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience = audience.into();
+        self
+    }
+
+    /// Returns who the token was issued to if it is genuine and current.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the token is malformed, expired, meant for
+    /// another audience or not signed by one of the configured keys.
+    pub fn verify(&self, token: &str) -> Result<VerifiedUser, JwtError> {
+        let header = jsonwebtoken::decode_header(token)?;
+        let key = match &self.keys {
+            Keys::Secret(key) => {
+                if !matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+                    return Err(JwtError::WrongAlgorithm(header.alg));
+                }
+                key
+            }
+            Keys::Jwks(keys) => {
+                let key = match &header.kid {
+                    Some(kid) => keys.iter().find(|key| key.id.as_deref() == Some(kid.as_str())),
+                    // Without a key ID only a lone key is unambiguous
+                    None => keys.first().filter(|_| keys.len() == 1),
+                }
+                .ok_or(JwtError::UnknownKey)?;
+                if key.algorithm.is_some_and(|algorithm| algorithm != header.alg) {
+                    return Err(JwtError::WrongAlgorithm(header.alg));
+                }
+                &key.key
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = LEEWAY_SECS;
+        validation.set_audience(&[&self.audience]);
+        validation.set_required_spec_claims(&["exp", "sub", "aud"]);
+        let claims = jsonwebtoken::decode::<Claims>(token, key, &validation)?.claims;
+
+        Ok(VerifiedUser {
+            user_id: claims.sub,
+            email: claims.email.filter(|email| !email.is_empty()),
+            role: claims.role.unwrap_or_else(|| DEFAULT_AUDIENCE.to_string()),
+            expires_at: claims.exp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    const SECRET: &[u8] = b"super-secret-jwt-token-with-at-least-32-characters";
+
+    fn now() -> i64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+    }
+
+    fn token(header: Header, secret: &[u8], claims: serde_json::Value) -> String {
+        jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(secret)).unwrap()
+    }
+
+    fn with_key_id(kid: &str, algorithm: Algorithm) -> Header {
+        Header {
+            kid: Some(kid.to_string()),
+            ..Header::new(algorithm)
+        }
+    }
+
+    fn claims(exp: i64) -> serde_json::Value {
+        json!({
+            "sub": "8d0fd2b3-9ca7-4d9e-a95f-9e13dded323e",
+            "aud": "authenticated",
+            "exp": exp,
+            "email": "someone@example.com",
+            "role": "authenticated",
+        })
+    }
+
+    #[test]
+    fn test_accepts_tokens_signed_with_the_secret() {
+        let verifier = JwtVerifier::from_secret(SECRET);
+        let user = verifier
+            .verify(&token(Header::default(), SECRET, claims(now() + 3600)))
+            .unwrap();
+        assert_eq!(user.user_id, "8d0fd2b3-9ca7-4d9e-a95f-9e13dded323e");
+        assert_eq!(user.email.as_deref(), Some("someone@example.com"));
+        assert_eq!(user.role, "authenticated");
+    }
+
+    #[test]
+    fn test_rejects_forged_expired_and_foreign_tokens() {
+        let verifier = JwtVerifier::from_secret(SECRET);
+        let forged = token(Header::default(), b"someone-elses-secret", claims(now() + 3600));
+        assert!(verifier.verify(&forged).is_err());
+
+        let expired = token(Header::default(), SECRET, claims(now() - 3600));
+        assert!(verifier.verify(&expired).is_err());
+
+        let mut other_audience = claims(now() + 3600);
+        other_audience["aud"] = json!("service");
+        assert!(verifier.verify(&token(Header::default(), SECRET, other_audience)).is_err());
+
+        assert!(verifier.verify("not.a.token").is_err());
+    }
+
+    #[test]
+    fn test_picks_jwks_key_by_id() {
+        let jwks = json!({"keys": [
+            {"kty": "oct", "kid": "old", "alg": "HS256", "k": "b2xkLXNlY3JldA"},
+            {"kty": "oct", "kid": "new", "alg": "HS256", "k": "bmV3LXNlY3JldA"},
+        ]});
+        let verifier = JwtVerifier::from_jwks(&jwks.to_string()).unwrap();
+        let header = with_key_id("new", Algorithm::HS256);
+        assert!(verifier.verify(&token(header.clone(), b"new-secret", claims(now() + 60))).is_ok());
+        assert!(verifier.verify(&token(header, b"old-secret", claims(now() + 60))).is_err());
+
+        assert!(matches!(
+            verifier.verify(&token(with_key_id("gone", Algorithm::HS256), b"new-secret", claims(now() + 60))),
+            Err(JwtError::UnknownKey)
+        ));
+        // Two keys and no key ID is ambiguous
+        assert!(matches!(
+            verifier.verify(&token(Header::default(), b"new-secret", claims(now() + 60))),
+            Err(JwtError::UnknownKey)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_algorithms_the_key_is_not_for() {
+        let jwks = json!({"keys": [{"kty": "oct", "kid": "k", "alg": "HS512", "k": "bmV3LXNlY3JldA"}]});
+        let verifier = JwtVerifier::from_jwks(&jwks.to_string()).unwrap();
+        assert!(matches!(
+            verifier.verify(&token(with_key_id("k", Algorithm::HS256), b"new-secret", claims(now() + 60))),
+            Err(JwtError::WrongAlgorithm(Algorithm::HS256))
+        ));
+    }
+}
@@ -1,2 +1,39 @@
 pub mod video_converter;
 pub mod check_status;
+pub mod cancel_conversion;
+pub mod get_usage;
+pub mod preview_video;
+#[cfg(feature = "ssr")]
+pub mod config_file;
+#[cfg(feature = "ssr")]
+pub mod conversion_cache;
+#[cfg(feature = "ssr")]
+pub mod downloader;
+#[cfg(feature = "ssr")]
+pub mod file_response;
+#[cfg(feature = "ssr")]
+pub mod job_queue;
+#[cfg(feature = "ssr")]
+pub mod job_store;
+#[cfg(feature = "ssr")]
+pub mod jwt;
+#[cfg(feature = "ssr")]
+pub mod preview_cache;
+#[cfg(feature = "ssr")]
+pub mod quota;
+#[cfg(feature = "ssr")]
+pub mod rate_limit;
+#[cfg(feature = "ssr")]
+pub mod reaper;
+#[cfg(feature = "ssr")]
+pub mod session;
+#[cfg(feature = "ssr")]
+pub mod strategy_config;
+#[cfg(feature = "ssr")]
+pub mod strategy_stats;
+#[cfg(feature = "ssr")]
+pub mod tagging;
+#[cfg(feature = "ssr")]
+pub mod playlist;
+#[cfg(feature = "ssr")]
+pub mod zip_stream;
@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+
+use crate::domain::services::zip_stream::{ZipEntry, ZipSource};
+
+/// Name of the M3U file inside a playlist's ZIP archive.
+pub const M3U_NAME: &str = "playlist.m3u";
+
+/// Longest file name, in characters, given to a track in the archive.
+const MAX_NAME_LEN: usize = 120;
+
+/// A converted track of a playlist, ready to be archived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveTrack {
+    /// File name inside the archive, e.g. `03 - Artist - Title.mp3`.
+    pub name: String,
+    /// Title shown by players for the M3U entry.
+    pub title: String,
+    pub path: PathBuf,
+}
+
+/// The finished tracks of a playlist job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaylistArchive {
+    pub title: Option<String>,
+    pub tracks: Vec<ArchiveTrack>,
+}
+
+impl PlaylistArchive {
+    /// Adds a track under a numbered file name built from `title`.
+    ///
+    /// `position` is the track's one-based place in the playlist, so the
+    /// numbering keeps gaps left by tracks that failed.
+    pub fn push(&mut self, position: usize, title: &str, extension: &str, path: PathBuf) {
+        self.tracks.push(ArchiveTrack {
+            name: format!("{position:02} - {}.{extension}", sanitize_file_name(title)),
+            title: title.to_string(),
+            path,
+        });
+    }
+
+    /// Name for the archive file, without extension.
+    pub fn file_stem(&self) -> String {
+        self.title
+            .as_deref()
+            .map(sanitize_file_name)
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| "playlist".to_string())
+    }
+
+    /// An extended M3U listing the tracks by their names in the archive.
+    pub fn m3u(&self) -> String {
+        let mut m3u = String::from("#EXTM3U\n");
+        if let Some(title) = &self.title {
+            m3u.push_str(&format!("#PLAYLIST:{}\n", single_line(title)));
+        }
+        for track in &self.tracks {
+            m3u.push_str(&format!("#EXTINF:-1,{}\n{}\n", single_line(&track.title), track.name));
+        }
+        m3u
+    }
+
+    /// Every track followed by the M3U, for [`zip_stream`](crate::domain::services::zip_stream::zip_stream).
+    pub fn zip_entries(&self) -> Vec<ZipEntry> {
+        let mut entries: Vec<ZipEntry> = self
+            .tracks
+            .iter()
+            .map(|track| ZipEntry {
+                name: track.name.clone(),
+                source: ZipSource::File(track.path.clone()),
+            })
+            .collect();
+        entries.push(ZipEntry {
+            name: M3U_NAME.to_string(),
+            source: ZipSource::Bytes(self.m3u().into_bytes()),
+        });
+        entries
+    }
+}
+
+/// Replaces characters that are not allowed in file names on common
+/// systems and shortens overly long names.
+pub fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(MAX_NAME_LEN)
+        .collect();
+    // Leading dots would hide the file, trailing ones are dropped by Windows
+    cleaned.trim().trim_matches('.').trim().to_string()
+}
+
+fn single_line(text: &str) -> String {
+    text.lines().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_file_name() {
+        assert_eq!(sanitize_file_name("AC/DC - Back In Black"), "AC_DC - Back In Black");
+        assert_eq!(sanitize_file_name("What? Why: \"Because\""), "What_ Why_ _Because_");
+        assert_eq!(sanitize_file_name("..hidden. "), "hidden");
+        assert_eq!(sanitize_file_name(&"x".repeat(500)).len(), MAX_NAME_LEN);
+    }
+
+    #[test]
+    fn test_m3u_lists_archive_names() {
+        let mut archive = PlaylistArchive {
+            title: Some("Road\nTrip".to_string()),
+            tracks: Vec::new(),
+        };
+        archive.push(1, "Rick Astley - Never Gonna Give You Up", "mp3", PathBuf::from("/a.mp3"));
+        archive.push(3, "a/b", "flac", PathBuf::from("/b.flac"));
+
+        assert_eq!(
+            archive.m3u(),
+            "#EXTM3U\n#PLAYLIST:Road Trip\n\
+             #EXTINF:-1,Rick Astley - Never Gonna Give You Up\n01 - Rick Astley - Never Gonna Give You Up.mp3\n\
+             #EXTINF:-1,a/b\n03 - a_b.flac\n"
+        );
+        assert_eq!(archive.file_stem(), "Road_Trip");
+
+        let names: Vec<String> = archive.zip_entries().into_iter().map(|entry| entry.name).collect();
+        assert_eq!(names, ["01 - Rick Astley - Never Gonna Give You Up.mp3", "03 - a_b.flac", M3U_NAME]);
+    }
+
+    #[test]
+    fn test_untitled_playlist_file_stem() {
+        let archive = PlaylistArchive {
+            title: Some("???".to_string()),
+            tracks: Vec::new(),
+        };
+        assert_eq!(archive.file_stem(), "___");
+        let archive = PlaylistArchive {
+            title: None,
+            tracks: Vec::new(),
+        };
+        assert_eq!(archive.file_stem(), "playlist");
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::domain::entities::preview::VideoPreview;
+
+/// How long a preview is reused by default. Long enough to confirm the
+/// conversion, short enough for a premiere that just started.
+pub const DEFAULT_PREVIEW_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Number of previews kept by default.
+pub const DEFAULT_MAX_PREVIEWS: usize = 500;
+
+/// Recently looked-up previews by video ID, so pasting a link again does
+/// not run the extractor again.
+#[derive(Debug)]
+pub struct PreviewCache {
+    ttl: Duration,
+    max_entries: usize,
+    /// Each preview with when it was looked up.
+    entries: Mutex<HashMap<String, (Instant, VideoPreview)>>,
+}
+
+impl Default for PreviewCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_PREVIEW_TTL, DEFAULT_MAX_PREVIEWS)
+    }
+}
+
+impl PreviewCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            ttl,
+            max_entries: max_entries.max(1),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, video_id: &str) -> Option<VideoPreview> {
+        self.get_at(video_id, Instant::now())
+    }
+
+    pub fn get_at(&self, video_id: &str, now: Instant) -> Option<VideoPreview> {
+        let entries = self.lock_entries();
+        let (looked_up, preview) = entries.get(video_id)?;
+        (now.duration_since(*looked_up) < self.ttl).then(|| preview.clone())
+    }
+
+    pub fn insert(&self, preview: VideoPreview) {
+        self.insert_at(preview, Instant::now());
+    }
+
+    /// Keeps `preview`, first dropping expired ones and, when still full,
+    /// the oldest.
+    pub fn insert_at(&self, preview: VideoPreview, now: Instant) {
+        let mut entries = self.lock_entries();
+        entries.retain(|_, (looked_up, _)| now.duration_since(*looked_up) < self.ttl);
+        if entries.len() >= self.max_entries && !entries.contains_key(&preview.video_id) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (looked_up, _))| *looked_up)
+                .map(|(video_id, _)| video_id.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(preview.video_id.clone(), (now, preview));
+    }
+
+    fn lock_entries(&self) -> std::sync::MutexGuard<'_, HashMap<String, (Instant, VideoPreview)>> {
+        // Every update leaves the map consistent before it can panic
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preview(video_id: &str) -> VideoPreview {
+        VideoPreview {
+            video_id: video_id.to_string(),
+            title: Some(format!("Video {video_id}")),
+            channel: None,
+            duration_secs: None,
+            thumbnail_url: None,
+            availability: None,
+            chapters: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_previews_expire() {
+        let cache = PreviewCache::new(Duration::from_secs(60), 10);
+        let start = Instant::now();
+        cache.insert_at(preview("aaaaaaaaaaa"), start);
+
+        assert_eq!(cache.get_at("aaaaaaaaaaa", start + Duration::from_secs(59)), Some(preview("aaaaaaaaaaa")));
+        assert_eq!(cache.get_at("aaaaaaaaaaa", start + Duration::from_secs(60)), None);
+        assert_eq!(cache.get_at("bbbbbbbbbbb", start), None);
+    }
+
+    #[test]
+    fn test_full_cache_drops_the_oldest_preview() {
+        let cache = PreviewCache::new(Duration::from_secs(60), 2);
+        let start = Instant::now();
+        cache.insert_at(preview("aaaaaaaaaaa"), start);
+        cache.insert_at(preview("bbbbbbbbbbb"), start + Duration::from_secs(1));
+        cache.insert_at(preview("ccccccccccc"), start + Duration::from_secs(2));
+
+        let now = start + Duration::from_secs(3);
+        assert_eq!(cache.get_at("aaaaaaaaaaa", now), None);
+        assert!(cache.get_at("bbbbbbbbbbb", now).is_some());
+        assert!(cache.get_at("ccccccccccc", now).is_some());
+    }
+}
@@ -0,0 +1,48 @@
+use leptos::prelude::*;
+
+use crate::domain::entities::preview::PreviewResponse;
+
+/// Looks up a single video's title, channel, length, thumbnail,
+/// availability and chapters without downloading it, so the user can check
+/// it before converting.
+#[server(PreviewVideo, "/api")]
+pub async fn preview_video(url: String) -> Result<PreviewResponse, ServerFnError> {
+    #[cfg(feature = "ssr")]
+    {
+        use crate::domain::entities::video_ref::VideoRef;
+        use crate::domain::services::rate_limit::{rate_limiter, Endpoint};
+        use crate::domain::services::session::{current_client_ip, current_user, set_retry_after};
+        use crate::domain::services::video_converter::server::preview;
+
+        let Some(user) = current_user() else {
+            return Ok(PreviewResponse::failed("Please sign in to convert videos"));
+        };
+
+        if let Err(limited) = rate_limiter().check(Endpoint::Preview, Some(&user), current_client_ip()) {
+            set_retry_after(&limited);
+            return Ok(PreviewResponse {
+                rate_limit: Some(limited),
+                ..PreviewResponse::failed(limited.message())
+            });
+        }
+
+        let video_ref = match VideoRef::parse(&url) {
+            Ok(video_ref) => video_ref,
+            Err(e) => return Ok(PreviewResponse::failed(e.to_string())),
+        };
+        Ok(match preview(&video_ref).await {
+            Ok(preview) => PreviewResponse {
+                preview: Some(preview),
+                ..PreviewResponse::default()
+            },
+            Err(e) => PreviewResponse::failed(e.to_string()),
+        })
+    }
+
+    #[cfg(not(feature = "ssr"))]
+    {
+        Err(ServerFnError::new(
+            "Server function not available on client",
+        ))
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::domain::entities::job_status::JobStatus;
+use crate::domain::entities::quota::{Meter, Usage};
+use crate::domain::services::config_file::{self, ConfigFileError};
+use crate::domain::services::video_converter::server::ConversionJob;
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// What one user may use. A missing quota means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Quota {
+    /// Videos converted since midnight UTC, counting each playlist track.
+    #[serde(default)]
+    pub conversions_per_day: Option<u64>,
+    /// Minutes of audio converted since midnight UTC.
+    #[serde(default)]
+    pub audio_minutes_per_day: Option<u64>,
+    /// Size of the converted files that can still be downloaded, in MiB.
+    #[serde(default)]
+    pub stored_mb: Option<u64>,
+}
+
+/// Quotas by the role claim of a user's access token, as read from a
+/// `[default]` table and `[roles.NAME]` tables. A role's table replaces the
+/// default one as a whole.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuotaConfig {
+    /// For roles without their own entry.
+    #[serde(default)]
+    pub default: Quota,
+    #[serde(default)]
+    pub roles: HashMap<String, Quota>,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            default: Quota {
+                conversions_per_day: Some(30),
+                audio_minutes_per_day: Some(180),
+                stored_mb: Some(1024),
+            },
+            roles: HashMap::new(),
+        }
+    }
+}
+
+impl QuotaConfig {
+    /// Reads quotas from a `.toml` or `.json` file. Quotas the file leaves
+    /// out are not limited.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed.
+    pub async fn load(path: &Path) -> Result<Self, ConfigFileError> {
+        config_file::load(path).await
+    }
+
+    pub fn for_role(&self, role: &str) -> Quota {
+        self.roles.get(role).copied().unwrap_or(self.default)
+    }
+}
+
+/// Tallies a user's jobs against their quota at `now_ms`.
+///
+/// Playlist jobs count through their tracks. Jobs served from the cache
+/// count too: they still take a slot and storage. Jobs still waiting or
+/// running count with the length expected of them, when known, so that
+/// queueing long videos does not get around the audio minutes; a single
+/// video whose length was never looked up only counts once it finishes.
+pub fn usage(jobs: &[ConversionJob], user_id: &str, quota: Quota, now_ms: u64) -> Usage {
+    let day_start_ms = now_ms - now_ms % DAY_MS;
+    let owned = jobs
+        .iter()
+        .filter(|job| job.owner_id.as_deref() == Some(user_id) && job.playlist.is_none());
+
+    let (mut conversions, mut audio_secs, mut stored_bytes) = (0, 0, 0);
+    for job in owned {
+        let started_ms = job.history.transitions().first().map_or(0, |t| t.at_ms);
+        if started_ms >= day_start_ms {
+            conversions += 1;
+            let expected_secs = job.expected_secs.filter(|_| job.status().is_active());
+            audio_secs += job.audio_secs.or(expected_secs).unwrap_or(0);
+        }
+        if job.status() == JobStatus::Completed {
+            stored_bytes += job.audio_bytes.unwrap_or(0);
+        }
+    }
+
+    Usage {
+        conversions: Meter {
+            used: conversions,
+            limit: quota.conversions_per_day,
+        },
+        audio_minutes: Meter {
+            used: audio_secs.div_ceil(60),
+            limit: quota.audio_minutes_per_day,
+        },
+        stored_bytes: Meter {
+            used: stored_bytes,
+            limit: quota.stored_mb.map(|mb| mb * 1024 * 1024),
+        },
+        resets_at_ms: day_start_ms + DAY_MS,
+    }
+}
+
+/// How many of the leading videos of a playlist, given their lengths, fit
+/// in what is left of `usage`. Videos of unknown length count as converts
+/// only.
+pub fn affordable(usage: &Usage, durations: &[Option<u64>]) -> usize {
+    if usage.exhausted().is_some() {
+        return 0;
+    }
+    let mut count = durations.len();
+    if let Some(remaining) = usage.conversions.remaining() {
+        count = count.min(usize::try_from(remaining).unwrap_or(usize::MAX));
+    }
+    if let Some(remaining_minutes) = usage.audio_minutes.remaining() {
+        let mut secs_left = remaining_minutes * 60;
+        let fitting = durations.iter().take_while(|secs| {
+            let secs = secs.unwrap_or(0);
+            let fits = secs <= secs_left;
+            secs_left = secs_left.saturating_sub(secs);
+            fits
+        });
+        count = count.min(fitting.count());
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::entities::job_status::StatusHistory;
+    use crate::domain::services::video_converter::server::PlaylistJob;
+
+    const NOW_MS: u64 = 20_000 * DAY_MS + 15 * 60 * 60 * 1000;
+
+    fn job(owner: &str, started_ms: u64, status: JobStatus, audio_secs: u64, audio_bytes: u64) -> ConversionJob {
+        let mut job = ConversionJob::new(
+            format!("{owner}-{started_ms}"),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+            "/tmp".into(),
+        );
+        job.owner_id = Some(owner.to_string());
+        job.history = StatusHistory::starting_at(JobStatus::Queued, started_ms);
+        if status != JobStatus::Queued {
+            job.history.transition_at(JobStatus::Downloading, started_ms + 1).unwrap();
+            job.history.transition_at(status, started_ms + 2).unwrap();
+        }
+        job.audio_secs = Some(audio_secs);
+        job.audio_bytes = Some(audio_bytes);
+        job
+    }
+
+    fn quota() -> Quota {
+        Quota {
+            conversions_per_day: Some(3),
+            audio_minutes_per_day: Some(10),
+            stored_mb: None,
+        }
+    }
+
+    #[test]
+    fn test_counts_todays_jobs_and_stored_files() {
+        let jobs = vec![
+            job("alice", NOW_MS - 60_000, JobStatus::Completed, 200, 3_000_000),
+            job("alice", NOW_MS - 30_000, JobStatus::Failed, 0, 0),
+            // Yesterday's conversion still takes storage
+            job("alice", NOW_MS - DAY_MS, JobStatus::Completed, 400, 5_000_000),
+            job("alice", NOW_MS - DAY_MS, JobStatus::Expired, 400, 5_000_000),
+            job("bob", NOW_MS, JobStatus::Completed, 600, 9_000_000),
+        ];
+        let usage = usage(&jobs, "alice", quota(), NOW_MS);
+
+        assert_eq!(usage.conversions, Meter { used: 2, limit: Some(3) });
+        assert_eq!(usage.audio_minutes, Meter { used: 4, limit: Some(10) });
+        assert_eq!(usage.stored_bytes, Meter { used: 8_000_000, limit: None });
+        assert_eq!(usage.resets_at_ms, 20_001 * DAY_MS);
+        assert_eq!(usage.exhausted(), None);
+    }
+
+    #[test]
+    fn test_playlists_count_by_their_tracks() {
+        let mut playlist = job("alice", NOW_MS, JobStatus::Queued, 0, 0);
+        playlist.playlist = Some(PlaylistJob::default());
+        let mut jobs = vec![playlist];
+        for _ in 0..3 {
+            jobs.push(job("alice", NOW_MS, JobStatus::Queued, 0, 0));
+        }
+        let usage = usage(&jobs, "alice", quota(), NOW_MS);
+        assert_eq!(usage.conversions.used, 3);
+        assert!(usage.exhausted().is_some());
+    }
+
+    #[test]
+    fn test_waiting_jobs_count_their_expected_length() {
+        let mut queued = job("alice", NOW_MS, JobStatus::Queued, 0, 0);
+        queued.audio_secs = None;
+        queued.expected_secs = Some(3 * 60 * 60);
+        let mut failed = job("alice", NOW_MS, JobStatus::Failed, 0, 0);
+        failed.audio_secs = None;
+        failed.expected_secs = Some(3 * 60 * 60);
+
+        let usage = usage(&[queued, failed], "alice", quota(), NOW_MS);
+        assert_eq!(usage.audio_minutes.used, 180);
+        assert!(usage.exhausted().is_some());
+    }
+
+    #[test]
+    fn test_playlists_are_trimmed_to_the_remaining_quota() {
+        let usage = |conversions, minutes| Usage {
+            conversions: Meter { used: conversions, limit: Some(30) },
+            audio_minutes: Meter { used: minutes, limit: Some(180) },
+            ..Usage::default()
+        };
+        let tracks = vec![Some(600); 50];
+        // 29 of 30 conversions used leaves room for one track
+        assert_eq!(affordable(&usage(29, 0), &tracks), 1);
+        // 175 of 180 minutes used fits no 10-minute track
+        assert_eq!(affordable(&usage(0, 175), &tracks), 0);
+        assert_eq!(affordable(&usage(0, 150), &tracks), 3);
+        assert_eq!(affordable(&usage(0, 150), &[None, None, Some(600)]), 3);
+        assert_eq!(affordable(&Usage::default(), &tracks), 50);
+    }
+
+    #[test]
+    fn test_roles_fall_back_to_the_default_quota() {
+        let config: QuotaConfig = config_file::parse(
+            Path::new("quotas.toml"),
+            "[default]\nconversions_per_day = 5\n\n[roles.premium]\naudio_minutes_per_day = 600\n",
+        )
+        .unwrap();
+        assert_eq!(config.for_role("authenticated").conversions_per_day, Some(5));
+        assert_eq!(config.for_role("premium").conversions_per_day, None);
+        assert_eq!(config.for_role("premium").audio_minutes_per_day, Some(600));
+    }
+
+    #[test]
+    fn test_shipped_config_matches_defaults() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../config/quotas.toml");
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(config_file::parse::<QuotaConfig>(&path, &contents).unwrap(), QuotaConfig::default());
+    }
+}
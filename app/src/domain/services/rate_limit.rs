@@ -0,0 +1,521 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use http::HeaderMap;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::domain::entities::auth::VerifiedUser;
+use crate::domain::entities::rate_limit::{RateLimitScope, RateLimited};
+use crate::domain::services::config_file::{self, ConfigFileError};
+
+/// How often buckets that have filled up again are forgotten.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Error)]
+pub enum RateLimitConfigError {
+    #[error(transparent)]
+    File(#[from] ConfigFileError),
+    #[error("the {0} limit must allow at least one request and refill")]
+    Empty(String),
+}
+
+/// A token bucket: up to `burst` requests at once, refilled at
+/// `per_minute` requests a minute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limit {
+    pub burst: u32,
+    pub per_minute: u32,
+}
+
+impl Limit {
+    pub const fn new(burst: u32, per_minute: u32) -> Self {
+        Self { burst, per_minute }
+    }
+
+    fn tokens_per_sec(self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+}
+
+/// Limits of one endpoint. A missing limit means no limit.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointLimits {
+    /// Shared by every request from one IP address, signed in or not.
+    #[serde(default)]
+    pub ip: Option<Limit>,
+    /// Per account, for roles without their own entry in `roles`.
+    #[serde(default)]
+    pub user: Option<Limit>,
+    /// Per account, by the role claim of its access token.
+    #[serde(default)]
+    pub roles: HashMap<String, Limit>,
+}
+
+impl EndpointLimits {
+    fn for_role(&self, role: &str) -> Option<Limit> {
+        self.roles.get(role).copied().or(self.user)
+    }
+}
+
+/// Rate limits of the endpoints that cost us YouTube requests or bandwidth,
+/// as read from `[convert]`, `[preview]` and `[download]` tables.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub convert: EndpointLimits,
+    #[serde(default)]
+    pub preview: EndpointLimits,
+    #[serde(default)]
+    pub download: EndpointLimits,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            convert: EndpointLimits {
+                ip: Some(Limit::new(20, 10)),
+                user: Some(Limit::new(5, 2)),
+                roles: HashMap::new(),
+            },
+            preview: EndpointLimits {
+                ip: Some(Limit::new(60, 30)),
+                user: Some(Limit::new(20, 10)),
+                roles: HashMap::new(),
+            },
+            download: EndpointLimits {
+                ip: Some(Limit::new(120, 60)),
+                user: Some(Limit::new(30, 15)),
+                roles: HashMap::new(),
+            },
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// Reads and validates limits from a `.toml` or `.json` file. Endpoints
+    /// the file leaves out are not limited.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, or a limit
+    /// has no burst or never refills.
+    pub async fn load(path: &Path) -> Result<Self, RateLimitConfigError> {
+        let config: Self = config_file::load(path).await?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), RateLimitConfigError> {
+        let endpoints = [
+            (Endpoint::Convert, &self.convert),
+            (Endpoint::Preview, &self.preview),
+            (Endpoint::Download, &self.download),
+        ];
+        for (endpoint, limits) in endpoints {
+            let named = [("ip".to_string(), limits.ip), ("user".to_string(), limits.user)];
+            let roles = limits.roles.iter().map(|(role, limit)| (format!("roles.{role}"), Some(*limit)));
+            for (name, limit) in named.into_iter().chain(roles) {
+                if limit.is_some_and(|limit| limit.burst == 0 || limit.per_minute == 0) {
+                    return Err(RateLimitConfigError::Empty(format!("{}.{name}", endpoint.name())));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn endpoint(&self, endpoint: Endpoint) -> &EndpointLimits {
+        match endpoint {
+            Endpoint::Convert => &self.convert,
+            Endpoint::Preview => &self.preview,
+            Endpoint::Download => &self.download,
+        }
+    }
+}
+
+/// An endpoint with its own buckets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    /// Starting conversions.
+    Convert,
+    /// Looking videos up before converting them.
+    Preview,
+    /// Downloading finished files and playlist archives.
+    Download,
+}
+
+impl Endpoint {
+    fn name(self) -> &'static str {
+        match self {
+            Endpoint::Convert => "convert",
+            Endpoint::Preview => "preview",
+            Endpoint::Download => "download",
+        }
+    }
+}
+
+/// IP address of the client that sent a request, stored in the request's
+/// extensions by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// The address a request came from: the connecting peer, or when the server
+/// runs behind a reverse proxy, the address the proxy appended last to
+/// `X-Forwarded-For`. Earlier entries are whatever the client claimed.
+pub fn client_ip(headers: &HeaderMap, peer: IpAddr, behind_proxy: bool) -> IpAddr {
+    if !behind_proxy {
+        return peer;
+    }
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .next_back()
+        .and_then(|hop| hop.trim().parse().ok())
+        .unwrap_or(peer)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Sender {
+    User(String),
+    /// An IPv4 address, or the /64 network of an IPv6 one since each
+    /// client usually has a whole network to pick addresses from.
+    Ip(IpAddr),
+}
+
+impl Sender {
+    fn ip(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(_) => Sender::Ip(ip),
+            IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+                Some(v4) => Sender::Ip(IpAddr::V4(v4)),
+                None => {
+                    let network = u128::from(v6) & !((1u128 << 64) - 1);
+                    Sender::Ip(IpAddr::V6(Ipv6Addr::from(network)))
+                }
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    limit: Limit,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: Limit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.tokens_per_sec()).min(f64::from(limit.burst));
+        self.limit = limit;
+        self.updated = now;
+    }
+
+    /// Whole seconds until there is a token again.
+    fn retry_after_secs(&self) -> u64 {
+        ((1.0 - self.tokens) / self.limit.tokens_per_sec()).ceil().max(1.0) as u64
+    }
+
+    fn is_full_at(&self, now: Instant) -> bool {
+        let mut bucket = *self;
+        bucket.refill(self.limit, now);
+        bucket.tokens >= f64::from(self.limit.burst)
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    by_sender: HashMap<(Endpoint, Sender), Bucket>,
+    last_pruned: Instant,
+}
+
+/// Token buckets per account and per IP address for each [`Endpoint`].
+///
+/// A request takes a token from every bucket that applies to it, and only
+/// if each of them has one, so a request refused by one limit does not
+/// count against the other. Full buckets are forgotten, which is the same
+/// as never having seen the sender.
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<Buckets>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(RateLimitConfig::default())
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(Buckets {
+                by_sender: HashMap::new(),
+                last_pruned: Instant::now(),
+            }),
+        }
+    }
+
+    /// Counts a request to `endpoint` by `user` from `ip`.
+    ///
+    /// # Errors
+    ///
+    /// Returns which limit was hit and when to retry if the request is over
+    /// a limit; the longest wait wins when both are.
+    pub fn check(
+        &self,
+        endpoint: Endpoint,
+        user: Option<&VerifiedUser>,
+        ip: Option<IpAddr>,
+    ) -> Result<(), RateLimited> {
+        self.check_at(endpoint, user, ip, Instant::now())
+    }
+
+    fn check_at(
+        &self,
+        endpoint: Endpoint,
+        user: Option<&VerifiedUser>,
+        ip: Option<IpAddr>,
+        now: Instant,
+    ) -> Result<(), RateLimited> {
+        let limits = self.config.endpoint(endpoint);
+        let mut applicable = Vec::with_capacity(2);
+        if let (Some(limit), Some(ip)) = (limits.ip, ip) {
+            applicable.push((Sender::ip(ip), limit, RateLimitScope::Ip));
+        }
+        if let Some(user) = user {
+            if let Some(limit) = limits.for_role(&user.role) {
+                applicable.push((Sender::User(user.user_id.clone()), limit, RateLimitScope::User));
+            }
+        }
+        if applicable.is_empty() {
+            return Ok(());
+        }
+
+        let mut buckets = self.lock_buckets();
+        if now.saturating_duration_since(buckets.last_pruned) >= PRUNE_INTERVAL {
+            buckets.by_sender.retain(|_, bucket| !bucket.is_full_at(now));
+            buckets.last_pruned = now;
+        }
+
+        let mut refused: Option<RateLimited> = None;
+        for (sender, limit, scope) in &applicable {
+            let bucket = buckets
+                .by_sender
+                .entry((endpoint, sender.clone()))
+                .or_insert(Bucket {
+                    limit: *limit,
+                    tokens: f64::from(limit.burst),
+                    updated: now,
+                });
+            bucket.refill(*limit, now);
+            if bucket.tokens < 1.0 {
+                let retry_after_secs = bucket.retry_after_secs();
+                if refused.is_none_or(|refused| refused.retry_after_secs < retry_after_secs) {
+                    refused = Some(RateLimited {
+                        scope: *scope,
+                        retry_after_secs,
+                    });
+                }
+            }
+        }
+        if let Some(refused) = refused {
+            return Err(refused);
+        }
+        for (sender, _, _) in applicable {
+            if let Some(bucket) = buckets.by_sender.get_mut(&(endpoint, sender)) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    fn lock_buckets(&self) -> std::sync::MutexGuard<'_, Buckets> {
+        // Buckets are updated one field at a time, each valid on its own
+        self.buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+static LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+
+/// Installs the limiter used by the convert and preview server functions
+/// and the download routes.
+///
+/// Must be called before the first request is handled; afterwards the
+/// default limiter is already in place.
+///
+/// # Errors
+///
+/// Returns the rejected limiter if one was already installed.
+pub fn install_rate_limiter(limiter: RateLimiter) -> Result<(), Box<RateLimiter>> {
+    LIMITER.set(limiter).map_err(Box::new)
+}
+
+/// The limiter installed with [`install_rate_limiter`], or one with the
+/// default limits.
+pub fn rate_limiter() -> &'static RateLimiter {
+    LIMITER.get_or_init(RateLimiter::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    fn user(user_id: &str, role: &str) -> VerifiedUser {
+        VerifiedUser {
+            user_id: user_id.to_string(),
+            email: None,
+            role: role.to_string(),
+            expires_at: 0,
+        }
+    }
+
+    fn limiter(ip: Option<Limit>, user: Option<Limit>) -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            convert: EndpointLimits {
+                ip,
+                user,
+                roles: HashMap::from([("premium".to_string(), Limit::new(10, 60))]),
+            },
+            preview: EndpointLimits::default(),
+            download: EndpointLimits::default(),
+        })
+    }
+
+    const IP: Option<IpAddr> = Some(IpAddr::V4(std::net::Ipv4Addr::new(203, 0, 113, 7)));
+
+    #[test]
+    fn test_bucket_allows_burst_then_refills() {
+        let limiter = limiter(None, Some(Limit::new(2, 6)));
+        let alice = user("alice", "authenticated");
+        let now = Instant::now();
+        assert!(limiter.check_at(Endpoint::Convert, Some(&alice), IP, now).is_ok());
+        assert!(limiter.check_at(Endpoint::Convert, Some(&alice), IP, now).is_ok());
+        assert_eq!(
+            limiter.check_at(Endpoint::Convert, Some(&alice), IP, now),
+            Err(RateLimited {
+                scope: RateLimitScope::User,
+                retry_after_secs: 10,
+            })
+        );
+
+        // Other users and other endpoints have their own buckets
+        assert!(limiter.check_at(Endpoint::Convert, Some(&user("bob", "authenticated")), IP, now).is_ok());
+        assert!(limiter.check_at(Endpoint::Download, Some(&alice), IP, now).is_ok());
+
+        let later = now + Duration::from_secs(10);
+        assert!(limiter.check_at(Endpoint::Convert, Some(&alice), IP, later).is_ok());
+        assert!(limiter.check_at(Endpoint::Convert, Some(&alice), IP, later).is_err());
+    }
+
+    #[test]
+    fn test_roles_get_their_own_limits() {
+        let limiter = limiter(None, Some(Limit::new(1, 1)));
+        let now = Instant::now();
+        let premium = user("carol", "premium");
+        for _ in 0..10 {
+            assert!(limiter.check_at(Endpoint::Convert, Some(&premium), IP, now).is_ok());
+        }
+        assert_eq!(
+            limiter.check_at(Endpoint::Convert, Some(&premium), IP, now).map_err(|e| e.retry_after_secs),
+            Err(1)
+        );
+    }
+
+    #[test]
+    fn test_ip_limit_is_shared_and_refusals_cost_nothing() {
+        let limiter = limiter(Some(Limit::new(2, 1)), Some(Limit::new(1, 2)));
+        let now = Instant::now();
+        let (alice, bob) = (user("alice", "authenticated"), user("bob", "authenticated"));
+        assert!(limiter.check_at(Endpoint::Convert, Some(&alice), IP, now).is_ok());
+        assert!(limiter.check_at(Endpoint::Convert, Some(&bob), IP, now).is_ok());
+
+        // Both limits are hit, so the longer wait is reported
+        assert_eq!(
+            limiter.check_at(Endpoint::Convert, Some(&alice), IP, now).map_err(|e| e.scope),
+            Err(RateLimitScope::Ip)
+        );
+        // Refused by the IP limit, so carol's own allowance is untouched
+        let carol = user("carol", "authenticated");
+        assert!(limiter.check_at(Endpoint::Convert, Some(&carol), IP, now).is_err());
+        let other_ip = Some("198.51.100.1".parse().unwrap());
+        assert!(limiter.check_at(Endpoint::Convert, Some(&carol), other_ip, now).is_ok());
+    }
+
+    #[test]
+    fn test_ipv6_clients_share_their_network_limit() {
+        let limiter = limiter(Some(Limit::new(1, 1)), None);
+        let now = Instant::now();
+        let first = Some("2001:db8:1:2::1".parse().unwrap());
+        let same_network = Some("2001:db8:1:2:ffff::9".parse().unwrap());
+        let other_network = Some("2001:db8:1:3::1".parse().unwrap());
+        assert!(limiter.check_at(Endpoint::Convert, None, first, now).is_ok());
+        assert!(limiter.check_at(Endpoint::Convert, None, same_network, now).is_err());
+        assert!(limiter.check_at(Endpoint::Convert, None, other_network, now).is_ok());
+    }
+
+    #[test]
+    fn test_full_buckets_are_forgotten() {
+        let limiter = limiter(None, Some(Limit::new(1, 60)));
+        let now = Instant::now();
+        assert!(limiter.check_at(Endpoint::Convert, Some(&user("alice", "authenticated")), IP, now).is_ok());
+        assert_eq!(limiter.lock_buckets().by_sender.len(), 1);
+        let later = now + PRUNE_INTERVAL;
+        assert!(limiter.check_at(Endpoint::Convert, Some(&user("bob", "authenticated")), IP, later).is_ok());
+        let buckets = limiter.lock_buckets();
+        assert_eq!(buckets.by_sender.len(), 1);
+        assert!(!buckets.by_sender.contains_key(&(Endpoint::Convert, Sender::User("alice".to_string()))));
+    }
+
+    #[test]
+    fn test_uses_last_forwarded_hop_only_behind_a_proxy() {
+        let peer: IpAddr = "10.0.0.2".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.append("x-forwarded-for", HeaderValue::from_static("1.1.1.1, 203.0.113.7"));
+        assert_eq!(client_ip(&headers, peer, false), peer);
+        assert_eq!(client_ip(&headers, peer, true), IP.unwrap());
+
+        headers.append("x-forwarded-for", HeaderValue::from_static("garbage"));
+        assert_eq!(client_ip(&headers, peer, true), peer);
+        assert_eq!(client_ip(&HeaderMap::new(), peer, true), peer);
+    }
+
+    fn parse(path: &Path, contents: &str) -> Result<RateLimitConfig, RateLimitConfigError> {
+        let config: RateLimitConfig = config_file::parse(path, contents)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn test_shipped_config_matches_defaults() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../config/rate_limits.toml");
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(parse(&path, &contents).unwrap(), RateLimitConfig::default());
+    }
+
+    #[test]
+    fn test_rejects_limits_that_never_allow_a_request() {
+        let path = Path::new("limits.toml");
+        let error = parse(path, "[convert.roles.free]\nburst = 0\nper_minute = 1\n").unwrap_err();
+        assert_eq!(error.to_string(), "the convert.roles.free limit must allow at least one request and refill");
+        assert!(parse(path, "[convert]\nips = {}\n").is_err());
+
+        let json = parse(Path::new("limits.json"), r#"{"download": {"ip": {"burst": 1, "per_minute": 1}}}"#).unwrap();
+        assert_eq!(json.convert, EndpointLimits::default());
+        assert_eq!(json.download.ip, Some(Limit::new(1, 1)));
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use leptos::logging::log;
+
+use crate::domain::entities::job_status::{now_ms, JobStatus};
+use crate::domain::services::job_store::{JobStore, StoreError};
+use crate::domain::services::video_converter::server::JobRunner;
+
+/// How long finished and stuck jobs are kept before their files are deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReaperConfig {
+    /// Time a completed job's file stays downloadable.
+    pub completed_ttl: Duration,
+    /// Time a failed or cancelled job's leftovers are kept.
+    pub failed_ttl: Duration,
+    /// Time after which a job that is still queued or running, but that no
+    /// worker is working on, is given up on.
+    pub abandoned_ttl: Duration,
+    /// Time an expired job is remembered, so its status can still be reported.
+    pub forget_after: Duration,
+    /// Time between two sweeps.
+    pub interval: Duration,
+}
+
+impl Default for ReaperConfig {
+    fn default() -> Self {
+        Self {
+            completed_ttl: Duration::from_secs(60 * 60),
+            failed_ttl: Duration::from_secs(15 * 60),
+            abandoned_ttl: Duration::from_secs(2 * 60 * 60),
+            forget_after: Duration::from_secs(24 * 60 * 60),
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl ReaperConfig {
+    /// Time a job may stay in `status` before the reaper acts on it.
+    fn ttl_for(&self, status: JobStatus) -> Duration {
+        match status {
+            JobStatus::Completed => self.completed_ttl,
+            JobStatus::Failed | JobStatus::Cancelled => self.failed_ttl,
+            JobStatus::Queued | JobStatus::Downloading | JobStatus::Transcoding => {
+                self.abandoned_ttl
+            }
+            JobStatus::Expired => self.forget_after,
+        }
+    }
+}
+
+/// What one sweep did.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReapReport {
+    pub expired: usize,
+    pub forgotten: usize,
+    pub reclaimed_bytes: u64,
+}
+
+/// Expires every job that outlived its TTL as of `now_ms`, deleting its
+/// files, and drops expired jobs that have been remembered long enough.
+///
+/// Jobs in `active` are waiting for or held by a worker and are left alone
+/// however long they take; a 3-hour mix or a long playlist does not change
+/// status while it downloads.
+///
+/// The tracks of a playlist job follow the playlist job, so that a ZIP of
+/// the playlist still has its first tracks when the last one finishes.
+///
+/// # Errors
+///
+/// Returns an error if the job store cannot be read or written.
+pub async fn reap(
+    store: &dyn JobStore,
+    config: &ReaperConfig,
+    active: &HashSet<String>,
+    now_ms: u64,
+) -> Result<ReapReport, StoreError> {
+    let mut report = ReapReport::default();
+    // Tracks already dealt with through their playlist job this sweep
+    let mut handled = HashSet::new();
+
+    for job in store.list().await? {
+        if handled.contains(&job.id) {
+            continue;
+        }
+        // Tracks whose playlist job is gone are reaped like any other job
+        if let Some(parent_id) = &job.parent_id {
+            if store.get(parent_id).await?.is_some() {
+                continue;
+            }
+        }
+
+        let status = job.status();
+        if !status.is_finished() && active.contains(&job.id) {
+            continue;
+        }
+        let age_ms = now_ms.saturating_sub(job.history.updated_at_ms());
+        if u128::from(age_ms) < config.ttl_for(status).as_millis() {
+            continue;
+        }
+
+        if status == JobStatus::Expired {
+            store.remove(&job.id).await?;
+            for track_id in job.track_ids() {
+                store.remove(track_id).await?;
+                handled.insert(track_id.to_string());
+            }
+            report.forgotten += 1;
+            continue;
+        }
+
+        match store
+            .update(&job.id, Box::new(|job| {
+                job.transition(JobStatus::Expired)?;
+                job.audio_path = None;
+                Ok(())
+            }))
+            .await
+        {
+            Ok(_) => {}
+            // The job moved on since it was listed; look at it next sweep
+            Err(StoreError::Transition(_)) => continue,
+            Err(e) => return Err(e),
+        }
+
+        let mut bytes = remove_dir_reporting_size(&job.work_dir).await;
+        for track_id in job.track_ids() {
+            bytes += expire_track(store, track_id).await?;
+            handled.insert(track_id.to_string());
+        }
+        log!("Expired job {} ({}), reclaimed {} bytes", job.id, status, bytes);
+        report.expired += 1;
+        report.reclaimed_bytes += bytes;
+    }
+
+    Ok(report)
+}
+
+/// Expires one track of an expired playlist job and returns the bytes freed.
+async fn expire_track(store: &dyn JobStore, track_id: &str) -> Result<u64, StoreError> {
+    let result = store
+        .update(track_id, Box::new(|job| {
+            if job.status() != JobStatus::Expired {
+                job.transition(JobStatus::Expired)?;
+            }
+            job.audio_path = None;
+            Ok(())
+        }))
+        .await;
+    match result {
+        Ok(track) => Ok(remove_dir_reporting_size(&track.work_dir).await),
+        Err(StoreError::NotFound) => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+/// Runs [`reap`] on the runner's store every `config.interval` for as long
+/// as the process lives, sparing the jobs the runner is working on.
+pub fn spawn_reaper(runner: JobRunner, config: ReaperConfig) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(config.interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let active = runner.active_job_ids();
+            match reap(runner.store().as_ref(), &config, &active, now_ms()).await {
+                Ok(report) if report.expired > 0 || report.forgotten > 0 => log!(
+                    "Reaper expired {} jobs, forgot {} jobs, reclaimed {} bytes",
+                    report.expired,
+                    report.forgotten,
+                    report.reclaimed_bytes
+                ),
+                Ok(_) => {}
+                Err(e) => log!("Reaper sweep failed: {}", e),
+            }
+        }
+    })
+}
+
+/// Deletes `dir` and returns how many bytes of files it held.
+async fn remove_dir_reporting_size(dir: &Path) -> u64 {
+    let bytes = dir_size(dir).await;
+    match tokio::fs::remove_dir_all(dir).await {
+        Ok(()) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(e) => {
+            log!("Failed to remove job directory {}: {}", dir.display(), e);
+            0
+        }
+    }
+}
+
+/// Total size of all files below `dir`.
+pub async fn dir_size(dir: &Path) -> u64 {
+    let mut total = 0;
+    let mut pending: Vec<PathBuf> = vec![dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else {
+                total += metadata.len();
+            }
+        }
+    }
+
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::entities::job_status::StatusHistory;
+    use crate::domain::services::job_store::MemoryJobStore;
+    use crate::domain::services::video_converter::server::ConversionJob;
+
+    const MINUTE_MS: u64 = 60 * 1000;
+
+    async fn job_at(
+        store: &MemoryJobStore,
+        root: &Path,
+        id: &str,
+        path: &[JobStatus],
+        at_ms: u64,
+    ) -> PathBuf {
+        let work_dir = root.join(format!("ytmp3_{id}"));
+        tokio::fs::create_dir_all(&work_dir).await.unwrap();
+        tokio::fs::write(work_dir.join("audio.mp3"), vec![0u8; 1000])
+            .await
+            .unwrap();
+
+        let mut job = ConversionJob::new(
+            id.to_string(),
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string(),
+            work_dir.clone(),
+        );
+        job.history = StatusHistory::starting_at(JobStatus::Queued, at_ms);
+        for status in path {
+            job.history.transition_at(*status, at_ms).unwrap();
+        }
+        job.audio_path = Some(work_dir.join("audio.mp3"));
+        store.insert(job).await.unwrap();
+        work_dir
+    }
+
+    #[tokio::test]
+    async fn test_reap_expires_jobs_past_their_ttl() {
+        let root = tempfile::tempdir().unwrap();
+        let store = MemoryJobStore::default();
+        let config = ReaperConfig::default();
+        let now = 1_000 * MINUTE_MS;
+
+        let completed = [JobStatus::Downloading, JobStatus::Completed];
+        let old_done = job_at(&store, root.path(), "old-done", &completed, now - 61 * MINUTE_MS).await;
+        let fresh_done = job_at(&store, root.path(), "fresh-done", &completed, now - 5 * MINUTE_MS).await;
+        let old_failed = job_at(&store, root.path(), "old-failed", &[JobStatus::Failed], now - 16 * MINUTE_MS).await;
+        let stuck = job_at(&store, root.path(), "stuck", &[JobStatus::Downloading], now - 121 * MINUTE_MS).await;
+        let running = job_at(&store, root.path(), "running", &[JobStatus::Downloading], now - 30 * MINUTE_MS).await;
+
+        let report = reap(&store, &config, &HashSet::new(), now).await.unwrap();
+        assert_eq!(report.expired, 3);
+        assert_eq!(report.forgotten, 0);
+        assert_eq!(report.reclaimed_bytes, 3000);
+
+        for (id, dir, status) in [
+            ("old-done", &old_done, JobStatus::Expired),
+            ("fresh-done", &fresh_done, JobStatus::Completed),
+            ("old-failed", &old_failed, JobStatus::Expired),
+            ("stuck", &stuck, JobStatus::Expired),
+            ("running", &running, JobStatus::Downloading),
+        ] {
+            let job = store.get(id).await.unwrap().unwrap();
+            assert_eq!(job.status(), status, "{id}");
+            assert_eq!(dir.exists(), status != JobStatus::Expired, "{id}");
+            assert_eq!(job.audio_path.is_some(), status != JobStatus::Expired, "{id}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reap_spares_long_running_active_jobs() {
+        let root = tempfile::tempdir().unwrap();
+        let store = MemoryJobStore::default();
+        let config = ReaperConfig::default();
+        let now = 1_000 * MINUTE_MS;
+
+        // A 3-hour mix still downloading, and one whose worker went away
+        let long_mix = job_at(&store, root.path(), "long-mix", &[JobStatus::Downloading], now - 180 * MINUTE_MS).await;
+        let stuck = job_at(&store, root.path(), "stuck", &[JobStatus::Downloading], now - 180 * MINUTE_MS).await;
+        let done = [JobStatus::Downloading, JobStatus::Completed];
+        let old_done = job_at(&store, root.path(), "old-done", &done, now - 61 * MINUTE_MS).await;
+        let active = HashSet::from(["long-mix".to_string(), "old-done".to_string()]);
+
+        let report = reap(&store, &config, &active, now).await.unwrap();
+        assert_eq!(report.expired, 2);
+        assert_eq!(store.get("long-mix").await.unwrap().unwrap().status(), JobStatus::Downloading);
+        assert!(long_mix.exists());
+        assert_eq!(store.get("stuck").await.unwrap().unwrap().status(), JobStatus::Expired);
+        assert!(!stuck.exists());
+        // Finished jobs expire whatever the runner says
+        assert!(!old_done.exists());
+    }
+
+    #[tokio::test]
+    async fn test_reap_forgets_long_expired_jobs() {
+        let root = tempfile::tempdir().unwrap();
+        let store = MemoryJobStore::default();
+        let config = ReaperConfig::default();
+        let now = 10_000 * MINUTE_MS;
+
+        job_at(
+            &store,
+            root.path(),
+            "ancient",
+            &[JobStatus::Failed, JobStatus::Expired],
+            now - 25 * 60 * MINUTE_MS,
+        )
+        .await;
+
+        let report = reap(&store, &config, &HashSet::new(), now).await.unwrap();
+        assert_eq!(report.forgotten, 1);
+        assert!(store.get("ancient").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reap_expires_playlist_tracks_with_their_playlist() {
+        use crate::domain::services::video_converter::server::{PlaylistJob, PlaylistJobTrack};
+
+        let root = tempfile::tempdir().unwrap();
+        let store = MemoryJobStore::default();
+        let config = ReaperConfig::default();
+        let now = 1_000 * MINUTE_MS;
+        let completed = [JobStatus::Downloading, JobStatus::Completed];
+
+        // The first track finished long before the playlist did
+        let first = job_at(&store, root.path(), "first", &completed, now - 90 * MINUTE_MS).await;
+        let second = job_at(&store, root.path(), "second", &completed, now - 70 * MINUTE_MS).await;
+        job_at(&store, root.path(), "mix", &completed, now - 30 * MINUTE_MS).await;
+        let tracks = ["first", "second"]
+            .map(|id| PlaylistJobTrack {
+                job_id: id.to_string(),
+                title: None,
+            })
+            .to_vec();
+        store
+            .update("mix", Box::new(move |job| {
+                job.playlist = Some(PlaylistJob { title: None, tracks });
+                job.audio_path = None;
+                Ok(())
+            }))
+            .await
+            .unwrap();
+        for id in ["first", "second"] {
+            store
+                .update(id, Box::new(|job| {
+                    job.parent_id = Some("mix".to_string());
+                    Ok(())
+                }))
+                .await
+                .unwrap();
+        }
+
+        let report = reap(&store, &config, &HashSet::new(), now).await.unwrap();
+        assert_eq!(report.expired, 0);
+        assert!(first.exists() && second.exists());
+
+        let report = reap(&store, &config, &HashSet::new(), now + 31 * MINUTE_MS).await.unwrap();
+        assert_eq!(report.expired, 1);
+        assert_eq!(report.reclaimed_bytes, 3000);
+        for id in ["mix", "first", "second"] {
+            assert_eq!(store.get(id).await.unwrap().unwrap().status(), JobStatus::Expired, "{id}");
+        }
+        assert!(!first.exists() && !second.exists());
+
+        // Expiring stamped the jobs with the real clock
+        let later = now_ms() + config.forget_after.as_millis() as u64 + MINUTE_MS;
+        let report = reap(&store, &config, &HashSet::new(), later).await.unwrap();
+        assert_eq!(report.forgotten, 1);
+        assert!(store.list().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,135 @@
+use std::net::IpAddr;
+
+use http::HeaderMap;
+use leptos::prelude::*;
+
+use crate::domain::entities::auth::{AuthSession, VerifiedUser};
+use crate::domain::entities::rate_limit::RateLimited;
+use crate::domain::services::rate_limit::ClientIp;
+use crate::domain::services::video_converter::server::AccessError;
+
+/// Cookie the browser keeps the signed-in user's [`AuthSession`] in, as
+/// percent-encoded JSON.
+pub const SESSION_COOKIE: &str = "supabase.auth.token";
+
+/// The session sent with a request, if the user is signed in.
+pub fn session_from_headers(headers: &HeaderMap) -> Option<AuthSession> {
+    headers
+        .get_all(http::header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(cookie::Cookie::split_parse_encoded)
+        .filter_map(Result::ok)
+        .find(|cookie| cookie.name() == SESSION_COOKIE)
+        .and_then(|cookie| serde_json::from_str::<AuthSession>(cookie.value()).ok())
+        .filter(|session| !session.user_id.is_empty())
+}
+
+/// The access token sent with a request, from an `Authorization: Bearer`
+/// header or else the session cookie. It still has to be verified.
+pub fn access_token(headers: &HeaderMap) -> Option<String> {
+    let bearer = headers
+        .get(http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty());
+    match bearer {
+        Some(token) => Some(token.to_string()),
+        None => session_from_headers(headers)
+            .map(|session| session.access_token)
+            .filter(|token| !token.is_empty()),
+    }
+}
+
+/// The verified user calling the current server function, if signed in.
+///
+/// The server's authentication middleware stores the [`VerifiedUser`] in the
+/// request's extensions, which server functions reach through the request
+/// [`Parts`](http::request::Parts) in their context.
+pub fn current_user() -> Option<VerifiedUser> {
+    use_context::<http::request::Parts>()?
+        .extensions
+        .get::<VerifiedUser>()
+        .cloned()
+}
+
+/// The address the current server function was called from, as resolved
+/// by the server.
+pub fn current_client_ip() -> Option<IpAddr> {
+    let parts = use_context::<http::request::Parts>()?;
+    parts.extensions.get::<ClientIp>().map(|ip| ip.0)
+}
+
+/// Turns a failed access check into a server function error with the
+/// matching HTTP status.
+pub fn access_denied(error: &AccessError) -> ServerFnError {
+    if let Some(response) = use_context::<leptos_axum::ResponseOptions>() {
+        response.set_status(error.status_code());
+    }
+    ServerFnError::new(error.to_string())
+}
+
+/// Adds `Retry-After` to a rate-limited server function's response. Its
+/// status stays 200 so the client can read the structured response; the
+/// header is for scripts.
+pub fn set_retry_after(limited: &RateLimited) {
+    if let Some(response) = use_context::<leptos_axum::ResponseOptions>() {
+        response.insert_header(
+            http::header::RETRY_AFTER,
+            http::HeaderValue::from(limited.retry_after_secs),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+
+    fn session(user_id: &str) -> AuthSession {
+        AuthSession {
+            user_id: user_id.to_string(),
+            access_token: "token".to_string(),
+            email: "someone@example.com".to_string(),
+            ..AuthSession::default()
+        }
+    }
+
+    fn headers_with_cookie(cookie: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::COOKIE, HeaderValue::from_str(cookie).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_reads_encoded_session_cookie() {
+        let json = serde_json::to_string(&session("user-1")).unwrap();
+        let cookie = cookie::Cookie::new(SESSION_COOKIE, json).encoded().to_string();
+        let headers = headers_with_cookie(&format!("theme=dark; {cookie}"));
+
+        assert_eq!(session_from_headers(&headers), Some(session("user-1")));
+    }
+
+    #[test]
+    fn test_prefers_bearer_token_over_cookie() {
+        let json = serde_json::to_string(&session("user-1")).unwrap();
+        let cookie = cookie::Cookie::new(SESSION_COOKIE, json).encoded().to_string();
+        let mut headers = headers_with_cookie(&cookie);
+        assert_eq!(access_token(&headers).as_deref(), Some("token"));
+
+        headers.insert(http::header::AUTHORIZATION, HeaderValue::from_static("Bearer abc.def.ghi"));
+        assert_eq!(access_token(&headers).as_deref(), Some("abc.def.ghi"));
+        assert_eq!(access_token(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_ignores_missing_or_invalid_sessions() {
+        assert_eq!(session_from_headers(&HeaderMap::new()), None);
+        assert_eq!(session_from_headers(&headers_with_cookie("supabase.auth.token=nope")), None);
+
+        let json = serde_json::to_string(&session("")).unwrap();
+        let cookie = cookie::Cookie::new(SESSION_COOKIE, json).encoded().to_string();
+        assert_eq!(session_from_headers(&headers_with_cookie(&cookie)), None);
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use leptos::logging::log;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::domain::services::config_file::{self, ConfigFileError};
+use crate::domain::services::downloader::Strategy;
+
+#[derive(Debug, Error)]
+pub enum StrategyConfigError {
+    #[error(transparent)]
+    File(#[from] ConfigFileError),
+    #[error("no strategies are defined")]
+    Empty,
+    #[error("strategy {0:?} is defined more than once")]
+    DuplicateName(String),
+    #[error("strategy {0:?} has a sleep min_secs greater than its max_secs")]
+    InvalidSleep(String),
+}
+
+/// Layout of a strategy file: a list of strategies in the order they are
+/// attempted, under `[[strategy]]` in TOML or `"strategies"` in JSON.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StrategyFile {
+    #[serde(rename = "strategy", alias = "strategies")]
+    strategies: Vec<Strategy>,
+}
+
+/// Reads and validates the strategies defined in a `.toml` or `.json` file.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or parsed, or defines no
+/// strategies, the same name twice, or a sleep interval whose minimum
+/// exceeds its maximum.
+pub async fn load(path: &Path) -> Result<Vec<Strategy>, StrategyConfigError> {
+    validated(config_file::load(path).await?)
+}
+
+#[cfg(test)]
+fn parse(path: &Path, contents: &str) -> Result<Vec<Strategy>, StrategyConfigError> {
+    validated(config_file::parse(path, contents)?)
+}
+
+fn validated(file: StrategyFile) -> Result<Vec<Strategy>, StrategyConfigError> {
+    validate(&file.strategies)?;
+    Ok(file.strategies)
+}
+
+fn validate(strategies: &[Strategy]) -> Result<(), StrategyConfigError> {
+    if strategies.is_empty() {
+        return Err(StrategyConfigError::Empty);
+    }
+    let mut names = HashSet::new();
+    for strategy in strategies {
+        if !names.insert(strategy.name.as_str()) {
+            return Err(StrategyConfigError::DuplicateName(strategy.name.clone()));
+        }
+        if strategy.sleep.is_some_and(|sleep| sleep.min_secs > sleep.max_secs) {
+            return Err(StrategyConfigError::InvalidSleep(strategy.name.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// The strategies jobs are currently attempted with, shared between the
+/// runner and the reloader so a new file takes effect without a restart.
+///
+/// Jobs take a snapshot when they start, so a reload never changes the
+/// strategies of an attempt already under way.
+#[derive(Debug, Clone)]
+pub struct Strategies(Arc<RwLock<Arc<Vec<Strategy>>>>);
+
+impl Strategies {
+    pub fn new(strategies: Vec<Strategy>) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(strategies))))
+    }
+
+    pub fn current(&self) -> Arc<Vec<Strategy>> {
+        // Replacing is a single assignment, so a poisoned lock still holds a valid list
+        self.0
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    pub fn replace(&self, strategies: Vec<Strategy>) {
+        *self.0.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(strategies);
+    }
+}
+
+impl Default for Strategies {
+    fn default() -> Self {
+        Self::new(Strategy::defaults())
+    }
+}
+
+/// Checks `path` every `interval` and loads it into `strategies` whenever
+/// its modification time changes.
+///
+/// A file that fails to load is logged and the previous strategies stay in
+/// use until it is fixed.
+pub fn spawn_reloader(
+    path: PathBuf,
+    strategies: Strategies,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    // Taken before spawning so a change made right after loading is not missed
+    let mut loaded_at = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+            let modified_at = modified(&path).await;
+            if modified_at == loaded_at {
+                continue;
+            }
+            loaded_at = modified_at;
+            match load(&path).await {
+                Ok(loaded) => {
+                    log!("Reloaded {} download strategies from {}", loaded.len(), path.display());
+                    strategies.replace(loaded);
+                }
+                Err(e) => log!("Keeping the current download strategies: {}", e),
+            }
+        }
+    })
+}
+
+async fn modified(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path).await.and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHIPPED: &str = include_str!("../../../../config/strategies.toml");
+
+    #[test]
+    fn test_shipped_file_matches_defaults() {
+        let strategies = parse(Path::new("strategies.toml"), SHIPPED).unwrap();
+        assert_eq!(strategies, Strategy::defaults());
+    }
+
+    #[test]
+    fn test_parses_json_with_defaults() {
+        let strategies = parse(
+            Path::new("strategies.json"),
+            r#"{"strategies": [
+                {"name": "tv", "args": ["--extractor-args", "youtube:player_client=tv"],
+                 "sleep": {"min_secs": 2, "max_secs": 5}},
+                {"name": "plain", "backoff_on": ["HTTP Error 403"]}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            strategies[0],
+            Strategy::new("tv", &["--extractor-args", "youtube:player_client=tv"]).with_sleep(2, 5)
+        );
+        assert_eq!(
+            strategies[0].command_args(),
+            ["--extractor-args", "youtube:player_client=tv", "--sleep-interval", "2", "--max-sleep-interval", "5"]
+        );
+        assert!(strategies[0].should_back_off("HTTP Error 429: Too Many Requests"));
+        assert!(strategies[1].should_back_off("HTTP Error 403: Forbidden"));
+        assert!(!strategies[1].should_back_off("HTTP Error 429: Too Many Requests"));
+    }
+
+    #[test]
+    fn test_rejects_invalid_files() {
+        let toml = Path::new("strategies.toml");
+        assert!(matches!(parse(toml, "strategy = []"), Err(StrategyConfigError::Empty)));
+        assert!(matches!(
+            parse(toml, "[[strategy]]\nname = \"a\"\n[[strategy]]\nname = \"a\""),
+            Err(StrategyConfigError::DuplicateName(name)) if name == "a"
+        ));
+        assert!(matches!(
+            parse(toml, "[[strategy]]\nname = \"a\"\nsleep = { min_secs = 3, max_secs = 1 }"),
+            Err(StrategyConfigError::InvalidSleep(_))
+        ));
+        assert!(matches!(
+            parse(toml, "[[strategy]]\nname = \"a\"\nuser_agnet = \"x\""),
+            Err(StrategyConfigError::File(ConfigFileError::Toml { .. }))
+        ));
+        assert!(matches!(
+            parse(Path::new("strategies.yaml"), ""),
+            Err(StrategyConfigError::File(ConfigFileError::UnknownFormat(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reloader_picks_up_changes_and_keeps_last_good_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("strategies.toml");
+        tokio::fs::write(&path, "[[strategy]]\nname = \"first\"").await.unwrap();
+        let strategies = Strategies::new(load(&path).await.unwrap());
+        let reloader = spawn_reloader(path.clone(), strategies.clone(), Duration::from_millis(10));
+
+        // Modification times can be coarse, so make sure the next one differs
+        let later = SystemTime::now() + Duration::from_secs(5);
+        tokio::fs::write(&path, "[[strategy]]\nname = \"second\"").await.unwrap();
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        wait_for(|| strategies.current()[0].name == "second").await;
+
+        tokio::fs::write(&path, "not toml").await.unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later + Duration::from_secs(5))
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(strategies.current()[0].name, "second");
+        reloader.abort();
+    }
+
+    async fn wait_for(condition: impl Fn() -> bool) {
+        for _ in 0..200 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("condition was never met");
+    }
+}
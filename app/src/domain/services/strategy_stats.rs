@@ -0,0 +1,309 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use leptos::logging::log;
+use serde::Serialize;
+
+use crate::domain::services::downloader::Strategy;
+
+/// Number of recent attempts per strategy that decide its ranking.
+const RECENT_ATTEMPTS: usize = 20;
+
+/// Consecutive bot-detection failures after which a strategy is skipped.
+pub const DEFAULT_BENCH_AFTER: u32 = 3;
+
+/// How long a strategy that keeps getting bot-checked is skipped.
+pub const DEFAULT_BENCH_FOR: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Copy)]
+struct Attempt {
+    succeeded: bool,
+    latency: Duration,
+}
+
+#[derive(Debug, Default)]
+struct Record {
+    successes: u64,
+    failures: u64,
+    recent: VecDeque<Attempt>,
+    /// Bot-detection failures since the last attempt that was not one.
+    bot_failures_in_a_row: u32,
+    benched_until: Option<Instant>,
+}
+
+impl Record {
+    /// Recent success rate, pulled towards one half while there are few
+    /// attempts so one lucky or unlucky job does not decide the order.
+    fn score(&self) -> f64 {
+        let successes = self.recent.iter().filter(|attempt| attempt.succeeded).count();
+        (successes as f64 + 1.0) / (self.recent.len() as f64 + 2.0)
+    }
+
+    /// Mean duration of recent successful attempts.
+    fn success_latency(&self) -> Option<Duration> {
+        let latencies: Vec<Duration> = self
+            .recent
+            .iter()
+            .filter(|attempt| attempt.succeeded)
+            .map(|attempt| attempt.latency)
+            .collect();
+        let count = u32::try_from(latencies.len()).ok().filter(|count| *count > 0)?;
+        Some(latencies.iter().sum::<Duration>() / count)
+    }
+
+    fn is_benched(&self, now: Instant) -> bool {
+        self.benched_until.is_some_and(|until| until > now)
+    }
+
+    fn push(&mut self, attempt: Attempt) {
+        if self.recent.len() == RECENT_ATTEMPTS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(attempt);
+    }
+}
+
+/// How a strategy has been doing, for operators.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StrategyReport {
+    pub name: String,
+    pub successes: u64,
+    pub failures: u64,
+    /// Share of the last attempts that succeeded, if there were any.
+    pub recent_success_rate: Option<f64>,
+    /// Mean duration of recent successful attempts.
+    pub mean_success_ms: Option<u64>,
+    pub bot_failures_in_a_row: u32,
+    /// Seconds until a skipped strategy is tried again.
+    pub benched_for_secs: Option<u64>,
+}
+
+/// Success, failure and latency of each strategy, used to try the ones
+/// that have been working lately first.
+///
+/// Strategies are ranked by their recent success rate, then by how fast
+/// they succeed; ties keep the configured order. A strategy that fails with
+/// bot detection `bench_after` times in a row is skipped for `bench_for`,
+/// unless every strategy is skipped, in which case all are tried. Once the
+/// time is up one more bot-detection failure skips it again.
+#[derive(Debug)]
+pub struct StrategyStats {
+    bench_after: u32,
+    bench_for: Duration,
+    records: Mutex<HashMap<String, Record>>,
+}
+
+impl Default for StrategyStats {
+    fn default() -> Self {
+        Self::new(DEFAULT_BENCH_AFTER, DEFAULT_BENCH_FOR)
+    }
+}
+
+impl StrategyStats {
+    pub fn new(bench_after: u32, bench_for: Duration) -> Self {
+        Self {
+            bench_after: bench_after.max(1),
+            bench_for,
+            records: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The strategies to attempt, best first, without skipped ones.
+    pub fn order(&self, strategies: &[Strategy]) -> Vec<Strategy> {
+        self.order_at(strategies, Instant::now())
+    }
+
+    fn order_at(&self, strategies: &[Strategy], now: Instant) -> Vec<Strategy> {
+        let records = self.lock_records();
+        let mut ranked: Vec<(&Strategy, Option<&Record>)> = strategies
+            .iter()
+            .map(|strategy| (strategy, records.get(&strategy.name)))
+            .collect();
+        if ranked.iter().any(|(_, record)| !record.is_some_and(|r| r.is_benched(now))) {
+            ranked.retain(|(_, record)| !record.is_some_and(|r| r.is_benched(now)));
+        }
+        // Stable, so strategies without a difference keep the configured order
+        ranked.sort_by(|(_, a), (_, b)| {
+            let score = |record: &Option<&Record>| record.map_or(0.5, Record::score);
+            let latency = |record: &Option<&Record>| record.and_then(Record::success_latency);
+            score(b).total_cmp(&score(a)).then_with(|| match (latency(a), latency(b)) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => std::cmp::Ordering::Equal,
+            })
+        });
+        ranked.into_iter().map(|(strategy, _)| strategy.clone()).collect()
+    }
+
+    pub fn record_success(&self, strategy: &str, latency: Duration) {
+        let mut records = self.lock_records();
+        let record = records.entry(strategy.to_string()).or_default();
+        record.successes += 1;
+        record.push(Attempt {
+            succeeded: true,
+            latency,
+        });
+        record.bot_failures_in_a_row = 0;
+        record.benched_until = None;
+    }
+
+    /// Records a failed attempt; `bot_detected` if YouTube blocked it as
+    /// automated, which may get the strategy skipped for a while.
+    pub fn record_failure(&self, strategy: &str, latency: Duration, bot_detected: bool) {
+        self.record_failure_at(strategy, latency, bot_detected, Instant::now());
+    }
+
+    fn record_failure_at(&self, strategy: &str, latency: Duration, bot_detected: bool, now: Instant) {
+        let mut records = self.lock_records();
+        let record = records.entry(strategy.to_string()).or_default();
+        record.failures += 1;
+        record.push(Attempt {
+            succeeded: false,
+            latency,
+        });
+        if !bot_detected {
+            record.bot_failures_in_a_row = 0;
+            return;
+        }
+        record.bot_failures_in_a_row += 1;
+        if record.bot_failures_in_a_row >= self.bench_after && !record.is_benched(now) {
+            log!(
+                "Skipping strategy {} for {}s after {} bot-detection failures in a row",
+                strategy,
+                self.bench_for.as_secs(),
+                record.bot_failures_in_a_row
+            );
+            record.benched_until = Some(now + self.bench_for);
+        }
+    }
+
+    /// A report per strategy, in the order they would be attempted now,
+    /// followed by the skipped ones.
+    pub fn report(&self, strategies: &[Strategy]) -> Vec<StrategyReport> {
+        self.report_at(strategies, Instant::now())
+    }
+
+    fn report_at(&self, strategies: &[Strategy], now: Instant) -> Vec<StrategyReport> {
+        let mut ordered = self.order_at(strategies, now);
+        let skipped: Vec<Strategy> = strategies
+            .iter()
+            .filter(|strategy| !ordered.contains(strategy))
+            .cloned()
+            .collect();
+        ordered.extend(skipped);
+
+        let records = self.lock_records();
+        ordered
+            .into_iter()
+            .map(|strategy| {
+                let record = records.get(&strategy.name);
+                let recent = record.map_or(0, |r| r.recent.len());
+                StrategyReport {
+                    successes: record.map_or(0, |r| r.successes),
+                    failures: record.map_or(0, |r| r.failures),
+                    recent_success_rate: record.filter(|_| recent > 0).map(|r| {
+                        r.recent.iter().filter(|attempt| attempt.succeeded).count() as f64
+                            / recent as f64
+                    }),
+                    mean_success_ms: record
+                        .and_then(Record::success_latency)
+                        .map(|latency| latency.as_millis() as u64),
+                    bot_failures_in_a_row: record.map_or(0, |r| r.bot_failures_in_a_row),
+                    benched_for_secs: record
+                        .and_then(|r| r.benched_until)
+                        .filter(|until| *until > now)
+                        .map(|until| (until - now).as_secs()),
+                    name: strategy.name,
+                }
+            })
+            .collect()
+    }
+
+    fn lock_records(&self) -> std::sync::MutexGuard<'_, HashMap<String, Record>> {
+        // Every update leaves the records consistent before it can panic
+        self.records
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strategies() -> Vec<Strategy> {
+        ["a", "b", "c"].iter().map(|name| Strategy::new(name, &[])).collect()
+    }
+
+    fn names(strategies: &[Strategy]) -> Vec<&str> {
+        strategies.iter().map(|strategy| strategy.name.as_str()).collect()
+    }
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_recently_successful_strategies_go_first() {
+        let stats = StrategyStats::default();
+        assert_eq!(names(&stats.order(&strategies())), ["a", "b", "c"]);
+
+        stats.record_failure("a", SECOND, false);
+        stats.record_success("c", SECOND * 20);
+        stats.record_success("b", SECOND * 5);
+        assert_eq!(names(&stats.order(&strategies())), ["b", "c", "a"]);
+
+        // A better rate beats a faster success
+        stats.record_success("c", SECOND * 20);
+        assert_eq!(names(&stats.order(&strategies())), ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn test_old_attempts_stop_counting() {
+        let stats = StrategyStats::default();
+        for _ in 0..RECENT_ATTEMPTS {
+            stats.record_success("a", SECOND);
+        }
+        stats.record_success("b", SECOND);
+        for _ in 0..RECENT_ATTEMPTS {
+            stats.record_failure("a", SECOND, false);
+        }
+        assert_eq!(names(&stats.order(&strategies())), ["b", "c", "a"]);
+        assert_eq!(stats.report(&strategies())[2].successes, RECENT_ATTEMPTS as u64);
+    }
+
+    #[test]
+    fn test_bot_checked_strategies_are_skipped_for_a_while() {
+        let stats = StrategyStats::new(2, Duration::from_secs(600));
+        let now = Instant::now();
+        stats.record_failure_at("a", SECOND, true, now);
+        assert_eq!(names(&stats.order_at(&strategies(), now)), ["b", "c", "a"]);
+        stats.record_failure_at("a", SECOND, true, now);
+        assert_eq!(names(&stats.order_at(&strategies(), now)), ["b", "c"]);
+
+        let report = stats.report_at(&strategies(), now);
+        assert_eq!(report[2].name, "a");
+        assert_eq!(report[2].benched_for_secs, Some(600));
+        assert_eq!(report[2].recent_success_rate, Some(0.0));
+        assert_eq!(report[0].recent_success_rate, None);
+
+        // Back once the time is up, and skipped again on the next block
+        let later = now + Duration::from_secs(601);
+        assert_eq!(names(&stats.order_at(&strategies(), later)), ["b", "c", "a"]);
+        stats.record_failure_at("a", SECOND, true, later);
+        assert_eq!(names(&stats.order_at(&strategies(), later)), ["b", "c"]);
+
+        // A success clears it
+        stats.record_success("a", SECOND);
+        assert_eq!(names(&stats.order_at(&strategies(), later)), ["b", "c", "a"]);
+        assert_eq!(stats.report_at(&strategies(), later)[2].bot_failures_in_a_row, 0);
+    }
+
+    #[test]
+    fn test_all_strategies_are_tried_when_all_are_skipped() {
+        let stats = StrategyStats::new(1, Duration::from_secs(600));
+        let now = Instant::now();
+        for name in ["a", "b", "c"] {
+            stats.record_failure_at(name, SECOND, true, now);
+        }
+        assert_eq!(names(&stats.order_at(&strategies(), now)), ["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,371 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::process::Command;
+
+use crate::domain::entities::audio_format::AudioFormat;
+use crate::domain::entities::tags::TrackTags;
+
+/// The parts of yt-dlp's info JSON used for tagging.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct VideoMetadata {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub channel: Option<String>,
+    #[serde(default)]
+    pub uploader: Option<String>,
+    /// Set by YouTube Music for official uploads, like `track` and `album`.
+    #[serde(default)]
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub track: Option<String>,
+    #[serde(default)]
+    pub album: Option<String>,
+    #[serde(default)]
+    pub release_year: Option<u32>,
+    /// Upload day as `YYYYMMDD`.
+    #[serde(default)]
+    pub upload_date: Option<String>,
+    /// Length in seconds.
+    #[serde(default)]
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+}
+
+impl VideoMetadata {
+    /// Reads an `.info.json` file written by yt-dlp.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or is not valid JSON.
+    pub async fn read(path: &Path) -> Result<Self, TagError> {
+        let json = tokio::fs::read(path).await?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+/// Separators that split `Artist - Title` style video titles.
+const TITLE_SEPARATORS: [&str; 4] = [" - ", " – ", " — ", " -- "];
+
+/// Words that mark a bracketed part of a title as upload noise, e.g.
+/// `(Official Music Video)` or `[HD]`.
+const TITLE_NOISE: [&str; 10] = [
+    "official", "video", "audio", "lyric", "visualizer", "visualiser", "hd", "hq", "4k", "mv",
+];
+
+/// Derives file tags from the video's metadata.
+///
+/// Proper music metadata wins when YouTube provides it. Otherwise the title
+/// is split on ` - ` into artist and title, falling back to the channel as
+/// the artist, and noise such as `(Official Video)` is removed.
+pub fn derive_tags(metadata: &VideoMetadata) -> TrackTags {
+    let cleaned = metadata.title.as_deref().map(clean_title).unwrap_or_default();
+
+    let (artist, title) = match (&metadata.artist, &metadata.track) {
+        (Some(artist), Some(track)) => (Some(artist.clone()), Some(track.clone())),
+        _ => match split_artist_title(&cleaned) {
+            Some((artist, title)) => (Some(artist), Some(title)),
+            None => (channel_artist(metadata), Some(cleaned)),
+        },
+    };
+
+    let year = metadata
+        .release_year
+        .map(|year| year.to_string())
+        .or_else(|| metadata.upload_date.as_ref().and_then(|date| date.get(..4)).map(str::to_string));
+
+    TrackTags {
+        title,
+        artist,
+        album: metadata.album.clone(),
+        year,
+    }
+    .normalized()
+}
+
+/// Removes bracketed noise such as `(Official Video)` or `[HD]` from a title.
+pub fn clean_title(title: &str) -> String {
+    let mut cleaned = String::with_capacity(title.len());
+    let mut rest = title;
+
+    while let Some(open) = rest.find(['(', '[']) {
+        let close_char = if rest[open..].starts_with('(') { ')' } else { ']' };
+        let Some(close) = rest[open..].find(close_char).map(|close| open + close) else {
+            break;
+        };
+        let inner = rest[open + 1..close].to_lowercase();
+        let is_noise = inner
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| TITLE_NOISE.contains(&word));
+
+        cleaned.push_str(&rest[..open]);
+        if !is_noise {
+            cleaned.push_str(&rest[open..=close]);
+        }
+        rest = &rest[close + 1..];
+    }
+    cleaned.push_str(rest);
+
+    let collapsed = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    collapsed.trim_end_matches([' ', '-', '|']).trim().to_string()
+}
+
+/// Splits `Artist - Title` at the first separator.
+pub fn split_artist_title(title: &str) -> Option<(String, String)> {
+    TITLE_SEPARATORS
+        .iter()
+        .filter_map(|separator| title.split_once(separator))
+        .min_by_key(|(artist, _)| artist.len())
+        .map(|(artist, title)| (artist.trim().to_string(), title.trim().to_string()))
+        .filter(|(artist, title)| !artist.is_empty() && !title.is_empty())
+}
+
+/// The channel name as an artist, without YouTube's ` - Topic` and `VEVO`
+/// decorations.
+fn channel_artist(metadata: &VideoMetadata) -> Option<String> {
+    let channel = metadata.channel.as_ref().or(metadata.uploader.as_ref())?;
+    let name = channel
+        .strip_suffix(" - Topic")
+        .or_else(|| channel.strip_suffix("VEVO"))
+        .unwrap_or(channel);
+    Some(name.trim().to_string())
+}
+
+/// Returns `true` if ffmpeg can attach a cover picture to this format.
+pub fn embeds_cover(format: AudioFormat) -> bool {
+    matches!(format, AudioFormat::Mp3 | AudioFormat::M4a | AudioFormat::Flac)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TagError {
+    #[error("could not run the tagger: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not read video metadata: {0}")]
+    Metadata(#[from] serde_json::Error),
+    #[error("tagging failed: {stderr}")]
+    Failed { stderr: String },
+}
+
+/// Writes tags and cover art into a finished audio file in place.
+#[async_trait]
+pub trait Tagger: Send + Sync + std::fmt::Debug {
+    /// Tags `audio` and, if given and supported by `format`, embeds `cover`
+    /// cropped to a square as the front cover.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file could not be rewritten; the original
+    /// file is left untouched in that case.
+    async fn write_tags(
+        &self,
+        audio: &Path,
+        format: AudioFormat,
+        tags: &TrackTags,
+        cover: Option<&Path>,
+    ) -> Result<(), TagError>;
+}
+
+/// Rewrites files with `ffmpeg`, copying the audio stream unchanged.
+#[derive(Debug, Clone)]
+pub struct FfmpegTagger {
+    program: PathBuf,
+}
+
+impl FfmpegTagger {
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+        }
+    }
+}
+
+impl Default for FfmpegTagger {
+    fn default() -> Self {
+        Self::new("ffmpeg")
+    }
+}
+
+#[async_trait]
+impl Tagger for FfmpegTagger {
+    async fn write_tags(
+        &self,
+        audio: &Path,
+        format: AudioFormat,
+        tags: &TrackTags,
+        cover: Option<&Path>,
+    ) -> Result<(), TagError> {
+        let tagged = audio.with_extension(format!("tagged.{}", format.extension()));
+        let cover = cover.filter(|_| embeds_cover(format));
+
+        let mut cmd = Command::new(&self.program);
+        cmd.arg("-y").arg("-loglevel").arg("error").arg("-i").arg(audio);
+        if let Some(cover) = cover {
+            cmd.arg("-i").arg(cover);
+        }
+        cmd.args(["-map", "0:a", "-c:a", "copy", "-map_metadata", "-1"]);
+        if cover.is_some() {
+            // Centre-crop the thumbnail to a square, as players expect
+            cmd.args([
+                "-map", "1:v",
+                "-c:v", "mjpeg",
+                "-vf", "crop=min(iw\\,ih):min(iw\\,ih)",
+                "-disposition:v", "attached_pic",
+                "-metadata:s:v", "title=Album cover",
+                "-metadata:s:v", "comment=Cover (front)",
+            ]);
+        }
+        for (key, value) in [
+            ("title", &tags.title),
+            ("artist", &tags.artist),
+            ("album", &tags.album),
+            ("date", &tags.year),
+        ] {
+            if let Some(value) = value {
+                cmd.arg("-metadata").arg(format!("{key}={value}"));
+            }
+        }
+        if format == AudioFormat::Mp3 {
+            // ID3v2.3 is what most players and libraries read
+            cmd.args(["-id3v2_version", "3"]);
+        }
+        cmd.arg(&tagged)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let output = cmd.output().await?;
+        if !output.status.success() {
+            let _ = tokio::fs::remove_file(&tagged).await;
+            return Err(TagError::Failed {
+                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            });
+        }
+
+        tokio::fs::rename(&tagged, audio).await?;
+        Ok(())
+    }
+}
+
+/// A tagger that only records what it was asked to write.
+#[derive(Debug, Default)]
+pub struct FakeTagger {
+    tagged: Mutex<Vec<(TrackTags, bool)>>,
+}
+
+impl FakeTagger {
+    /// Tags of every file tagged so far, with whether a cover was embedded.
+    pub fn tagged(&self) -> Vec<(TrackTags, bool)> {
+        self.tagged
+            .lock()
+            .map(|tagged| tagged.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl Tagger for FakeTagger {
+    async fn write_tags(
+        &self,
+        _audio: &Path,
+        format: AudioFormat,
+        tags: &TrackTags,
+        cover: Option<&Path>,
+    ) -> Result<(), TagError> {
+        if let Ok(mut tagged) = self.tagged.lock() {
+            tagged.push((tags.clone(), cover.is_some() && embeds_cover(format)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(title: &str, channel: &str) -> VideoMetadata {
+        VideoMetadata {
+            title: Some(title.to_string()),
+            channel: Some(channel.to_string()),
+            upload_date: Some("20091025".to_string()),
+            ..VideoMetadata::default()
+        }
+    }
+
+    #[test]
+    fn test_clean_title() {
+        for (title, cleaned) in [
+            ("Never Gonna Give You Up (Official Music Video)", "Never Gonna Give You Up"),
+            ("Song [HD] (Lyric Video)", "Song"),
+            ("Song (Live at Wembley)", "Song (Live at Wembley)"),
+            ("Song (feat. Someone) [Official Audio]", "Song (feat. Someone)"),
+            ("Unbalanced (Official", "Unbalanced (Official"),
+        ] {
+            assert_eq!(clean_title(title), cleaned, "{title}");
+        }
+    }
+
+    #[test]
+    fn test_derive_tags() {
+        let cases = [
+            (
+                metadata("Rick Astley - Never Gonna Give You Up (Official Music Video)", "Rick Astley"),
+                ("Rick Astley", "Never Gonna Give You Up"),
+            ),
+            (
+                metadata("Daft Punk – One More Time [HD]", "Some Uploader"),
+                ("Daft Punk", "One More Time"),
+            ),
+            (
+                metadata("Bohemian Rhapsody", "Queen - Topic"),
+                ("Queen", "Bohemian Rhapsody"),
+            ),
+            (
+                metadata("Hello (Official Video)", "AdeleVEVO"),
+                ("Adele", "Hello"),
+            ),
+        ];
+        for (metadata, (artist, title)) in cases {
+            let tags = derive_tags(&metadata);
+            assert_eq!(tags.artist.as_deref(), Some(artist), "{:?}", metadata.title);
+            assert_eq!(tags.title.as_deref(), Some(title), "{:?}", metadata.title);
+            assert_eq!(tags.year.as_deref(), Some("2009"));
+        }
+    }
+
+    #[test]
+    fn test_music_metadata_wins_over_title_heuristics() {
+        let metadata = VideoMetadata {
+            artist: Some("Rick Astley".to_string()),
+            track: Some("Never Gonna Give You Up".to_string()),
+            album: Some("Whenever You Need Somebody".to_string()),
+            release_year: Some(1987),
+            ..metadata("Never Gonna Give You Up - Remastered", "Rick Astley - Topic")
+        };
+        assert_eq!(
+            derive_tags(&metadata),
+            TrackTags {
+                title: Some("Never Gonna Give You Up".to_string()),
+                artist: Some("Rick Astley".to_string()),
+                album: Some("Whenever You Need Somebody".to_string()),
+                year: Some("1987".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_reads_ytdlp_info_json() {
+        let json = r#"{"id": "dQw4w9WgXcQ", "title": "Video", "duration": 212.0, "formats": [], "release_year": null}"#;
+        let metadata: VideoMetadata = serde_json::from_str(json).unwrap();
+        assert_eq!(metadata.id.as_deref(), Some("dQw4w9WgXcQ"));
+        assert_eq!(metadata.duration, Some(212.0));
+        assert_eq!(metadata.release_year, None);
+    }
+}
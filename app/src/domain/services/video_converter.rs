@@ -1,40 +1,198 @@
 use leptos::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::domain::entities::audio_format::AudioFormat;
+use crate::domain::entities::clip::ClipRange;
+use crate::domain::entities::conversion_error::ConversionError;
+use crate::domain::entities::job_status::JobStatus;
+use crate::domain::entities::playlist::PlaylistStatus;
+use crate::domain::entities::progress::JobProgress;
+use crate::domain::entities::quality::QualityProfile;
+use crate::domain::entities::rate_limit::RateLimited;
+use crate::domain::entities::tags::TrackTags;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ConvertResponse {
     pub id: String,
-    pub status: String,
+    pub status: JobStatus,
     pub message: String,
+    /// Why the job failed, next to the message meant for people.
+    #[serde(default)]
+    pub error_code: Option<ConversionError>,
+    /// Set when the request was turned away for coming too often.
+    #[serde(default)]
+    pub rate_limit: Option<RateLimited>,
+    /// Format the job converts to.
+    #[serde(default)]
+    pub format: AudioFormat,
+    /// Encoder settings the job converts with.
+    #[serde(default)]
+    pub quality: QualityProfile,
+    /// Section of the video being converted, if not all of it.
+    #[serde(default)]
+    pub clip: Option<ClipRange>,
+    /// Tags written into the finished file.
+    #[serde(default)]
+    pub tags: Option<TrackTags>,
+    /// One-based place in the queue while the job waits for a worker.
+    #[serde(default)]
+    pub queue_position: Option<usize>,
+    /// Progress of the current download, while there is one.
+    #[serde(default)]
+    pub progress: Option<JobProgress>,
+    /// Per-track status, for playlist jobs.
+    #[serde(default)]
+    pub playlist: Option<PlaylistStatus>,
+}
+
+impl ConvertResponse {
+    /// A response for a request that did not produce a running job.
+    pub fn failed(id: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            status: JobStatus::Failed,
+            message: message.into(),
+            error_code: None,
+            rate_limit: None,
+            format: AudioFormat::default(),
+            quality: QualityProfile::default(),
+            clip: None,
+            tags: None,
+            queue_position: None,
+            progress: None,
+            playlist: None,
+        }
+    }
 }
 
 #[server(ConvertVideo, "/api")]
-pub async fn convert_video(url: String) -> Result<ConvertResponse, ServerFnError> {
+pub async fn convert_video(
+    url: String,
+    #[server(default)] format: AudioFormat,
+    #[server(default)] quality: QualityProfile,
+    #[server(default)] clip_start: String,
+    #[server(default)] clip_end: String,
+    #[server(default)] tags: TrackTags,
+    #[server(default)] whole_playlist: bool,
+) -> Result<ConvertResponse, ServerFnError> {
     #[cfg(feature = "ssr")]
     {
+        use crate::domain::entities::video_ref::VideoRef;
+        use crate::domain::services::rate_limit::{rate_limiter, Endpoint};
+        use crate::domain::services::session::{current_client_ip, current_user, set_retry_after};
         use crate::domain::services::video_converter::server::{
-            is_valid_youtube_url, start_conversion,
+            start_conversion, usage, ConversionOptions, StartError,
+        };
+        use leptos::logging::log;
+
+        let Some(user) = current_user() else {
+            return Ok(ConvertResponse::failed(
+                String::new(),
+                "Please sign in to convert videos",
+            ));
         };
 
-        if url.is_empty() || !is_valid_youtube_url(&url) {
+        if let Err(limited) = rate_limiter().check(Endpoint::Convert, Some(&user), current_client_ip()) {
+            set_retry_after(&limited);
             return Ok(ConvertResponse {
-                id: String::new(),
-                status: "error".to_string(),
-                message: "Please enter a valid YouTube URL".to_string(),
+                rate_limit: Some(limited),
+                ..ConvertResponse::failed(String::new(), limited.message())
             });
         }
 
-        match start_conversion(url).await {
+        match usage(&user.user_id, &user.role).await {
+            Ok(usage) => {
+                if let Some(message) = usage.exhausted() {
+                    return Ok(ConvertResponse::failed(String::new(), message));
+                }
+            }
+            Err(e) => log!("Quota of user {} could not be checked: {}", user.user_id, e),
+        }
+
+        if url.trim().is_empty() {
+            return Ok(ConvertResponse::failed(
+                String::new(),
+                "Please enter a valid YouTube URL",
+            ));
+        }
+        let video_ref = match VideoRef::parse(&url) {
+            Ok(video_ref) => video_ref,
+            Err(e) => return Ok(ConvertResponse::failed(String::new(), e.to_string())),
+        };
+
+        if let Err(e) = quality.validate(format) {
+            return Ok(ConvertResponse::failed(String::new(), e.to_string()));
+        }
+
+        // A watch link inside a playlist converts the one video unless asked otherwise
+        let whole_playlist =
+            video_ref.is_playlist() || (whole_playlist && video_ref.playlist_id.is_some());
+        // Only the IDs go on to yt-dlp, never the rest of what was pasted
+        let canonical_url = if whole_playlist {
+            video_ref.playlist_url()
+        } else {
+            video_ref.video_url()
+        };
+        let Some(canonical_url) = canonical_url else {
+            return Ok(ConvertResponse::failed(
+                String::new(),
+                "Please enter a valid YouTube URL",
+            ));
+        };
+
+        let clip = if whole_playlist {
+            if !clip_start.trim().is_empty() || !clip_end.trim().is_empty() {
+                return Ok(ConvertResponse::failed(
+                    String::new(),
+                    "Clips can only be cut from single videos, not whole playlists",
+                ));
+            }
+            None
+        } else {
+            match ClipRange::from_inputs(&clip_start, &clip_end, video_ref.start_secs) {
+                Ok(clip) => clip,
+                Err(e) => return Ok(ConvertResponse::failed(String::new(), e.to_string())),
+            }
+        };
+
+        let mut tags = tags.normalized();
+        if whole_playlist {
+            // One title cannot fit every track
+            tags.title = None;
+        }
+        if let Err(e) = tags.validate() {
+            return Ok(ConvertResponse::failed(String::new(), e.to_string()));
+        }
+
+        let options = ConversionOptions {
+            format,
+            quality,
+            clip,
+            tags,
+            whole_playlist,
+            owner_id: Some(user.user_id),
+            owner_role: Some(user.role),
+        };
+        match start_conversion(canonical_url, options).await {
             Ok(job_id) => Ok(ConvertResponse {
                 id: job_id,
-                status: "processing".to_string(),
+                status: JobStatus::Queued,
                 message: "Conversion started".to_string(),
+                error_code: None,
+                rate_limit: None,
+                format,
+                quality,
+                clip,
+                tags: None,
+                queue_position: None,
+                progress: None,
+                playlist: whole_playlist.then(PlaylistStatus::default),
             }),
-            Err(e) => Ok(ConvertResponse {
-                id: String::new(),
-                status: "error".to_string(),
-                message: format!("Failed to start conversion: {e}"),
-            }),
+            Err(e @ StartError::Busy(_)) => Ok(ConvertResponse::failed(String::new(), e.to_string())),
+            Err(e) => Ok(ConvertResponse::failed(
+                String::new(),
+                format!("Failed to start conversion: {e}"),
+            )),
         }
     }
 
@@ -48,405 +206,1988 @@ pub async fn convert_video(url: String) -> Result<ConvertResponse, ServerFnError
 
 #[cfg(feature = "ssr")]
 pub mod server {
-    use std::collections::HashMap;
-    use std::path::PathBuf;
-    use std::process::Stdio;
+    use std::collections::{HashMap, HashSet};
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex, OnceLock};
+    use std::time::{Duration, Instant};
+    use futures::Stream;
     use leptos::logging::log;
-    use tempfile::TempDir;
-    use tokio::process::Command;
+    use serde::{Deserialize, Serialize};
+    use tokio::sync::mpsc::UnboundedReceiver;
+    use tokio::sync::{broadcast, Notify};
     use uuid::Uuid;
 
+    use crate::domain::entities::audio_format::AudioFormat;
+    use crate::domain::entities::clip::ClipRange;
+    use crate::domain::entities::conversion_error::ConversionError;
+    use crate::domain::entities::file_name::FileNameTemplate;
+    use crate::domain::entities::job_status::{now_ms, InvalidTransition, JobStatus, StatusHistory};
+    use crate::domain::entities::playlist::{PlaylistStatus, PlaylistTrack};
+    use crate::domain::entities::preview::VideoPreview;
+    use crate::domain::entities::progress::JobProgress;
+    use crate::domain::entities::quality::QualityProfile;
+    use crate::domain::entities::quota::Usage;
+    use crate::domain::entities::tags::TrackTags;
+    use crate::domain::services::conversion_cache::{CacheKey, CachedFile, ConversionCache};
+    use crate::domain::services::downloader::{
+        Download, DownloadRequest, Downloader, PlaylistListing, ProgressEvent, Strategy,
+        YtDlpDownloader,
+    };
+    use crate::domain::services::job_queue::{JobQueue, QueueFull};
+    use crate::domain::services::job_store::{JobStore, JobUpdate, MemoryJobStore, StoreError};
+    use crate::domain::services::playlist::{sanitize_file_name, PlaylistArchive};
+    use crate::domain::services::preview_cache::PreviewCache;
+    use crate::domain::services::quota::{self, QuotaConfig};
+    use crate::domain::services::strategy_config::Strategies;
+    use crate::domain::services::strategy_stats::{StrategyReport, StrategyStats};
+    use crate::domain::services::tagging::{derive_tags, FfmpegTagger, Tagger, VideoMetadata};
+    use crate::domain::entities::video_ref::VideoRef;
     use crate::domain::services::video_converter::ConvertResponse;
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct ConversionJob {
         pub id: String,
-        pub temp_dir: TempDir,
-        pub mp3_path: Option<PathBuf>,
-        pub status: String,
+        pub url: String,
+        /// The job's private `ytmp3_*` directory holding all of its files.
+        pub work_dir: PathBuf,
+        /// Format requested for the output file.
+        #[serde(default)]
+        pub format: AudioFormat,
+        /// Encoder settings requested for the output file.
+        #[serde(default)]
+        pub quality: QualityProfile,
+        /// Section of the video to convert, or `None` for all of it.
+        #[serde(default)]
+        pub clip: Option<ClipRange>,
+        /// Tags the user asked for, replacing the derived ones.
+        #[serde(default)]
+        pub tag_overrides: TrackTags,
+        /// Tags written into the finished file.
+        #[serde(default)]
+        pub tags: Option<TrackTags>,
+        /// Title of the video as published, once completed, if known.
+        #[serde(default)]
+        pub title: Option<String>,
+        #[serde(alias = "mp3_path")]
+        pub audio_path: Option<PathBuf>,
+        pub history: StatusHistory,
         pub error: Option<String>,
+        /// Classification of `error`.
+        #[serde(default)]
+        pub error_code: Option<ConversionError>,
+        /// Progress of the running download attempt.
+        #[serde(default)]
+        pub progress: Option<JobProgress>,
+        /// Set on playlist jobs, which convert their tracks as child jobs.
+        #[serde(default)]
+        pub playlist: Option<PlaylistJob>,
+        /// The playlist job this job converts a track for.
+        #[serde(default)]
+        pub parent_id: Option<String>,
+        /// ID of the user who started the job; only they may see it.
+        #[serde(default)]
+        pub owner_id: Option<String>,
+        /// Role of the owner when the job was started, for their quotas.
+        #[serde(default)]
+        pub owner_role: Option<String>,
+        /// Length the converted audio is expected to have, if known before
+        /// converting, so waiting jobs count against the audio quota.
+        #[serde(default)]
+        pub expected_secs: Option<u64>,
+        /// Length of the converted audio, once completed, if known.
+        #[serde(default)]
+        pub audio_secs: Option<u64>,
+        /// Size of the converted file, once completed.
+        #[serde(default)]
+        pub audio_bytes: Option<u64>,
+    }
+
+    /// The tracks of a playlist job.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct PlaylistJob {
+        pub title: Option<String>,
+        /// Empty until the playlist has been listed.
+        pub tracks: Vec<PlaylistJobTrack>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct PlaylistJobTrack {
+        pub job_id: String,
+        /// Video title from the playlist listing.
+        pub title: Option<String>,
+    }
+
+    impl ConversionJob {
+        pub fn new(id: String, url: String, work_dir: PathBuf) -> Self {
+            Self {
+                id,
+                url,
+                work_dir,
+                format: AudioFormat::default(),
+                quality: QualityProfile::default(),
+                clip: None,
+                tag_overrides: TrackTags::default(),
+                tags: None,
+                title: None,
+                audio_path: None,
+                history: StatusHistory::new(),
+                error: None,
+                error_code: None,
+                progress: None,
+                playlist: None,
+                parent_id: None,
+                owner_id: None,
+                owner_role: None,
+                expected_secs: None,
+                audio_secs: None,
+                audio_bytes: None,
+            }
+        }
+
+        pub fn status(&self) -> JobStatus {
+            self.history.current()
+        }
+
+        /// IDs of the child jobs of a playlist job.
+        pub fn track_ids(&self) -> impl Iterator<Item = &str> {
+            self.playlist
+                .iter()
+                .flat_map(|playlist| &playlist.tracks)
+                .map(|track| track.job_id.as_str())
+        }
+
+        /// Moves the job to `next`, recording when the transition happened.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if `next` is not reachable from the current status.
+        pub fn transition(&mut self, next: JobStatus) -> Result<(), InvalidTransition> {
+            self.history.transition(next)
+        }
+    }
+
+    /// Directory under which each job gets its own `ytmp3_*` temp dir.
+    pub const DEFAULT_WORK_DIR: &str = "/home/app";
+
+    /// Number of conversions that run at the same time by default.
+    pub const DEFAULT_WORKERS: usize = 2;
+
+    /// Number of jobs that may wait for a worker before new ones are refused.
+    pub const DEFAULT_MAX_QUEUE_LEN: usize = 20;
+
+    /// Number of videos converted from one playlist by default.
+    pub const DEFAULT_MAX_PLAYLIST_LEN: usize = 50;
+
+    /// Minimum time between two progress writes to the job store.
+    const PROGRESS_WRITE_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// How often a status stream re-reads its job even without an update
+    /// notification, to catch changes made outside the runner such as expiry.
+    const STATUS_RECHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+    /// Number of update notifications buffered for slow status streams.
+    const UPDATE_CHANNEL_CAPACITY: usize = 256;
+
+    /// Longest a preview lookup may take before the user is told to retry.
+    const PREVIEW_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// What the user asked for besides the URL.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub struct ConversionOptions {
+        pub format: AudioFormat,
+        pub quality: QualityProfile,
+        pub clip: Option<ClipRange>,
+        /// Tags replacing the ones derived from the video.
+        pub tags: TrackTags,
+        /// Convert every video of the URL's playlist instead of just one.
+        pub whole_playlist: bool,
+        /// ID of the signed-in user starting the conversion.
+        pub owner_id: Option<String>,
+        /// Role of that user, whose quotas apply.
+        pub owner_role: Option<String>,
+    }
+
+    /// Where a finished job's output file is, for the caller to open.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct AudioFile {
+        pub job_id: String,
+        pub format: AudioFormat,
+        pub path: PathBuf,
+        /// Title of the video as published.
+        pub title: Option<String>,
+        pub tags: Option<TrackTags>,
+    }
+
+    impl AudioFile {
+        /// The name to download the file under, following `template`.
+        ///
+        /// Falls back to the video's title, then to the job ID, when the
+        /// template needs a tag the file does not have.
+        pub fn file_name(&self, template: &FileNameTemplate) -> String {
+            let mut tags = self.tags.clone().unwrap_or_default();
+            tags.title = tags.title.or_else(|| self.title.clone());
+            let name = template
+                .render(&tags)
+                .or_else(|| self.title.clone())
+                .map(|name| sanitize_file_name(&name))
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| self.job_id.clone());
+            format!("{name}.{}", self.format.extension())
+        }
+    }
+
+    /// Why a user may not see a job.
+    #[derive(Debug, thiserror::Error)]
+    pub enum AccessError {
+        #[error("Job not found")]
+        NotFound,
+        #[error("This conversion belongs to another user")]
+        Forbidden,
+        #[error(transparent)]
+        Store(#[from] StoreError),
+    }
+
+    impl AccessError {
+        pub fn status_code(&self) -> http::StatusCode {
+            match self {
+                AccessError::NotFound => http::StatusCode::NOT_FOUND,
+                AccessError::Forbidden => http::StatusCode::FORBIDDEN,
+                AccessError::Store(_) => http::StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        }
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum StartError {
+        #[error(transparent)]
+        Busy(#[from] QueueFull),
+        #[error("could not create job directory: {0}")]
+        Io(#[from] std::io::Error),
+        #[error(transparent)]
+        Store(#[from] StoreError),
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum PreviewError {
+        #[error("Previews are only available for single videos")]
+        NotAVideo,
+        #[error("{}", lookup_message(*.0))]
+        Lookup(ConversionError),
+    }
+
+    fn lookup_message(code: ConversionError) -> &'static str {
+        match code {
+            // Its message is about retried downloads
+            ConversionError::Unknown => "This video could not be looked up.",
+            code => code.message(),
+        }
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum CancelError {
+        #[error("Job not found")]
+        NotFound,
+        #[error("This conversion has already {0}")]
+        Finished(JobStatus),
+        #[error(transparent)]
+        Store(StoreError),
+    }
+
+    /// What to do at startup with jobs that were running when the server stopped.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum RecoveryPolicy {
+        /// Mark interrupted jobs as failed and let the user retry.
+        #[default]
+        Fail,
+        /// Discard partial files and run interrupted jobs again.
+        Requeue,
+    }
+
+    impl std::str::FromStr for RecoveryPolicy {
+        type Err = String;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s {
+                "fail" => Ok(RecoveryPolicy::Fail),
+                "requeue" => Ok(RecoveryPolicy::Requeue),
+                other => Err(format!("unknown recovery policy `{other}`, expected `fail` or `requeue`")),
+            }
+        }
+    }
+
+    /// Counts of what [`JobRunner::recover`] did with each stored job.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct RecoveryReport {
+        pub reattached: usize,
+        pub missing_files: usize,
+        pub failed: usize,
+        pub requeued: usize,
+        pub orphaned_dirs_removed: usize,
+    }
+
+    /// How long the runner pauses between download attempts.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct RetryDelays {
+        /// Pause after every failed attempt.
+        pub between_attempts: Duration,
+        /// Extra pause after an attempt that looked rate limited or bot-blocked.
+        pub after_throttling: Duration,
+    }
+
+    impl RetryDelays {
+        pub const NONE: RetryDelays = RetryDelays {
+            between_attempts: Duration::ZERO,
+            after_throttling: Duration::ZERO,
+        };
+    }
+
+    impl Default for RetryDelays {
+        fn default() -> Self {
+            Self {
+                between_attempts: Duration::from_secs(2),
+                after_throttling: Duration::from_secs(10),
+            }
+        }
+    }
+
+    /// Owns the job store and drives each job through a [`Downloader`],
+    /// retrying with every configured [`Strategy`] until one succeeds.
+    ///
+    /// Jobs wait in a bounded [`JobQueue`] and a fixed number of workers,
+    /// started with the first job, take them off the front one at a time.
+    #[derive(Debug, Clone)]
+    pub struct JobRunner {
+        store: Arc<dyn JobStore>,
+        downloader: Arc<dyn Downloader>,
+        tagger: Arc<dyn Tagger>,
+        /// Finished conversions reused for identical requests, if enabled.
+        cache: Option<Arc<ConversionCache>>,
+        /// Recent previews, so confirming a conversion does not look the
+        /// video up twice.
+        previews: Arc<PreviewCache>,
+        strategies: Strategies,
+        /// How each strategy has been doing, to try the working ones first.
+        strategy_stats: Arc<StrategyStats>,
+        /// What each role's users may convert and keep.
+        quotas: Arc<QuotaConfig>,
+        work_dir: PathBuf,
+        retry_delays: RetryDelays,
+        queue: Arc<JobQueue>,
+        workers: usize,
+        max_playlist_len: usize,
+        workers_started: Arc<std::sync::Once>,
+        /// IDs of jobs the runner just changed, for status streams.
+        updates: broadcast::Sender<String>,
+        /// Cancellation signal of every job a worker has picked up.
+        running: Arc<Mutex<HashMap<String, Arc<Notify>>>>,
+    }
+
+    impl JobRunner {
+        pub fn new(downloader: Arc<dyn Downloader>) -> Self {
+            Self {
+                store: Arc::new(MemoryJobStore::default()),
+                downloader,
+                tagger: Arc::new(FfmpegTagger::default()),
+                cache: None,
+                previews: Arc::new(PreviewCache::default()),
+                strategies: Strategies::default(),
+                strategy_stats: Arc::new(StrategyStats::default()),
+                quotas: Arc::new(QuotaConfig::default()),
+                work_dir: PathBuf::from(DEFAULT_WORK_DIR),
+                retry_delays: RetryDelays::default(),
+                queue: Arc::new(JobQueue::new(DEFAULT_MAX_QUEUE_LEN)),
+                workers: DEFAULT_WORKERS,
+                max_playlist_len: DEFAULT_MAX_PLAYLIST_LEN,
+                workers_started: Arc::new(std::sync::Once::new()),
+                updates: broadcast::channel(UPDATE_CHANNEL_CAPACITY).0,
+                running: Arc::new(Mutex::new(HashMap::new())),
+            }
+        }
+
+        pub fn with_workers(mut self, workers: usize) -> Self {
+            self.workers = workers.max(1);
+            self
+        }
+
+        pub fn with_max_queue_len(mut self, max_queue_len: usize) -> Self {
+            self.queue = Arc::new(JobQueue::new(max_queue_len));
+            self
+        }
+
+        pub fn with_max_playlist_len(mut self, max_playlist_len: usize) -> Self {
+            self.max_playlist_len = max_playlist_len.max(1);
+            self
+        }
+
+        pub fn with_store(mut self, store: Arc<dyn JobStore>) -> Self {
+            self.store = store;
+            self
+        }
+
+        pub fn store(&self) -> &Arc<dyn JobStore> {
+            &self.store
+        }
+
+        pub fn with_tagger(mut self, tagger: Arc<dyn Tagger>) -> Self {
+            self.tagger = tagger;
+            self
+        }
+
+        pub fn with_cache(mut self, cache: Arc<ConversionCache>) -> Self {
+            self.cache = Some(cache);
+            self
+        }
+
+        pub fn with_preview_cache(mut self, previews: Arc<PreviewCache>) -> Self {
+            self.previews = previews;
+            self
+        }
+
+        pub fn cache(&self) -> Option<&Arc<ConversionCache>> {
+            self.cache.as_ref()
+        }
+
+        pub fn with_work_dir(mut self, work_dir: impl Into<PathBuf>) -> Self {
+            self.work_dir = work_dir.into();
+            self
+        }
+
+        pub fn with_strategies(mut self, strategies: Vec<Strategy>) -> Self {
+            self.strategies = Strategies::new(strategies);
+            self
+        }
+
+        /// IDs of the jobs waiting for or being worked on by this runner,
+        /// including the tracks of a running playlist.
+        pub fn active_job_ids(&self) -> HashSet<String> {
+            let mut ids: HashSet<String> = self.queue.waiting().into_iter().collect();
+            ids.extend(self.lock_running().keys().cloned());
+            ids
+        }
+
+        /// The strategies new attempts use; replacing them affects jobs
+        /// started afterwards.
+        pub fn strategies(&self) -> &Strategies {
+            &self.strategies
+        }
+
+        pub fn with_strategy_stats(mut self, strategy_stats: StrategyStats) -> Self {
+            self.strategy_stats = Arc::new(strategy_stats);
+            self
+        }
+
+        /// How each configured strategy has been doing, in the order the
+        /// next job would attempt them.
+        pub fn strategy_report(&self) -> Vec<StrategyReport> {
+            self.strategy_stats.report(&self.strategies.current())
+        }
+
+        pub fn with_quotas(mut self, quotas: QuotaConfig) -> Self {
+            self.quotas = Arc::new(quotas);
+            self
+        }
+
+        /// How much of their role's quotas a user has used, from the jobs
+        /// they started.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if the jobs cannot be read from the store.
+        pub async fn usage(&self, user_id: &str, role: &str) -> Result<Usage, StoreError> {
+            let jobs = self.store.list_by_owner(user_id).await?;
+            Ok(quota::usage(&jobs, user_id, self.quotas.for_role(role), now_ms()))
+        }
+
+        /// Looks up what a single video is without downloading it, reusing
+        /// a recent lookup of the same video.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if `video` is a playlist, or the extractor fails
+        /// or takes longer than [`PREVIEW_TIMEOUT`].
+        pub async fn preview(&self, video: &VideoRef) -> Result<VideoPreview, PreviewError> {
+            let (Some(video_id), Some(url)) = (video.video_id.as_deref(), video.video_url()) else {
+                return Err(PreviewError::NotAVideo);
+            };
+            if let Some(preview) = self.previews.get(video_id) {
+                return Ok(preview);
+            }
+            let preview = match tokio::time::timeout(PREVIEW_TIMEOUT, self.downloader.preview(&url)).await {
+                Ok(Ok(preview)) => preview,
+                Ok(Err(e)) => {
+                    log!("Preview of {} failed: {}", url, e);
+                    return Err(PreviewError::Lookup(e.code()));
+                }
+                Err(_) => return Err(PreviewError::Lookup(ConversionError::Timeout)),
+            };
+            self.previews.insert(preview.clone());
+            Ok(preview)
+        }
+
+        pub fn with_retry_delays(mut self, retry_delays: RetryDelays) -> Self {
+            self.retry_delays = retry_delays;
+            self
+        }
+
+        /// Starts a new conversion job for a YouTube URL.
+        ///
+        /// If the same video was recently converted with the same settings,
+        /// the job completes immediately with a copy of the cached file.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if:
+        /// - The queue is full
+        /// - Unable to create temporary directory
+        /// - Failed to store job in the job store
+        pub async fn start_conversion(
+            &self,
+            url: String,
+            options: ConversionOptions,
+        ) -> Result<String, StartError> {
+            let job_id = Uuid::new_v4().to_string();
+            let mut job = ConversionJob::new(job_id.clone(), url, PathBuf::new());
+            job.format = options.format;
+            job.quality = options.quality;
+            job.clip = options.clip;
+            job.tag_overrides = options.tags;
+            job.owner_id = options.owner_id;
+            job.owner_role = options.owner_role;
+            if options.whole_playlist {
+                job.playlist = Some(PlaylistJob::default());
+            } else {
+                // Known when the video was previewed before converting
+                let video_secs = VideoRef::parse(&job.url)
+                    .ok()
+                    .and_then(|video| self.previews.get(video.video_id.as_deref()?))
+                    .and_then(|preview| preview.duration_secs);
+                job.expected_secs = job.clip.and_then(|clip| clip.duration_secs()).or(video_secs);
+            }
+
+            // A cached conversion needs no worker, so it is served even when the queue is full
+            let cached = self.cached_file(&job);
+            if cached.is_none() && self.queue.len() >= self.queue.max_len() {
+                return Err(QueueFull.into());
+            }
+            job.work_dir = self.create_job_dir()?;
+
+            if let Some(cached) = cached {
+                if let Some(audio_path) = self.restore_cached(&job, &cached).await {
+                    job.transition(JobStatus::Downloading).map_err(StoreError::from)?;
+                    job.transition(JobStatus::Completed).map_err(StoreError::from)?;
+                    job.audio_bytes = file_size(&audio_path).await;
+                    job.audio_path = Some(audio_path);
+                    job.tags = cached.tags;
+                    job.title = cached.title;
+                    job.audio_secs = cached.audio_secs;
+                    self.store.insert(job).await?;
+                    log!("Job {} served from the conversion cache", job_id);
+                    return Ok(job_id);
+                }
+            }
+
+            // Store the job
+            self.store.insert(job.clone()).await?;
+
+            // Queue the job for the next free worker
+            if let Err(e) = self.queue.push(job_id.clone()) {
+                // Lost the race for the last slot; undo the job
+                self.store.remove(&job_id).await?;
+                let _ = tokio::fs::remove_dir_all(&job.work_dir).await;
+                return Err(e.into());
+            }
+            self.ensure_workers();
+
+            Ok(job_id)
+        }
+
+        /// Checks that `user_id` may see the job.
+        ///
+        /// Jobs from before owners were recorded are open to everyone.
+        ///
+        /// # Errors
+        ///
+        /// Returns [`AccessError::NotFound`] for unknown jobs and
+        /// [`AccessError::Forbidden`] for jobs of other users or, when
+        /// `user_id` is `None`, of any user.
+        pub async fn check_access(
+            &self,
+            job_id: &str,
+            user_id: Option<&str>,
+        ) -> Result<(), AccessError> {
+            let job = self.store.get(job_id).await?.ok_or(AccessError::NotFound)?;
+            match job.owner_id.as_deref() {
+                None => Ok(()),
+                Some(owner) if Some(owner) == user_id => Ok(()),
+                Some(_) => Err(AccessError::Forbidden),
+            }
+        }
+
+        /// The key `job`'s output is cached under, if it may be cached.
+        ///
+        /// Playlists are made of cached tracks rather than cached themselves,
+        /// and files with the user's own tags are not shared with others.
+        fn cache_key(&self, job: &ConversionJob) -> Option<CacheKey> {
+            if job.playlist.is_some() || !job.tag_overrides.is_empty() {
+                return None;
+            }
+            Some(CacheKey {
+                video_id: VideoRef::parse(&job.url).ok()?.video_id?,
+                format: job.format,
+                quality: job.quality,
+                clip: job.clip,
+            })
+        }
+
+        fn cached_file(&self, job: &ConversionJob) -> Option<CachedFile> {
+            self.cache.as_ref()?.get(&self.cache_key(job)?)
+        }
+
+        /// Copies a cached file into the job's directory.
+        async fn restore_cached(&self, job: &ConversionJob, cached: &CachedFile) -> Option<PathBuf> {
+            let cache = self.cache.as_ref()?;
+            match cache.restore(cached, &job.work_dir).await {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    // Evicted since the lookup; convert it again
+                    log!("Job {} could not use the cached file: {}", job.id, e);
+                    None
+                }
+            }
+        }
+
+        /// Keeps a finished conversion for identical requests.
+        async fn cache_conversion(
+            &self,
+            job: &ConversionJob,
+            audio_path: &Path,
+            title: Option<String>,
+            tags: Option<TrackTags>,
+            audio_secs: Option<u64>,
+        ) {
+            let (Some(cache), Some(key)) = (&self.cache, self.cache_key(job)) else {
+                return;
+            };
+            if let Err(e) = cache.insert(key, audio_path, title, tags, audio_secs).await {
+                log!("Job {} could not be cached: {}", job.id, e);
+            }
+        }
+
+        /// Creates a job's own directory. It outlives the process so
+        /// completed files can be re-attached after a restart.
+        fn create_job_dir(&self) -> std::io::Result<PathBuf> {
+            Ok(tempfile::Builder::new()
+                .prefix("ytmp3_")
+                .tempdir_in(&self.work_dir)?
+                .keep())
+        }
+
+        /// Starts the worker tasks the first time a job is queued.
+        fn ensure_workers(&self) {
+            self.workers_started.call_once(|| {
+                log!("Starting {} conversion workers", self.workers);
+                for _ in 0..self.workers {
+                    let runner = self.clone();
+                    tokio::spawn(async move {
+                        loop {
+                            let job_id = runner.queue.pop().await;
+                            // Run each job in its own task so a panic only loses that job
+                            let job = tokio::spawn(runner.clone().process_conversion(job_id.clone()));
+                            if let Err(e) = job.await {
+                                log!("Job {} worker task failed: {}", job_id, e);
+                            }
+                        }
+                    });
+                }
+            });
+        }
+
+        /// Cancels a queued or running job and deletes its files.
+        ///
+        /// A waiting job is taken off the queue. A running job's download is
+        /// dropped, which kills the extractor, and its worker deletes the
+        /// partial files before moving on to the next job. Cancelling a
+        /// playlist job cancels its unfinished tracks as well.
+        ///
+        /// # Errors
+        ///
+        /// Returns [`CancelError::NotFound`] for unknown jobs,
+        /// [`CancelError::Finished`] if the job already stopped, or an error if
+        /// the job store fails.
+        pub async fn cancel(&self, job_id: &str) -> Result<ConversionJob, CancelError> {
+            let job = self
+                .update_job(job_id, Box::new(|job| {
+                    job.transition(JobStatus::Cancelled)?;
+                    job.progress = None;
+                    Ok(())
+                }))
+                .await
+                .map_err(|e| match e {
+                    StoreError::NotFound => CancelError::NotFound,
+                    StoreError::Transition(e) => CancelError::Finished(e.from),
+                    e => CancelError::Store(e),
+                })?;
+
+            self.queue.remove(job_id);
+            self.cancel_tracks(&job).await;
+            let running = self.lock_running().get(job_id).cloned();
+            match running {
+                // The worker cleans up once the download has stopped writing
+                Some(cancel) => cancel.notify_one(),
+                None => remove_job_dir(&job.work_dir).await,
+            }
+
+            log!("Job {} cancelled", job_id);
+            Ok(job)
+        }
+
+        /// Cancels every track of a playlist job that has not finished yet.
+        async fn cancel_tracks(&self, job: &ConversionJob) {
+            for track_id in job.track_ids() {
+                match Box::pin(self.cancel(track_id)).await {
+                    Ok(_) | Err(CancelError::Finished(_) | CancelError::NotFound) => {}
+                    Err(e) => log!("Track {} of job {} could not be cancelled: {}", track_id, job.id, e),
+                }
+            }
+        }
+
+        /// Reconciles the store with the disk after a restart.
+        ///
+        /// Completed jobs whose files still exist are kept, interrupted jobs
+        /// are failed or re-queued according to `policy`, and `ytmp3_*`
+        /// directories that no job refers to are deleted.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if the job store cannot be read or written.
+        pub async fn recover(
+            &self,
+            policy: RecoveryPolicy,
+        ) -> Result<RecoveryReport, Box<dyn std::error::Error + Send + Sync>> {
+            let mut report = RecoveryReport::default();
+            let mut known_dirs = HashSet::new();
+
+            for job in self.store.list().await? {
+                known_dirs.insert(job.work_dir.clone());
+
+                match job.status() {
+                    // A playlist job's files belong to its tracks
+                    JobStatus::Completed if job.playlist.is_some() => report.reattached += 1,
+                    JobStatus::Completed => {
+                        let file_exists = match job.audio_path {
+                            Some(ref path) => tokio::fs::try_exists(path).await.unwrap_or(false),
+                            None => false,
+                        };
+                        if file_exists {
+                            report.reattached += 1;
+                        } else {
+                            log!("Job {} lost its output file, marking it expired", job.id);
+                            self.store
+                                .update(&job.id, Box::new(|job| {
+                                    job.transition(JobStatus::Expired)?;
+                                    job.audio_path = None;
+                                    Ok(())
+                                }))
+                                .await?;
+                            report.missing_files += 1;
+                        }
+                    }
+                    JobStatus::Queued | JobStatus::Downloading | JobStatus::Transcoding => {
+                        match policy {
+                            RecoveryPolicy::Fail => {
+                                self.store
+                                    .update(&job.id, Box::new(|job| {
+                                        job.transition(JobStatus::Failed)?;
+                                        job.error = Some(
+                                            "The server restarted before this conversion finished. Please try again."
+                                                .to_string(),
+                                        );
+                                        job.error_code = Some(ConversionError::Unknown);
+                                        Ok(())
+                                    }))
+                                    .await?;
+                                report.failed += 1;
+                            }
+                            RecoveryPolicy::Requeue => {
+                                reset_dir(&job.work_dir).await?;
+                                self.store
+                                    .update(&job.id, Box::new(|job| {
+                                        if job.status() != JobStatus::Queued {
+                                            job.transition(JobStatus::Queued)?;
+                                        }
+                                        job.audio_path = None;
+                                        Ok(())
+                                    }))
+                                    .await?;
+                                // Tracks run again as part of their playlist job
+                                if job.parent_id.is_none() {
+                                    self.queue.push_unbounded(job.id.clone());
+                                }
+                                report.requeued += 1;
+                            }
+                        }
+                    }
+                    JobStatus::Failed | JobStatus::Cancelled | JobStatus::Expired => {}
+                }
+            }
+
+            report.orphaned_dirs_removed =
+                remove_orphaned_dirs(&self.work_dir, &known_dirs).await;
+
+            if report.requeued > 0 {
+                self.ensure_workers();
+            }
+
+            log!("Job recovery finished: {:?}", report);
+            Ok(report)
+        }
+
+        /// Gets the current status of a conversion job.
+        ///
+        /// # Errors
+        ///
+        /// This function doesn't typically return errors, but wraps responses in Result
+        /// for consistency with the API interface.
+        pub async fn get_job_status(
+            &self,
+            job_id: &str,
+        ) -> Result<ConvertResponse, Box<dyn std::error::Error + Send + Sync>> {
+            match self.store.get(job_id).await? {
+                Some(job) => {
+                    let status = job.status();
+                    let queue_position = if status == JobStatus::Queued {
+                        self.queue.position(&job.id)
+                    } else {
+                        None
+                    };
+                    let playlist = match &job.playlist {
+                        Some(playlist) => Some(self.playlist_status(playlist).await?),
+                        None => None,
+                    };
+                    let message = match (status, job.error) {
+                        // Expiry wins over any earlier error
+                        (JobStatus::Expired, _) => {
+                            "This download has expired. Please convert the video again.".to_string()
+                        }
+                        (_, Some(error)) => error,
+                        (JobStatus::Queued, None) => match queue_position {
+                            Some(position) => {
+                                format!("Waiting for a free converter (position {position} in queue)...")
+                            }
+                            None => "Starting conversion...".to_string(),
+                        },
+                        (JobStatus::Downloading, None) => match (&playlist, job.clip) {
+                            (Some(playlist), _) if playlist.tracks.is_empty() => {
+                                "Reading the playlist...".to_string()
+                            }
+                            (Some(playlist), _) => {
+                                let total = playlist.tracks.len();
+                                let current = (playlist.finished() + 1).min(total);
+                                format!("Converting track {current} of {total}...")
+                            }
+                            (None, Some(clip)) => format!("Downloading {} of the video...", clip.label()),
+                            (None, None) => "Downloading video...".to_string(),
+                        },
+                        (JobStatus::Transcoding, None) => {
+                            format!("Converting to {}...", job.format.label())
+                        }
+                        (JobStatus::Completed, None) => match &playlist {
+                            Some(playlist) => format!(
+                                "Converted {} of {} tracks",
+                                playlist.completed(),
+                                playlist.tracks.len()
+                            ),
+                            None => "Conversion completed successfully".to_string(),
+                        },
+                        (JobStatus::Failed, None) => "Conversion failed".to_string(),
+                        (JobStatus::Cancelled, None) => "Conversion was cancelled".to_string(),
+                    };
+
+                    Ok(ConvertResponse {
+                        id: job.id,
+                        status,
+                        message,
+                        error_code: job.error_code,
+                        rate_limit: None,
+                        format: job.format,
+                        quality: job.quality,
+                        clip: job.clip,
+                        tags: job.tags,
+                        queue_position,
+                        progress: job.progress,
+                        playlist,
+                    })
+                }
+                None => Ok(ConvertResponse::failed(job_id, "Job not found")),
+            }
+        }
+
+        /// Looks up the status of every track of a playlist job.
+        async fn playlist_status(
+            &self,
+            playlist: &PlaylistJob,
+        ) -> Result<PlaylistStatus, Box<dyn std::error::Error + Send + Sync>> {
+            let mut tracks = Vec::with_capacity(playlist.tracks.len());
+            for track in &playlist.tracks {
+                let response = Box::pin(self.get_job_status(&track.job_id)).await?;
+                let title = response
+                    .tags
+                    .as_ref()
+                    .and_then(TrackTags::display_name)
+                    .or_else(|| track.title.clone());
+                tracks.push(PlaylistTrack {
+                    job_id: track.job_id.clone(),
+                    title,
+                    status: response.status,
+                    message: response.message,
+                });
+            }
+            Ok(PlaylistStatus {
+                title: playlist.title.clone(),
+                tracks,
+            })
+        }
+
+        /// Streams the status of a job, starting with its current status and
+        /// then every time it changes, until the job has finished.
+        ///
+        /// Unknown jobs yield a single "Job not found" response.
+        pub fn status_stream(&self, job_id: String) -> impl Stream<Item = ConvertResponse> + Send + 'static {
+            struct Watch {
+                runner: JobRunner,
+                job_id: String,
+                updates: broadcast::Receiver<String>,
+                last: Option<ConvertResponse>,
+                done: bool,
+            }
+
+            // Subscribe before the first read so no change can slip in between
+            let watch = Watch {
+                updates: self.updates.subscribe(),
+                runner: self.clone(),
+                job_id,
+                last: None,
+                done: false,
+            };
+
+            futures::stream::unfold(watch, |mut watch| async move {
+                if watch.done {
+                    return None;
+                }
+                loop {
+                    if watch.last.is_some() {
+                        match tokio::time::timeout(STATUS_RECHECK_INTERVAL, watch.updates.recv()).await {
+                            Ok(Ok(id)) if id != watch.job_id => continue,
+                            Ok(Err(broadcast::error::RecvError::Closed)) => return None,
+                            // Our job changed, we missed some updates, or it is time to re-check
+                            _ => {}
+                        }
+                    }
+
+                    let response = match watch.runner.get_job_status(&watch.job_id).await {
+                        Ok(response) => response,
+                        Err(e) => {
+                            log!("Job {} status stream stopped: {}", watch.job_id, e);
+                            return None;
+                        }
+                    };
+                    if watch.last.as_ref() == Some(&response) {
+                        continue;
+                    }
+                    watch.done = response.status.is_finished();
+                    watch.last = Some(response.clone());
+                    return Some((response, watch));
+                }
+            })
+        }
+
+        /// Locates the audio file of a completed conversion job.
+        ///
+        /// Only the job is read from the store; the file is left for the
+        /// caller to open, so no store lock is held while it is read.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if:
+        /// - Job not found
+        /// - Conversion not completed yet or expired
+        /// - The job has no audio file
+        /// - The store cannot be read
+        pub async fn get_audio_file(
+            &self,
+            job_id: &str,
+        ) -> Result<AudioFile, Box<dyn std::error::Error + Send + Sync>> {
+            match self.store.get(job_id).await? {
+                Some(job) if job.status() == JobStatus::Completed => {
+                    if let Some(audio_path) = job.audio_path {
+                        Ok(AudioFile {
+                            job_id: job.id,
+                            format: job.format,
+                            path: audio_path,
+                            title: job.title,
+                            tags: job.tags,
+                        })
+                    } else {
+                        Err("Audio file not found".into())
+                    }
+                }
+                Some(job) if job.status() == JobStatus::Expired => {
+                    Err("This download has expired".into())
+                }
+                Some(_) => Err("Conversion not completed yet".into()),
+                None => Err("Job not found".into()),
+            }
+        }
+
+        /// Collects the converted tracks of a completed playlist job.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error if:
+        /// - Job not found or not a playlist
+        /// - Conversion not completed yet
+        /// - None of the tracks' files are left
+        pub async fn get_playlist_archive(
+            &self,
+            job_id: &str,
+        ) -> Result<PlaylistArchive, Box<dyn std::error::Error + Send + Sync>> {
+            let job = match self.store.get(job_id).await? {
+                Some(job) if job.playlist.is_none() => return Err("This job is not a playlist".into()),
+                Some(job) if job.status() == JobStatus::Completed => job,
+                Some(job) if job.status() == JobStatus::Expired => {
+                    return Err("This download has expired".into())
+                }
+                Some(_) => return Err("Conversion not completed yet".into()),
+                None => return Err("Job not found".into()),
+            };
+            let playlist = job.playlist.unwrap_or_default();
+
+            let mut archive = PlaylistArchive {
+                title: playlist.title,
+                tracks: Vec::new(),
+            };
+            for (index, track) in playlist.tracks.into_iter().enumerate() {
+                let Some(child) = self.store.get(&track.job_id).await? else {
+                    continue;
+                };
+                let (JobStatus::Completed, Some(path)) = (child.status(), child.audio_path) else {
+                    continue;
+                };
+                let title = child
+                    .tags
+                    .as_ref()
+                    .and_then(TrackTags::display_name)
+                    .or(track.title)
+                    .unwrap_or_else(|| format!("Track {}", index + 1));
+                archive.push(index + 1, &title, child.format.extension(), path);
+            }
+
+            if archive.tracks.is_empty() {
+                return Err("This download has expired".into());
+            }
+            Ok(archive)
+        }
+
+        async fn process_conversion(self, job_id: String) {
+            let cancel = Arc::new(Notify::new());
+            self.lock_running().insert(job_id.clone(), cancel.clone());
+            let is_playlist = matches!(
+                self.store.get(&job_id).await,
+                Ok(Some(job)) if job.playlist.is_some()
+            );
+            if is_playlist {
+                self.run_playlist(&job_id, &cancel).await;
+            } else {
+                self.run_conversion(&job_id, &cancel).await;
+            }
+            self.lock_running().remove(&job_id);
+        }
+
+        /// Moves a job taken off the queue into the download phase.
+        ///
+        /// Returns `None` if it cannot start, e.g. because it was cancelled
+        /// meanwhile, in which case its directory is removed.
+        async fn start_job(&self, job_id: &str) -> Option<ConversionJob> {
+            match self
+                .update_job(job_id, Box::new(|job| job.transition(JobStatus::Downloading)))
+                .await
+            {
+                Ok(job) => Some(job),
+                Err(e) => {
+                    log!("Job {} cannot start: {}", job_id, e);
+                    // Cancelled between leaving the queue and starting
+                    if let Ok(Some(job)) = self.store.get(job_id).await {
+                        if job.status() == JobStatus::Cancelled {
+                            remove_job_dir(&job.work_dir).await;
+                        }
+                    }
+                    None
+                }
+            }
+        }
+
+        /// Marks a job as completed with its output file.
+        async fn complete_job(
+            &self,
+            job_id: &str,
+            work_dir: &Path,
+            audio_path: PathBuf,
+            title: Option<String>,
+            tags: Option<TrackTags>,
+            audio_secs: Option<u64>,
+        ) {
+            let audio_bytes = file_size(&audio_path).await;
+            let result = self
+                .update_job(job_id, Box::new(move |job| {
+                    job.transition(JobStatus::Completed)?;
+                    job.audio_path = Some(audio_path);
+                    job.title = title;
+                    job.tags = tags;
+                    job.audio_secs = audio_secs;
+                    job.audio_bytes = audio_bytes;
+                    job.progress = None;
+                    Ok(())
+                }))
+                .await;
+            match result {
+                Ok(_) => log!("Job {} completed successfully", job_id),
+                // Cancelled while the last attempt was finishing
+                Err(StoreError::Transition(_)) => remove_job_dir(work_dir).await,
+                Err(e) => log!("Job {} finished but could not be completed: {}", job_id, e),
+            }
+        }
+
+        /// Marks a job as failed with a message for the user.
+        async fn fail_job(&self, job_id: &str, work_dir: &Path, code: ConversionError, error: String) {
+            log!("Job {} failed with error {}: {}", job_id, code, error);
+            let result = self
+                .update_job(job_id, Box::new(move |job| {
+                    job.transition(JobStatus::Failed)?;
+                    job.error = Some(error);
+                    job.error_code = Some(code);
+                    job.progress = None;
+                    Ok(())
+                }))
+                .await;
+            match result {
+                Ok(_) => {}
+                // Cancelled while it was failing
+                Err(StoreError::Transition(_)) => remove_job_dir(work_dir).await,
+                Err(e) => log!("Job {} failed but could not be marked as failed: {}", job_id, e),
+            }
+        }
+
+        /// Lists a playlist job's videos, then converts them one after
+        /// another as child jobs.
+        ///
+        /// A track that fails does not stop the others; the playlist job
+        /// completes if at least one track produced a file.
+        async fn run_playlist(&self, job_id: &str, cancel: &Notify) {
+            let Some(job) = self.start_job(job_id).await else {
+                return;
+            };
+
+            // Tracks are only listed once, so a re-queued job picks up where it stopped
+            let mut tracks = job.playlist.clone().unwrap_or_default().tracks;
+            if tracks.is_empty() {
+                log!("Listing playlist for job {}: {}", job_id, job.url);
+                let listing = tokio::select! {
+                    biased;
+                    () = cancel.notified() => {
+                        remove_job_dir(&job.work_dir).await;
+                        return;
+                    }
+                    listing = self.downloader.list_playlist(&job.url, self.max_playlist_len) => listing,
+                };
+                let listing = match listing {
+                    Ok(listing) if !listing.entries.is_empty() => listing,
+                    Ok(_) => {
+                        let error = "This playlist has no videos that can be converted.".to_string();
+                        self.fail_job(job_id, &job.work_dir, ConversionError::Unavailable, error).await;
+                        return;
+                    }
+                    Err(e) => {
+                        log!("Job {} playlist could not be listed: {}", job_id, e);
+                        let error = "This playlist could not be read. It may be private or deleted, or YouTube is blocking requests right now.".to_string();
+                        self.fail_job(job_id, &job.work_dir, e.code(), error).await;
+                        return;
+                    }
+                };
+                let listing = match self.fit_to_quota(&job, listing).await {
+                    Ok(listing) => listing,
+                    Err(error) => {
+                        self.fail_job(job_id, &job.work_dir, ConversionError::Unknown, error).await;
+                        return;
+                    }
+                };
+                tracks = match self.add_tracks(&job, listing).await {
+                    Ok(tracks) => tracks,
+                    Err(e) => {
+                        let error = format!("Failed to start the playlist's conversions: {e}");
+                        self.fail_job(job_id, &job.work_dir, ConversionError::Unknown, error).await;
+                        return;
+                    }
+                };
+            }
+
+            let total = tracks.len();
+            for (index, track) in tracks.iter().enumerate() {
+                if !self.is_active(job_id).await {
+                    break;
+                }
+                // Tracks finish early when cancelled on their own or after a restart
+                if self.is_active(&track.job_id).await {
+                    let track_cancel = Arc::new(Notify::new());
+                    self.lock_running().insert(track.job_id.clone(), track_cancel.clone());
+                    self.run_conversion(&track.job_id, &track_cancel).await;
+                    self.lock_running().remove(&track.job_id);
+                }
+
+                let percent = (index + 1) as f32 / total as f32 * 100.0;
+                let result = self
+                    .update_job(job_id, Box::new(move |job| {
+                        if job.status().is_active() {
+                            job.progress = Some(JobProgress {
+                                percent: Some(percent),
+                                ..JobProgress::default()
+                            });
+                        }
+                        Ok(())
+                    }))
+                    .await;
+                if let Err(e) = result {
+                    log!("Job {} progress could not be recorded: {}", job_id, e);
+                }
+            }
+
+            let job = match self.store.get(job_id).await {
+                Ok(Some(job)) => job,
+                Ok(None) => return,
+                Err(e) => {
+                    log!("Job {} could not be read after its tracks: {}", job_id, e);
+                    return;
+                }
+            };
+            if !job.status().is_active() {
+                // Cancelled while listing or between tracks
+                self.cancel_tracks(&job).await;
+                remove_job_dir(&job.work_dir).await;
+                return;
+            }
+
+            let mut completed = 0;
+            for track in &tracks {
+                if let Ok(Some(child)) = self.store.get(&track.job_id).await {
+                    if child.status() == JobStatus::Completed {
+                        completed += 1;
+                    }
+                }
+            }
+            if completed == 0 {
+                let error = "None of the playlist's videos could be converted.".to_string();
+                self.fail_job(job_id, &job.work_dir, ConversionError::Unknown, error).await;
+                return;
+            }
+
+            let result = self
+                .update_job(job_id, Box::new(|job| {
+                    job.transition(JobStatus::Completed)?;
+                    job.progress = None;
+                    Ok(())
+                }))
+                .await;
+            match result {
+                Ok(_) => log!("Job {} converted {} of {} tracks", job_id, completed, total),
+                Err(StoreError::Transition(_)) => remove_job_dir(&job.work_dir).await,
+                Err(e) => log!("Job {} finished but could not be completed: {}", job_id, e),
+            }
+        }
+
+        /// Drops the videos of `listing` that would take its owner past
+        /// their quotas.
+        ///
+        /// Returns why the playlist cannot be converted if none fit.
+        async fn fit_to_quota(
+            &self,
+            job: &ConversionJob,
+            mut listing: PlaylistListing,
+        ) -> Result<PlaylistListing, String> {
+            let Some(owner_id) = job.owner_id.as_deref() else {
+                return Ok(listing);
+            };
+            let usage = match self.usage(owner_id, job.owner_role.as_deref().unwrap_or_default()).await {
+                Ok(usage) => usage,
+                Err(e) => {
+                    log!("Quota of user {} could not be checked: {}", owner_id, e);
+                    return Ok(listing);
+                }
+            };
+            let durations: Vec<Option<u64>> = listing.entries.iter().map(|entry| entry.duration_secs).collect();
+            let affordable = quota::affordable(&usage, &durations);
+            if affordable == 0 {
+                return Err(usage.exhausted().unwrap_or_else(|| {
+                    "Your remaining quota for today does not cover any video of this playlist.".to_string()
+                }));
+            }
+            if affordable < listing.entries.len() {
+                log!(
+                    "Job {} converts {} of {} playlist videos within its owner's quota",
+                    job.id,
+                    affordable,
+                    listing.entries.len()
+                );
+                listing.entries.truncate(affordable);
+            }
+            Ok(listing)
+        }
+
+        /// Creates a queued child job for every listed video and records
+        /// them on the playlist job.
+        async fn add_tracks(
+            &self,
+            job: &ConversionJob,
+            listing: PlaylistListing,
+        ) -> Result<Vec<PlaylistJobTrack>, StartError> {
+            let mut tracks = Vec::with_capacity(listing.entries.len());
+            for entry in listing.entries {
+                let mut child = ConversionJob::new(
+                    Uuid::new_v4().to_string(),
+                    entry.url(),
+                    self.create_job_dir()?,
+                );
+                child.format = job.format;
+                child.quality = job.quality;
+                child.tag_overrides = job.tag_overrides.clone();
+                child.parent_id = Some(job.id.clone());
+                child.owner_id = job.owner_id.clone();
+                child.owner_role = job.owner_role.clone();
+                child.expected_secs = entry.duration_secs;
+                tracks.push(PlaylistJobTrack {
+                    job_id: child.id.clone(),
+                    title: entry.title,
+                });
+                self.store.insert(child).await?;
+            }
+
+            let playlist = PlaylistJob {
+                title: listing.title,
+                tracks: tracks.clone(),
+            };
+            self.update_job(&job.id, Box::new(move |job| {
+                job.playlist = Some(playlist);
+                Ok(())
+            }))
+            .await?;
+            Ok(tracks)
+        }
+
+        async fn is_active(&self, job_id: &str) -> bool {
+            matches!(self.store.get(job_id).await, Ok(Some(job)) if job.status().is_active())
+        }
+
+        /// Tries every strategy until one produces a file or `cancel` fires.
+        async fn run_conversion(&self, job_id: &str, cancel: &Notify) {
+            let Some(job) = self.start_job(job_id).await else {
+                return;
+            };
+            // An identical job may have finished while this one was waiting
+            if let Some(cached) = self.cached_file(&job) {
+                if let Some(audio_path) = self.restore_cached(&job, &cached).await {
+                    log!("Job {} served from the conversion cache", job_id);
+                    self.complete_job(job_id, &job.work_dir, audio_path, cached.title, cached.tags, cached.audio_secs)
+                        .await;
+                    return;
+                }
+            }
+            let job_id = job_id.to_string();
+            let url = job.url.clone();
+            let temp_dir_path = job.work_dir.clone();
+
+            log!("Starting conversion for job {}: {}", job_id, url);
+
+            let mut final_download = None;
+            let mut last_error = String::new();
+            let mut last_code = ConversionError::Unknown;
+            let strategies = self.strategy_stats.order(&self.strategies.current());
+
+            for (attempt, strategy) in strategies.iter().enumerate() {
+                log!("Job {} attempt {} with strategy: {}", job_id, attempt + 1, strategy.name);
+
+                if attempt > 0 {
+                    self.restart_download(&job_id).await;
+                }
+
+                let (progress_tx, progress_rx) = tokio::sync::mpsc::unbounded_channel();
+                let request = DownloadRequest {
+                    job_id: &job_id,
+                    url: &url,
+                    output_dir: &temp_dir_path,
+                    format: job.format,
+                    quality: job.quality,
+                    clip: job.clip,
+                    strategy,
+                    progress: progress_tx,
+                };
+                let started = Instant::now();
+
+                // The tracker finishes once the downloader drops the request;
+                // cancelling drops both, which kills the extractor
+                let result = tokio::select! {
+                    biased;
+                    () = cancel.notified() => None,
+                    (result, ()) = async {
+                        tokio::join!(
+                            self.downloader.download(request),
+                            self.track_progress(&job_id, progress_rx),
+                        )
+                    } => Some(result),
+                };
+                let Some(result) = result else {
+                    remove_job_dir(&temp_dir_path).await;
+                    return;
+                };
+
+                match result {
+                    Ok(download) => {
+                        log!("Job {} attempt {} succeeded", job_id, attempt + 1);
+                        self.strategy_stats.record_success(&strategy.name, started.elapsed());
+                        final_download = Some(download);
+                        break; // Success!
+                    }
+                    Err(e) => {
+                        last_error = e.to_string();
+                        last_code = e.code();
+                        log!("Job {} attempt {} failed: {}", job_id, attempt + 1, last_error);
+                        let bot_detected = last_code == ConversionError::BotDetection;
+                        self.strategy_stats.record_failure(&strategy.name, started.elapsed(), bot_detected);
+
+                        // If it's a rate limit or bot detection, wait before next attempt
+                        if strategy.should_back_off(&last_error)
+                            && !pause(self.retry_delays.after_throttling, cancel).await
+                        {
+                            remove_job_dir(&temp_dir_path).await;
+                            return;
+                        }
+                    }
+                }
+
+                // Small delay between attempts
+                if attempt < strategies.len() - 1
+                    && !pause(self.retry_delays.between_attempts, cancel).await
+                {
+                    remove_job_dir(&temp_dir_path).await;
+                    return;
+                }
+            }
+
+            // Update job status based on results
+            if let Some(download) = final_download {
+                let tags = tokio::select! {
+                    biased;
+                    () = cancel.notified() => {
+                        remove_job_dir(&temp_dir_path).await;
+                        return;
+                    }
+                    tags = self.tag_file(&job_id, job.format, &job.tag_overrides, &download) => tags,
+                };
+                let audio_secs = audio_secs(job.clip, download.metadata.as_ref());
+                let title = download.metadata.and_then(|metadata| metadata.title);
+                self.cache_conversion(&job, &download.audio_path, title.clone(), tags.clone(), audio_secs)
+                    .await;
+                self.complete_job(&job_id, &temp_dir_path, download.audio_path, title, tags, audio_secs)
+                    .await;
+            } else {
+                self.fail_job(&job_id, &temp_dir_path, last_code, failure_message(last_code, &last_error)).await;
+            }
+        }
+
+        /// Writes the derived and overridden tags into the downloaded file.
+        ///
+        /// Tagging is best effort: if it fails the untagged file is kept and
+        /// `None` is returned.
+        async fn tag_file(
+            &self,
+            job_id: &str,
+            format: AudioFormat,
+            overrides: &TrackTags,
+            download: &Download,
+        ) -> Option<TrackTags> {
+            let derived = download.metadata.as_ref().map(derive_tags).unwrap_or_default();
+            let tags = derived.overridden_by(overrides);
+            if tags.is_empty() {
+                return None;
+            }
+
+            let cover = download.thumbnail_path.as_deref();
+            match self.tagger.write_tags(&download.audio_path, format, &tags, cover).await {
+                Ok(()) => Some(tags),
+                Err(e) => {
+                    log!("Job {} could not be tagged: {}", job_id, e);
+                    None
+                }
+            }
+        }
+
+        fn lock_running(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Notify>>> {
+            // The map only holds signals, so a panic elsewhere cannot leave it inconsistent
+            self.running
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+        }
+
+        /// Applies `update` to a job and tells status streams about it.
+        async fn update_job(&self, job_id: &str, update: JobUpdate) -> Result<ConversionJob, StoreError> {
+            let job = self.store.update(job_id, update).await?;
+            // Nobody may be listening, which is fine
+            let _ = self.updates.send(job.id.clone());
+            // A track's status is part of its playlist's status
+            if let Some(parent_id) = &job.parent_id {
+                let _ = self.updates.send(parent_id.clone());
+            }
+            Ok(job)
+        }
+
+        /// Puts a job back into the download phase before another attempt.
+        async fn restart_download(&self, job_id: &str) {
+            let result = self
+                .update_job(job_id, Box::new(|job| {
+                    if job.status() == JobStatus::Transcoding {
+                        job.transition(JobStatus::Downloading)?;
+                    }
+                    job.progress = None;
+                    Ok(())
+                }))
+                .await;
+            if let Err(e) = result {
+                log!("Job {} could not be reset for another attempt: {}", job_id, e);
+            }
+        }
+
+        /// Records progress events on the job until the sender is dropped.
+        ///
+        /// Download progress is written at most every
+        /// [`PROGRESS_WRITE_INTERVAL`] so a fast download does not flood the
+        /// store; the final 100% update is always written.
+        async fn track_progress(&self, job_id: &str, mut events: UnboundedReceiver<ProgressEvent>) {
+            let mut last_write: Option<Instant> = None;
+
+            while let Some(event) = events.recv().await {
+                let update: JobUpdate = match event {
+                    ProgressEvent::Downloading(progress) => {
+                        let due = last_write.is_none_or(|at| at.elapsed() >= PROGRESS_WRITE_INTERVAL);
+                        if !due && !progress.is_complete() {
+                            continue;
+                        }
+                        last_write = Some(Instant::now());
+                        Box::new(move |job| {
+                            // Late events must not bring back a cancelled job's progress
+                            if job.status().is_active() {
+                                job.progress = Some(progress);
+                            }
+                            Ok(())
+                        })
+                    }
+                    ProgressEvent::Transcoding => Box::new(|job| {
+                        if job.status() == JobStatus::Downloading {
+                            job.transition(JobStatus::Transcoding)?;
+                        }
+                        job.progress = None;
+                        Ok(())
+                    }),
+                };
+
+                if let Err(e) = self.update_job(job_id, update).await {
+                    log!("Job {} progress could not be recorded: {}", job_id, e);
+                }
+            }
+        }
+    }
+
+    /// Length of the audio cut from a video of the given metadata.
+    fn audio_secs(clip: Option<ClipRange>, metadata: Option<&VideoMetadata>) -> Option<u64> {
+        let video_secs = metadata.and_then(|metadata| metadata.duration).map(|secs| secs.round() as u64);
+        match clip {
+            None => video_secs,
+            Some(clip) => match (clip.duration_secs(), video_secs) {
+                (Some(clip_secs), Some(video_secs)) => Some(clip_secs.min(video_secs.saturating_sub(clip.start_secs))),
+                (Some(clip_secs), None) => Some(clip_secs),
+                (None, video_secs) => video_secs.map(|secs| secs.saturating_sub(clip.start_secs)),
+            },
+        }
+    }
+
+    async fn file_size(path: &Path) -> Option<u64> {
+        tokio::fs::metadata(path).await.ok().map(|metadata| metadata.len())
+    }
+
+    /// Sleeps for `duration`. Returns `false` if `cancel` fired meanwhile.
+    async fn pause(duration: Duration, cancel: &Notify) -> bool {
+        tokio::select! {
+            () = cancel.notified() => false,
+            () = tokio::time::sleep(duration) => true,
+        }
+    }
+
+    /// Deletes a cancelled job's directory with everything downloaded so far.
+    async fn remove_job_dir(dir: &Path) {
+        match tokio::fs::remove_dir_all(dir).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log!("Failed to remove job directory {}: {}", dir.display(), e),
+        }
+    }
+
+    /// Empties `dir`, creating it if needed.
+    async fn reset_dir(dir: &Path) -> std::io::Result<()> {
+        match tokio::fs::remove_dir_all(dir).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        tokio::fs::create_dir_all(dir).await
+    }
+
+    /// Deletes `ytmp3_*` directories under `work_dir` that no job refers to.
+    async fn remove_orphaned_dirs(work_dir: &Path, known_dirs: &HashSet<PathBuf>) -> usize {
+        let Ok(mut entries) = tokio::fs::read_dir(work_dir).await else {
+            return 0;
+        };
+
+        let mut removed = 0;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let is_job_dir = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("ytmp3_"));
+            if !is_job_dir || known_dirs.contains(&path) || !path.is_dir() {
+                continue;
+            }
+
+            match tokio::fs::remove_dir_all(&path).await {
+                Ok(()) => {
+                    log!("Removed orphaned job directory {}", path.display());
+                    removed += 1;
+                }
+                Err(e) => log!("Failed to remove orphaned job directory {}: {}", path.display(), e),
+            }
+        }
+        removed
+    }
+
+    impl Default for JobRunner {
+        fn default() -> Self {
+            Self::new(Arc::new(YtDlpDownloader::default()))
+        }
+    }
+
+    static RUNNER: OnceLock<JobRunner> = OnceLock::new();
+
+    /// Installs the runner used by the server functions and the download route.
+    ///
+    /// Must be called before the first request is handled; afterwards the
+    /// default yt-dlp runner is already in place.
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejected runner if one was already installed.
+    pub fn install_runner(runner: JobRunner) -> Result<(), Box<JobRunner>> {
+        RUNNER.set(runner).map_err(Box::new)
+    }
+
+    /// The runner installed with [`install_runner`], or a yt-dlp runner.
+    pub fn runner() -> &'static JobRunner {
+        RUNNER.get_or_init(JobRunner::default)
+    }
+
+    /// How much of their quotas a user has used on the installed [`runner`].
+    ///
+    /// # Errors
+    ///
+    /// See [`JobRunner::usage`].
+    pub async fn usage(user_id: &str, role: &str) -> Result<Usage, StoreError> {
+        runner().usage(user_id, role).await
+    }
+
+    /// Looks up a video on the installed [`runner`].
+    ///
+    /// # Errors
+    ///
+    /// See [`JobRunner::preview`].
+    pub async fn preview(video: &VideoRef) -> Result<VideoPreview, PreviewError> {
+        runner().preview(video).await
+    }
+
+    /// Starts a new conversion job on the installed [`runner`].
+    ///
+    /// # Errors
+    ///
+    /// See [`JobRunner::start_conversion`].
+    pub async fn start_conversion(
+        url: String,
+        options: ConversionOptions,
+    ) -> Result<String, StartError> {
+        runner().start_conversion(url, options).await
+    }
+
+    /// Checks access to a job on the installed [`runner`].
+    ///
+    /// # Errors
+    ///
+    /// See [`JobRunner::check_access`].
+    pub async fn check_access(job_id: &str, user_id: Option<&str>) -> Result<(), AccessError> {
+        runner().check_access(job_id, user_id).await
     }
 
-    // In a real application, you'd use a proper database or redis
-    // For now, we'll use a simple in-memory store
-    type JobStore = std::sync::Arc<tokio::sync::RwLock<HashMap<String, ConversionJob>>>;
-
-    static JOB_STORE: std::sync::LazyLock<JobStore> =
-        std::sync::LazyLock::new(|| std::sync::Arc::new(tokio::sync::RwLock::new(HashMap::new())));
-
-    /// Starts a new conversion job for a YouTube URL.
+    /// Gets the current status of a job on the installed [`runner`].
     ///
     /// # Errors
     ///
-    /// Returns an error if:
-    /// - Unable to create temporary directory
-    /// - Failed to store job in the job store
-    pub async fn start_conversion(
-        url: String,
-    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let job_id = Uuid::new_v4().to_string();
-
-        // Create temporary directory for this job
-        let temp_dir = tempfile::Builder::new()
-            .prefix("ytmp3_")
-            .tempdir_in("/home/app")?;
-
-        let job = ConversionJob {
-            id: job_id.clone(),
-            temp_dir,
-            mp3_path: None,
-            status: "processing".to_string(),
-            error: None,
-        };
-
-        // Store the job
-        JOB_STORE.write().await.insert(job_id.clone(), job);
-
-        // Start the conversion process in the background
-        let job_id_clone = job_id.clone();
-        let url_clone = url.clone();
+    /// See [`JobRunner::get_job_status`].
+    pub async fn get_job_status(
+        job_id: &str,
+    ) -> Result<ConvertResponse, Box<dyn std::error::Error + Send + Sync>> {
+        runner().get_job_status(job_id).await
+    }
 
-        tokio::spawn(async move {
-            process_conversion(job_id_clone, url_clone).await;
-        });
+    /// Cancels a job on the installed [`runner`].
+    ///
+    /// # Errors
+    ///
+    /// See [`JobRunner::cancel`].
+    pub async fn cancel_conversion(job_id: &str) -> Result<ConversionJob, CancelError> {
+        runner().cancel(job_id).await
+    }
 
-        Ok(job_id)
+    /// Streams the status of a job on the installed [`runner`].
+    ///
+    /// See [`JobRunner::status_stream`].
+    pub fn job_status_stream(job_id: String) -> impl futures::Stream<Item = ConvertResponse> + Send + 'static {
+        runner().status_stream(job_id)
     }
 
-    /// Gets the current status of a conversion job.
+    /// Retrieves the audio file of a job on the installed [`runner`].
     ///
     /// # Errors
     ///
-    /// This function doesn't typically return errors, but wraps responses in Result
-    /// for consistency with the API interface.
-    pub async fn get_job_status(
+    /// See [`JobRunner::get_audio_file`].
+    pub async fn get_audio_file(
         job_id: &str,
-    ) -> Result<ConvertResponse, Box<dyn std::error::Error + Send + Sync>> {
-        let jobs = JOB_STORE.read().await;
-
-        match jobs.get(job_id) {
-            Some(job) => {
-                let message = if let Some(ref error) = job.error {
-                    error.clone()
-                } else if job.status == "completed" {
-                    "Conversion completed successfully".to_string()
-                } else {
-                    "Processing your video...".to_string()
-                };
-
-                Ok(ConvertResponse {
-                    id: job.id.clone(),
-                    status: job.status.clone(),
-                    message,
-                })
-            }
-            None => Ok(ConvertResponse {
-                id: job_id.to_string(),
-                status: "not_found".to_string(),
-                message: "Job not found".to_string(),
-            }),
-        }
+    ) -> Result<AudioFile, Box<dyn std::error::Error + Send + Sync>> {
+        runner().get_audio_file(job_id).await
     }
 
-    /// Retrieves the MP3 file contents for a completed conversion job.
+    /// Collects a completed playlist job's tracks on the installed [`runner`].
     ///
     /// # Errors
     ///
-    /// Returns an error if:
-    /// - Job not found
-    /// - Conversion not completed yet
-    /// - MP3 file not found or unable to read file
-    /// - File system I/O errors
-    pub async fn get_mp3_file(
+    /// See [`JobRunner::get_playlist_archive`].
+    pub async fn get_playlist_archive(
         job_id: &str,
-    ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
-        let jobs = JOB_STORE.read().await;
-
-        match jobs.get(job_id) {
-            Some(job) if job.status == "completed" => {
-                if let Some(ref mp3_path) = job.mp3_path {
-                    let file_content = tokio::fs::read(mp3_path).await?;
-                    Ok(file_content)
-                } else {
-                    Err("MP3 file not found".into())
-                }
-            }
-            Some(_) => Err("Conversion not completed yet".into()),
-            None => Err("Job not found".into()),
-        }
-    }
-
-    async fn process_conversion(job_id: String, url: String) {
-          log!("Starting conversion for job {}: {}", job_id, url);
-          
-          let temp_dir_path = {
-              let jobs = JOB_STORE.read().await;
-              if let Some(job) = jobs.get(&job_id) {
-                  job.temp_dir.path().to_path_buf()
-              } else {
-                  log!("Job {} not found in store", job_id);
-                  return;
-              }
-          };
-
-          // Multiple retry strategies
-          let strategies = vec![
-              // Strategy 1: Android client (Docker-optimized)
-              vec![
-                  "--extractor-args", "youtube:player_client=android",
-                  "--user-agent", "com.google.android.youtube/17.31.35 (Linux; U; Android 11) gzip",
-                  "--no-check-certificates",
-              ],
-              // Strategy 2: Android TV client (often works well in containers)
-              vec![
-                  "--extractor-args", "youtube:player_client=android_embedded",
-                  "--user-agent", "com.google.android.youtube/17.31.35 (Linux; U; Android 11) gzip",
-              ],
-              // Strategy 3: iOS client
-              vec![
-                  "--extractor-args", "youtube:player_client=ios",
-                  "--user-agent", "com.google.ios.youtube/17.31.4 (iPhone14,3; U; CPU iOS 15_6 like Mac OS X)",
-              ],
-              // Strategy 4: Web client without cookies (Docker-safe)
-              vec![
-                  "--extractor-args", "youtube:player_client=web",
-                  "--user-agent", "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
-                  "--add-header", "Accept-Language:en-US,en;q=0.9",
-              ],
-              // Strategy 5: Legacy method with Docker optimizations
-              vec![
-                  "--extractor-args", "youtube:player_client=web",
-                  "--compat-options", "prefer-legacy-http-handler",
-                  "--no-check-certificates",
-                  "--prefer-insecure",
-              ],
-              // Strategy 6: Minimal approach for containers
-              vec![
-                  "--extractor-args", "youtube:player_client=mediaconnect",
-                  "--socket-timeout", "30",
-              ],
-          ];
-
-          let mut final_mp3_path = None;
-          let mut last_error = String::new();
-
-          for (attempt, strategy) in strategies.iter().enumerate() {
-              log!("Job {} attempt {} with strategy: {:?}", job_id, attempt + 1, strategy);
-
-              let mut cmd = Command::new("yt-dlp");
-              cmd.arg(&url)
-                  .arg("-x")
-                  .arg("--audio-format")
-                  .arg("mp3")
-                  .arg("--audio-quality")
-                  .arg("192K")
-                  .arg("-o")
-                  .arg("%(title)s.%(ext)s")
-                  .arg("--restrict-filenames")
-                  .current_dir(&temp_dir_path)
-                  .arg("--retries")
-                  .arg("2")
-                  .arg("--retry-sleep")
-                  .arg("3")
-                  .stdout(Stdio::piped())
-                  .stderr(Stdio::piped());
-
-              // Add strategy-specific arguments
-              for arg in strategy {
-                  cmd.arg(arg);
-              }
-
-              // Add common anti-detection measures for non-android strategies
-              if !strategy.contains(&"youtube:player_client=android") {
-                  cmd.arg("--sleep-interval")
-                      .arg("1")
-                      .arg("--max-sleep-interval")
-                      .arg("3");
-              }
-
-              let download_result = cmd.output().await;
-
-              match download_result {
-                  Ok(output) => {
-                      log!("Job {} attempt {} completed with status: {}", job_id, attempt + 1, output.status);
-                      log!("Job {} attempt {} stdout: {}", job_id, attempt + 1, String::from_utf8_lossy(&output.stdout));
-                      
-                      if output.status.success() {
-                          log!("Job {} attempt {} succeeded", job_id, attempt + 1);
-                          
-                          // Look for MP3 file
-                          if let Ok(mut entries) = tokio::fs::read_dir(&temp_dir_path).await {
-                              while let Ok(Some(entry)) = entries.next_entry().await {
-                                  let path = entry.path();
-                                  if path.extension().and_then(|s| s.to_str()) == Some("mp3") {
-                                      final_mp3_path = Some(path);
-                                      break;
-                                  }
-                              }
-                          }
-                          
-                          if final_mp3_path.is_some() {
-                              break; // Success!
-                          }
-                      } else {
-                          let error_msg = String::from_utf8_lossy(&output.stderr);
-                          last_error = error_msg.to_string();
-                          log!("Job {} attempt {} failed: {}", job_id, attempt + 1, error_msg);
-                          
-                          // If it's a rate limit or bot detection, wait before next attempt
-                          if error_msg.contains("Sign in to confirm") || 
-                             error_msg.contains("rate limit") ||
-                             error_msg.contains("429") {
-                              tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
-                          }
-                      }
-                  }
-                  Err(e) => {
-                      last_error = format!("Command execution failed: {}", e);
-                      log!("Job {} attempt {} command failed: {}", job_id, attempt + 1, e);
-                  }
-              }
-
-              // Small delay between attempts
-              if attempt < strategies.len() - 1 {
-                  tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
-              }
-          }
-
-          // Update job status based on results
-          let mut jobs = JOB_STORE.write().await;
-          if let Some(job) = jobs.get_mut(&job_id) {
-              if let Some(mp3_path) = final_mp3_path {
-                  job.status = "completed".to_string();
-                  job.mp3_path = Some(mp3_path);
-                  log!("Job {} completed successfully", job_id);
-              } else {
-                  job.status = "error".to_string();
-                  
-                  // Provide user-friendly error message
-                  let user_friendly_error = if last_error.contains("Sign in to confirm you're not a bot") {
-                      "YouTube is currently blocking automated downloads. This is temporary - please try again in 10-15 minutes, or try a different video.".to_string()
-                  } else if last_error.contains("Failed to extract any player response") {
-                      "YouTube has updated their protection. Please try again in a few minutes, or contact support if the issue persists.".to_string()
-                  } else if last_error.contains("Video unavailable") {
-                      "This video is unavailable. It may be private, deleted, or region-restricted.".to_string()
-                  } else if last_error.contains("age-restricted") || last_error.contains("age_restricted") {
-                      "This video is age-restricted and cannot be downloaded without authentication.".to_string()
-                  } else if last_error.contains("rate limit") || last_error.contains("too many requests") || last_error.contains("HTTP Error 429") {
-                      "YouTube is rate limiting requests. Please wait a few minutes before trying again.".to_string()
-                  } else if last_error.contains("premieres in") {
-                      "This video is a premiere that hasn't started yet. Please wait until it's available.".to_string()
-                  } else if last_error.contains("live stream") {
-                      "Live streams cannot be downloaded. Please wait until the stream ends or try a regular video.".to_string()
-                  } else {
-                      format!("Download failed after multiple attempts. Last error: {}", 
-                          last_error.lines().take(2).collect::<Vec<_>>().join(" "))
-                  };
-                  
-                  job.error = Some(user_friendly_error);
-                  log!("Job {} failed with error: {}", job_id, job.error.as_ref().unwrap());
-              }
-          }
-    }
-
-    pub fn is_valid_youtube_url(url: &str) -> bool {
-        url.contains("youtube.com/watch")
-            || url.contains("youtu.be/")
-            || url.contains("youtube.com/shorts/")
-            || url.contains("m.youtube.com/watch")
+    ) -> Result<PlaylistArchive, Box<dyn std::error::Error + Send + Sync>> {
+        runner().get_playlist_archive(job_id).await
+    }
+
+    /// Reports how each strategy has been doing on the installed [`runner`].
+    ///
+    /// See [`JobRunner::strategy_report`].
+    pub fn strategy_report() -> Vec<StrategyReport> {
+        runner().strategy_report()
+    }
+
+    /// The message for a job whose last attempt failed with `last_error`.
+    fn failure_message(code: ConversionError, last_error: &str) -> String {
+        match code {
+            ConversionError::Unknown => format!(
+                "{} Last error: {}",
+                code.message(),
+                last_error.lines().take(2).collect::<Vec<_>>().join(" ")
+            ),
+            code => code.message().to_string(),
+        }
     }
 
     #[cfg(test)]
     mod tests {
         use super::*;
+        use crate::domain::entities::preview::Availability;
+        use crate::domain::services::downloader::{fixture_mp3, FakeDownloader, FakeOutcome};
+        use crate::domain::services::tagging::{FakeTagger, VideoMetadata};
+
+        const URL: &str = "https://www.youtube.com/watch?v=dQw4w9WgXcQ";
 
-        // Helper to clean up job store between tests
-        async fn reset_job_store() {
-            JOB_STORE.write().await.clear();
+        const BOT_CHECK: &str =
+            "ERROR: [youtube] dQw4w9WgXcQ: Sign in to confirm you're not a bot. Use --cookies-from-browser or --cookies for the authentication.";
+
+        struct Harness {
+            runner: JobRunner,
+            downloader: Arc<FakeDownloader>,
+            tagger: Arc<FakeTagger>,
+            work_dir: tempfile::TempDir,
         }
 
-        #[tokio::test]
-        async fn test_is_valid_youtube_url() {
-            assert!(is_valid_youtube_url(
-                "https://www.youtube.com/watch?v=dQw4w9WgXcQ"
-            ));
-            assert!(is_valid_youtube_url("https://youtu.be/dQw4w9WgXcQ"));
-            assert!(is_valid_youtube_url(
-                "https://m.youtube.com/watch?v=dQw4w9WgXcQ"
-            ));
-            assert!(is_valid_youtube_url(
-                "https://www.youtube.com/shorts/abcdef123"
-            ));
-            assert!(is_valid_youtube_url(
-                "https://www.youtube.com/watch?v=some_id&list=PL..."
-            ));
-            assert!(!is_valid_youtube_url("https://www.google.com"));
-            assert!(!is_valid_youtube_url(
-                "https://www.youtube.com/feed/subscriptions"
-            ));
-            assert!(!is_valid_youtube_url(
-                "https://example.com/watch?v=dQw4w9WgXcQ"
-            ));
+        fn test_runner(downloader: FakeDownloader) -> Harness {
+            test_runner_with(downloader, |runner| runner)
+        }
+
+        // Each test gets its own store and work dir, so tests can run in parallel
+        fn test_runner_with(
+            downloader: FakeDownloader,
+            configure: impl FnOnce(JobRunner) -> JobRunner,
+        ) -> Harness {
+            let downloader = Arc::new(downloader);
+            let tagger = Arc::new(FakeTagger::default());
+            let work_dir = tempfile::tempdir().unwrap();
+            let runner = configure(
+                JobRunner::new(downloader.clone())
+                    .with_tagger(tagger.clone())
+                    .with_work_dir(work_dir.path())
+                    .with_retry_delays(RetryDelays::NONE),
+            );
+            Harness {
+                runner,
+                downloader,
+                tagger,
+                work_dir,
+            }
+        }
+
+        async fn wait_until_finished(runner: &JobRunner, job_id: &str) -> ConvertResponse {
+            for _ in 0..500 {
+                let response = runner.get_job_status(job_id).await.unwrap();
+                if response.status.is_finished() {
+                    return response;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("job {job_id} did not finish in time");
         }
 
         #[tokio::test]
         async fn test_start_conversion_creates_job() {
-            reset_job_store().await;
-            // We are not testing the conversion itself, just that the job is created.
-            // The conversion process is spawned in the background and would require `yt-dlp` to be installed.
+            let harness = test_runner(FakeDownloader::default());
+            let runner = &harness.runner;
             let url = "https://www.youtube.com/watch?v=video_id".to_string();
-            let job_id = start_conversion(url).await.unwrap();
+            let job_id = runner.start_conversion(url, ConversionOptions::default()).await.unwrap();
 
-            let jobs = JOB_STORE.read().await;
-            let job = jobs.get(&job_id).expect("Job should be in the store");
+            let job = runner
+                .store
+                .get(&job_id)
+                .await
+                .unwrap()
+                .expect("Job should be in the store");
 
             assert_eq!(job.id, job_id);
-            assert_eq!(job.status, "processing");
+            assert_eq!(job.status(), JobStatus::Queued);
             assert!(job.error.is_none());
-            assert!(job.mp3_path.is_none());
+            assert!(job.audio_path.is_none());
+        }
+
+        #[tokio::test]
+        async fn test_preview_is_looked_up_once() {
+            let preview = VideoPreview {
+                video_id: "dQw4w9WgXcQ".to_string(),
+                title: Some("Never Gonna Give You Up".to_string()),
+                channel: Some("Rick Astley".to_string()),
+                duration_secs: Some(213),
+                thumbnail_url: None,
+                availability: Some(Availability::Public),
+                chapters: Vec::new(),
+            };
+            let harness = test_runner(FakeDownloader::default().with_preview(preview.clone()));
+            let (runner, downloader) = (&harness.runner, &harness.downloader);
+            let video = VideoRef::parse(URL).unwrap();
+
+            assert_eq!(runner.preview(&video).await.unwrap(), preview);
+            assert_eq!(runner.preview(&video).await.unwrap(), preview);
+            assert_eq!(downloader.preview_count(), 1);
+
+            let playlist = VideoRef::parse("https://www.youtube.com/playlist?list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI").unwrap();
+            assert!(matches!(runner.preview(&playlist).await, Err(PreviewError::NotAVideo)));
+        }
+
+        #[tokio::test]
+        async fn test_preview_failure_is_classified() {
+            let harness = test_runner(FakeDownloader::default());
+            let video = VideoRef::parse(URL).unwrap();
+
+            let error = harness.runner.preview(&video).await.unwrap_err();
+            assert!(matches!(error, PreviewError::Lookup(ConversionError::Unavailable)), "{error:?}");
+            assert_eq!(error.to_string(), ConversionError::Unavailable.message());
+        }
+
+        #[tokio::test]
+        async fn test_only_the_owner_may_access_a_job() {
+            let harness = test_runner(FakeDownloader::default());
+            let runner = &harness.runner;
+            let options = ConversionOptions {
+                owner_id: Some("alice".to_string()),
+                ..ConversionOptions::default()
+            };
+            let job_id = runner.start_conversion(URL.to_string(), options).await.unwrap();
 
-            // The background task will run and likely fail because yt-dlp isn't real for the test.
-            // We can't easily check the final state without more complex test setup (e.g. mocking Command).
+            assert!(runner.check_access(&job_id, Some("alice")).await.is_ok());
+            assert!(matches!(
+                runner.check_access(&job_id, Some("bob")).await,
+                Err(AccessError::Forbidden)
+            ));
+            assert!(matches!(runner.check_access(&job_id, None).await, Err(AccessError::Forbidden)));
+            assert!(matches!(
+                runner.check_access("missing", Some("alice")).await,
+                Err(AccessError::NotFound)
+            ));
+
+            // Jobs from before owners were recorded stay open
+            let job_id = runner.start_conversion(URL.to_string(), ConversionOptions::default()).await.unwrap();
+            assert!(runner.check_access(&job_id, Some("bob")).await.is_ok());
         }
 
         #[tokio::test]
         async fn test_get_job_status_not_found() {
-            reset_job_store().await;
+            let harness = test_runner(FakeDownloader::default());
+            let runner = &harness.runner;
             let job_id = "non-existent-job-id";
-            let response = get_job_status(job_id).await.unwrap();
+            let response = runner.get_job_status(job_id).await.unwrap();
             assert_eq!(response.id, job_id);
-            assert_eq!(response.status, "not_found");
+            assert_eq!(response.status, JobStatus::Failed);
             assert_eq!(response.message, "Job not found");
         }
 
         #[tokio::test]
         async fn test_get_job_status_processing() {
-            reset_job_store().await;
+            let harness = test_runner(FakeDownloader::default());
+            let runner = &harness.runner;
             let url = "https://www.youtube.com/watch?v=video_id".to_string();
-            let job_id = start_conversion(url).await.unwrap();
+            let job_id = runner.start_conversion(url, ConversionOptions::default()).await.unwrap();
 
-            let response = get_job_status(&job_id).await.unwrap();
+            let response = runner.get_job_status(&job_id).await.unwrap();
             assert_eq!(response.id, job_id);
-            assert_eq!(response.status, "processing");
-            assert_eq!(response.message, "Processing your video...");
+            assert_eq!(response.status, JobStatus::Queued);
+            assert_eq!(response.queue_position, Some(1));
+            assert_eq!(
+                response.message,
+                "Waiting for a free converter (position 1 in queue)..."
+            );
         }
 
         #[tokio::test]
-        async fn test_get_mp3_file_job_not_found() {
-            reset_job_store().await;
+        async fn test_get_audio_file_job_not_found() {
+            let harness = test_runner(FakeDownloader::default());
+            let runner = &harness.runner;
             let job_id = "non-existent-job-id";
-            let result = get_mp3_file(job_id).await;
+            let result = runner.get_audio_file(job_id).await;
             assert!(result.is_err());
             assert_eq!(result.err().unwrap().to_string(), "Job not found");
         }
 
         #[tokio::test]
-        async fn test_get_mp3_file_conversion_not_completed() {
-            reset_job_store().await;
+        async fn test_get_audio_file_conversion_not_completed() {
+            let harness = test_runner(FakeDownloader::default());
+            let runner = &harness.runner;
             let url = "https://www.youtube.com/watch?v=video_id".to_string();
-            let job_id = start_conversion(url).await.unwrap();
+            let job_id = runner.start_conversion(url, ConversionOptions::default()).await.unwrap();
 
-            let result = get_mp3_file(&job_id).await;
+            let result = runner.get_audio_file(&job_id).await;
             assert!(result.is_err());
             assert_eq!(
                 result.err().unwrap().to_string(),
@@ -456,69 +2197,702 @@ pub mod server {
 
         #[tokio::test]
         async fn test_get_job_status_completed() {
-            reset_job_store().await;
+            let harness = test_runner(FakeDownloader::default());
+            let runner = &harness.runner;
             let job_id = "completed-job".to_string();
             let temp_dir = tempfile::tempdir().unwrap();
-            let mp3_path = temp_dir.path().join("test.mp3");
-            tokio::fs::write(&mp3_path, "mp3 content").await.unwrap();
-
-            let job = ConversionJob {
-                id: job_id.clone(),
-                temp_dir,
-                mp3_path: Some(mp3_path),
-                status: "completed".to_string(),
-                error: None,
-            };
-            JOB_STORE.write().await.insert(job_id.clone(), job);
+            let audio_path = temp_dir.path().join("test.mp3");
+            tokio::fs::write(&audio_path, "mp3 content").await.unwrap();
+
+            let mut job = ConversionJob::new(job_id.clone(), URL.to_string(), temp_dir.path().to_path_buf());
+            job.transition(JobStatus::Downloading).unwrap();
+            job.transition(JobStatus::Completed).unwrap();
+            job.audio_path = Some(audio_path);
+            runner.store.insert(job).await.unwrap();
 
-            let response = get_job_status(&job_id).await.unwrap();
+            let response = runner.get_job_status(&job_id).await.unwrap();
             assert_eq!(response.id, job_id);
-            assert_eq!(response.status, "completed");
+            assert_eq!(response.status, JobStatus::Completed);
             assert_eq!(response.message, "Conversion completed successfully");
         }
 
         #[tokio::test]
-        async fn test_get_mp3_file_success() {
-            reset_job_store().await;
+        async fn test_get_audio_file_success() {
+            let harness = test_runner(FakeDownloader::default());
+            let runner = &harness.runner;
             let job_id = "completed-job-for-mp3".to_string();
             let temp_dir = tempfile::tempdir().unwrap();
-            let mp3_path = temp_dir.path().join("test.mp3");
+            let audio_path = temp_dir.path().join("test.mp3");
             let file_contents = b"mp3 file data";
-            tokio::fs::write(&mp3_path, file_contents).await.unwrap();
+            tokio::fs::write(&audio_path, file_contents).await.unwrap();
 
-            let job = ConversionJob {
-                id: job_id.clone(),
-                temp_dir,
-                mp3_path: Some(mp3_path),
-                status: "completed".to_string(),
-                error: None,
-            };
-            JOB_STORE.write().await.insert(job_id.clone(), job);
+            let mut job = ConversionJob::new(job_id.clone(), URL.to_string(), temp_dir.path().to_path_buf());
+            job.transition(JobStatus::Downloading).unwrap();
+            job.transition(JobStatus::Completed).unwrap();
+            job.audio_path = Some(audio_path);
+            runner.store.insert(job).await.unwrap();
 
-            let result = get_mp3_file(&job_id).await.unwrap();
-            assert_eq!(result, file_contents);
+            let result = runner.get_audio_file(&job_id).await.unwrap();
+            assert_eq!(tokio::fs::read(&result.path).await.unwrap(), file_contents);
+            assert_eq!(result.format, AudioFormat::Mp3);
         }
 
         #[tokio::test]
         async fn test_get_job_status_error() {
-            reset_job_store().await;
+            let harness = test_runner(FakeDownloader::default());
+            let runner = &harness.runner;
             let job_id = "error-job".to_string();
             let temp_dir = tempfile::tempdir().unwrap();
             let error_message = "Something went wrong".to_string();
 
-            let job = ConversionJob {
-                id: job_id.clone(),
-                temp_dir,
-                mp3_path: None,
-                status: "error".to_string(),
-                error: Some(error_message.clone()),
-            };
-            JOB_STORE.write().await.insert(job_id.clone(), job);
+            let mut job = ConversionJob::new(job_id.clone(), URL.to_string(), temp_dir.path().to_path_buf());
+            job.transition(JobStatus::Failed).unwrap();
+            job.error = Some(error_message.clone());
+            runner.store.insert(job).await.unwrap();
 
-            let response = get_job_status(&job_id).await.unwrap();
+            let response = runner.get_job_status(&job_id).await.unwrap();
             assert_eq!(response.id, job_id);
-            assert_eq!(response.status, "error");
+            assert_eq!(response.status, JobStatus::Failed);
             assert_eq!(response.message, error_message);
         }
+
+        #[tokio::test]
+        async fn test_conversion_completes_with_first_strategy() {
+            let harness = test_runner(FakeDownloader::default());
+            let (runner, downloader) = (&harness.runner, &harness.downloader);
+            let url = "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string();
+            let job_id = runner.start_conversion(url, ConversionOptions::default()).await.unwrap();
+
+            let response = wait_until_finished(runner, &job_id).await;
+            assert_eq!(response.status, JobStatus::Completed);
+            assert!(response.progress.is_none());
+            assert_eq!(downloader.attempts(), vec!["android"]);
+            assert_eq!(tokio::fs::read(runner.get_audio_file(&job_id).await.unwrap().path).await.unwrap(), fixture_mp3());
+
+            let job = runner.store.get(&job_id).await.unwrap().unwrap();
+            let phases: Vec<JobStatus> = job.history.transitions().iter().map(|t| t.status).collect();
+            assert_eq!(
+                phases,
+                vec![
+                    JobStatus::Queued,
+                    JobStatus::Downloading,
+                    JobStatus::Transcoding,
+                    JobStatus::Completed,
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn test_conversion_records_requested_options() {
+            use crate::domain::entities::quality::{Bitrate, SampleRate};
+
+            let harness = test_runner(FakeDownloader::default());
+            let runner = &harness.runner;
+            let quality = QualityProfile {
+                bitrate: Bitrate::Vbr(0),
+                sample_rate: Some(SampleRate::Hz48000),
+                channels: None,
+            };
+            let options = ConversionOptions {
+                format: AudioFormat::Flac,
+                quality,
+                clip: Some(ClipRange::new(83, Some(245)).unwrap()),
+                ..ConversionOptions::default()
+            };
+            let job_id = runner.start_conversion(URL.to_string(), options).await.unwrap();
+
+            let response = wait_until_finished(runner, &job_id).await;
+            assert_eq!(response.status, JobStatus::Completed);
+            assert_eq!(response.format, AudioFormat::Flac);
+            assert_eq!(response.quality, quality);
+            assert_eq!(response.clip, Some(ClipRange::new(83, Some(245)).unwrap()));
+
+            let job = runner.store.get(&job_id).await.unwrap().unwrap();
+            assert_eq!(
+                job.audio_path.unwrap().extension().and_then(|ext| ext.to_str()),
+                Some("flac")
+            );
+            assert_eq!(runner.get_audio_file(&job_id).await.unwrap().format, AudioFormat::Flac);
+        }
+
+        #[tokio::test]
+        async fn test_completed_jobs_count_against_their_owners_quota() {
+            let metadata = VideoMetadata {
+                duration: Some(212.4),
+                ..VideoMetadata::default()
+            };
+            let harness = test_runner(FakeDownloader::default().with_metadata(metadata));
+            let runner = &harness.runner;
+            let options = ConversionOptions {
+                owner_id: Some("alice".to_string()),
+                clip: Some(ClipRange::new(150, None).unwrap()),
+                ..ConversionOptions::default()
+            };
+            let job_id = runner.start_conversion(URL.to_string(), options).await.unwrap();
+            wait_until_finished(runner, &job_id).await;
+
+            let job = runner.store().get(&job_id).await.unwrap().unwrap();
+            assert_eq!(job.audio_secs, Some(62));
+            assert!(job.audio_bytes.is_some_and(|bytes| bytes > 0));
+
+            let usage = runner.usage("alice", "authenticated").await.unwrap();
+            assert_eq!(usage.conversions.used, 1);
+            assert_eq!(usage.audio_minutes.used, 2);
+            assert_eq!(usage.stored_bytes.used, job.audio_bytes.unwrap());
+            assert_eq!(runner.usage("bob", "authenticated").await.unwrap().conversions.used, 0);
+        }
+
+        #[tokio::test]
+        async fn test_conversion_tags_file_with_derived_and_overridden_tags() {
+            let metadata = VideoMetadata {
+                title: Some("Rick Astley - Never Gonna Give You Up (Official Music Video)".to_string()),
+                channel: Some("Rick Astley".to_string()),
+                upload_date: Some("20091025".to_string()),
+                ..VideoMetadata::default()
+            };
+            let harness = test_runner(FakeDownloader::default().with_metadata(metadata));
+            let runner = &harness.runner;
+            let options = ConversionOptions {
+                tags: TrackTags {
+                    album: Some("Whenever You Need Somebody".to_string()),
+                    year: Some("1987".to_string()),
+                    ..TrackTags::default()
+                },
+                ..ConversionOptions::default()
+            };
+            let job_id = runner.start_conversion(URL.to_string(), options).await.unwrap();
+
+            let response = wait_until_finished(runner, &job_id).await;
+            let expected = TrackTags {
+                title: Some("Never Gonna Give You Up".to_string()),
+                artist: Some("Rick Astley".to_string()),
+                album: Some("Whenever You Need Somebody".to_string()),
+                year: Some("1987".to_string()),
+            };
+            assert_eq!(response.tags, Some(expected.clone()));
+            assert_eq!(harness.tagger.tagged(), vec![(expected, true)]);
+        }
+
+        #[tokio::test]
+        async fn test_conversion_without_metadata_or_overrides_skips_tagging() {
+            let harness = test_runner(FakeDownloader::default());
+            let job_id = harness
+                .runner
+                .start_conversion(URL.to_string(), ConversionOptions::default())
+                .await
+                .unwrap();
+
+            let response = wait_until_finished(&harness.runner, &job_id).await;
+            assert_eq!(response.status, JobStatus::Completed);
+            assert_eq!(response.tags, None);
+            assert!(harness.tagger.tagged().is_empty());
+            let file = harness.runner.get_audio_file(&job_id).await.unwrap();
+            assert_eq!(file.file_name(&FileNameTemplate::default()), format!("{job_id}.mp3"));
+        }
+
+        #[tokio::test]
+        async fn test_download_is_named_after_the_video() {
+            let metadata = VideoMetadata {
+                title: Some("Sigur Rós - Hoppípolla (Official Video)".to_string()),
+                ..VideoMetadata::default()
+            };
+            let harness = test_runner(FakeDownloader::default().with_metadata(metadata));
+            let runner = &harness.runner;
+            let job_id = runner.start_conversion(URL.to_string(), ConversionOptions::default()).await.unwrap();
+            wait_until_finished(runner, &job_id).await;
+
+            let file = runner.get_audio_file(&job_id).await.unwrap();
+            assert_eq!(file.title.as_deref(), Some("Sigur Rós - Hoppípolla (Official Video)"));
+            let name = |template: &str| file.file_name(&FileNameTemplate::parse(template).unwrap());
+            assert_eq!(name("{artist} - {title}"), "Sigur Rós - Hoppípolla.mp3");
+            assert_eq!(name("{title}"), "Hoppípolla.mp3");
+            // Without an album the published title is used
+            assert_eq!(name("{album} - {title}"), "Sigur Rós - Hoppípolla (Official Video).mp3");
+        }
+
+        #[tokio::test]
+        async fn test_conversion_retries_with_next_strategy() {
+            let harness = test_runner(FakeDownloader::new([
+                FakeOutcome::Fail(BOT_CHECK.to_string()),
+                FakeOutcome::Fail("ERROR: unable to download video data: HTTP Error 403: Forbidden".to_string()),
+            ]));
+            let (runner, downloader) = (&harness.runner, &harness.downloader);
+            let url = "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string();
+            let job_id = runner.start_conversion(url, ConversionOptions::default()).await.unwrap();
+
+            let response = wait_until_finished(runner, &job_id).await;
+            assert_eq!(response.status, JobStatus::Completed);
+            assert_eq!(downloader.attempts(), vec!["android", "android_embedded", "ios"]);
+            assert_eq!(tokio::fs::read(runner.get_audio_file(&job_id).await.unwrap().path).await.unwrap(), fixture_mp3());
+        }
+
+        #[test]
+        fn test_failure_message_quotes_unknown_errors() {
+            let last_error = "ERROR: unable to download video data: HTTP Error 403: Forbidden\nsecond\nthird";
+            assert_eq!(
+                failure_message(ConversionError::classify(last_error), last_error),
+                "Download failed after multiple attempts. Last error: ERROR: unable to download video data: HTTP Error 403: Forbidden second"
+            );
+            assert_eq!(
+                failure_message(ConversionError::Premiere, "ERROR: Premieres in 3 hours"),
+                ConversionError::Premiere.message()
+            );
+        }
+
+        #[tokio::test]
+        async fn test_later_jobs_skip_bot_checked_strategies() {
+            let harness = test_runner_with(
+                FakeDownloader::new([FakeOutcome::Fail(BOT_CHECK.to_string())]),
+                |runner| {
+                    runner
+                        .with_strategies(["a", "b", "c"].iter().map(|name| Strategy::new(name, &[])).collect())
+                        .with_strategy_stats(StrategyStats::new(1, Duration::from_secs(600)))
+                },
+            );
+            let (runner, downloader) = (&harness.runner, &harness.downloader);
+            for _ in 0..2 {
+                let job_id = runner.start_conversion(URL.to_string(), ConversionOptions::default()).await.unwrap();
+                assert_eq!(wait_until_finished(runner, &job_id).await.status, JobStatus::Completed);
+            }
+
+            assert_eq!(downloader.attempts(), vec!["a", "b", "b"]);
+            let report = runner.strategy_report();
+            let names: Vec<&str> = report.iter().map(|strategy| strategy.name.as_str()).collect();
+            assert_eq!(names, ["b", "c", "a"]);
+            assert_eq!(report[0].successes, 2);
+            assert!(report[2].benched_for_secs.is_some());
+        }
+
+        #[tokio::test]
+        async fn test_conversion_fails_after_all_strategies() {
+            let strategies = Strategy::defaults().len();
+            let harness = test_runner(FakeDownloader::always_failing(BOT_CHECK, strategies));
+            let (runner, downloader) = (&harness.runner, &harness.downloader);
+            let url = "https://www.youtube.com/watch?v=dQw4w9WgXcQ".to_string();
+            let job_id = runner.start_conversion(url, ConversionOptions::default()).await.unwrap();
+
+            let response = wait_until_finished(runner, &job_id).await;
+            assert_eq!(response.status, JobStatus::Failed);
+            assert!(response.message.starts_with("YouTube is currently blocking automated downloads"));
+            assert_eq!(response.error_code, Some(ConversionError::BotDetection));
+            assert_eq!(downloader.attempts().len(), strategies);
+            assert_eq!(
+                runner.get_audio_file(&job_id).await.err().unwrap().to_string(),
+                "Conversion not completed yet"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_status_stream_follows_job_until_finished() {
+            use futures::StreamExt;
+
+            let harness = test_runner(FakeDownloader::default());
+            let runner = &harness.runner;
+            let job_id = runner.start_conversion(URL.to_string(), ConversionOptions::default()).await.unwrap();
+
+            let responses: Vec<ConvertResponse> = tokio::time::timeout(
+                Duration::from_secs(5),
+                runner.status_stream(job_id.clone()).collect(),
+            )
+            .await
+            .expect("stream should end once the job finishes");
+
+            let (last, earlier) = responses.split_last().unwrap();
+            assert_eq!(last.status, JobStatus::Completed);
+            assert!(earlier.iter().all(|r| r.status.is_active()));
+            assert!(responses.windows(2).all(|pair| pair[0] != pair[1]));
+        }
+
+        #[tokio::test]
+        async fn test_status_stream_of_unknown_job_ends_immediately() {
+            use futures::StreamExt;
+
+            let harness = test_runner(FakeDownloader::default());
+            let responses: Vec<ConvertResponse> =
+                harness.runner.status_stream("missing".to_string()).collect().await;
+            assert_eq!(responses, vec![ConvertResponse::failed("missing", "Job not found")]);
+        }
+
+        #[tokio::test]
+        async fn test_cancel_running_job_stops_download_and_removes_files() {
+            let harness = test_runner(FakeDownloader::default().with_delay(Duration::from_secs(30)));
+            let (runner, downloader) = (&harness.runner, &harness.downloader);
+            let job_id = runner.start_conversion(URL.to_string(), ConversionOptions::default()).await.unwrap();
+
+            for _ in 0..500 {
+                if !downloader.attempts().is_empty() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            let work_dir = runner.store.get(&job_id).await.unwrap().unwrap().work_dir;
+            tokio::fs::write(work_dir.join("partial.webm.part"), b"partial").await.unwrap();
+
+            let cancelled = runner.cancel(&job_id).await.unwrap();
+            assert_eq!(cancelled.status(), JobStatus::Cancelled);
+
+            for _ in 0..500 {
+                if !work_dir.exists() && runner.lock_running().is_empty() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            assert!(!work_dir.exists(), "partial files should be deleted");
+            assert!(runner.lock_running().is_empty(), "worker should have moved on");
+            assert_eq!(downloader.attempts(), vec!["android"]);
+
+            let response = runner.get_job_status(&job_id).await.unwrap();
+            assert_eq!(response.status, JobStatus::Cancelled);
+            assert_eq!(response.message, "Conversion was cancelled");
+            assert!(matches!(
+                runner.cancel(&job_id).await,
+                Err(CancelError::Finished(JobStatus::Cancelled))
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_cancel_queued_job_leaves_the_queue() {
+            let harness = test_runner_with(
+                FakeDownloader::default().with_delay(Duration::from_millis(50)),
+                |runner| runner.with_workers(1),
+            );
+            let runner = &harness.runner;
+            let first = runner.start_conversion(URL.to_string(), ConversionOptions::default()).await.unwrap();
+            let second = runner.start_conversion(URL.to_string(), ConversionOptions::default()).await.unwrap();
+
+            let work_dir = runner.cancel(&second).await.unwrap().work_dir;
+            assert!(!work_dir.exists());
+            assert_eq!(runner.get_job_status(&second).await.unwrap().queue_position, None);
+
+            assert_eq!(wait_until_finished(runner, &first).await.status, JobStatus::Completed);
+            assert_eq!(harness.downloader.attempts().len(), 1);
+            assert_eq!(
+                runner.get_job_status(&second).await.unwrap().status,
+                JobStatus::Cancelled
+            );
+            assert!(matches!(runner.cancel("missing").await, Err(CancelError::NotFound)));
+        }
+
+        async fn job_in(harness: &Harness, id: &str, status: JobStatus) -> ConversionJob {
+            let work_dir = harness.work_dir.path().join(format!("ytmp3_{id}"));
+            tokio::fs::create_dir_all(&work_dir).await.unwrap();
+            let mut job = ConversionJob::new(id.to_string(), URL.to_string(), work_dir);
+            if status != JobStatus::Queued {
+                job.transition(JobStatus::Downloading).unwrap();
+            }
+            if status == JobStatus::Completed {
+                job.transition(JobStatus::Completed).unwrap();
+            }
+            job
+        }
+
+        #[tokio::test]
+        async fn test_recover_reattaches_completed_and_fails_interrupted() {
+            let harness = test_runner(FakeDownloader::default());
+            let runner = &harness.runner;
+
+            let mut kept = job_in(&harness, "kept", JobStatus::Completed).await;
+            let kept_path = kept.work_dir.join("song.mp3");
+            tokio::fs::write(&kept_path, fixture_mp3()).await.unwrap();
+            kept.audio_path = Some(kept_path);
+            runner.store.insert(kept).await.unwrap();
+
+            let mut lost = job_in(&harness, "lost", JobStatus::Completed).await;
+            lost.audio_path = Some(lost.work_dir.join("gone.mp3"));
+            runner.store.insert(lost).await.unwrap();
+
+            let interrupted = job_in(&harness, "interrupted", JobStatus::Downloading).await;
+            runner.store.insert(interrupted).await.unwrap();
+
+            let orphan = harness.work_dir.path().join("ytmp3_orphan");
+            tokio::fs::create_dir_all(&orphan).await.unwrap();
+
+            let report = runner.recover(RecoveryPolicy::Fail).await.unwrap();
+            assert_eq!(
+                report,
+                RecoveryReport {
+                    reattached: 1,
+                    missing_files: 1,
+                    failed: 1,
+                    requeued: 0,
+                    orphaned_dirs_removed: 1,
+                }
+            );
+
+            assert_eq!(tokio::fs::read(runner.get_audio_file("kept").await.unwrap().path).await.unwrap(), fixture_mp3());
+            let lost = runner.get_job_status("lost").await.unwrap();
+            assert_eq!(lost.status, JobStatus::Expired);
+            assert!(lost.message.starts_with("This download has expired"));
+            assert_eq!(
+                runner.get_audio_file("lost").await.err().unwrap().to_string(),
+                "This download has expired"
+            );
+            let interrupted = runner.get_job_status("interrupted").await.unwrap();
+            assert_eq!(interrupted.status, JobStatus::Failed);
+            assert!(interrupted.message.contains("server restarted"));
+            assert!(!orphan.exists());
+            assert!(harness.work_dir.path().join("ytmp3_interrupted").exists());
+        }
+
+        #[tokio::test]
+        async fn test_recover_requeues_interrupted_jobs() {
+            let harness = test_runner(FakeDownloader::default());
+            let (runner, downloader) = (&harness.runner, &harness.downloader);
+
+            let interrupted = job_in(&harness, "interrupted", JobStatus::Downloading).await;
+            let partial = interrupted.work_dir.join("video.webm.part");
+            tokio::fs::write(&partial, b"partial").await.unwrap();
+            runner.store.insert(interrupted).await.unwrap();
+
+            let report = runner.recover(RecoveryPolicy::Requeue).await.unwrap();
+            assert_eq!(report.requeued, 1);
+
+            let response = wait_until_finished(runner, "interrupted").await;
+            assert_eq!(response.status, JobStatus::Completed);
+            assert_eq!(downloader.attempts(), vec!["android"]);
+            assert!(!partial.exists());
+        }
+
+        #[tokio::test]
+        async fn test_queue_limits_workers_and_rejects_when_full() {
+            let harness = test_runner_with(
+                FakeDownloader::default().with_delay(Duration::from_millis(50)),
+                |runner| runner.with_workers(1).with_max_queue_len(2),
+            );
+            let (runner, downloader) = (&harness.runner, &harness.downloader);
+
+            let first = runner.start_conversion(URL.to_string(), ConversionOptions::default()).await.unwrap();
+            let second = runner.start_conversion(URL.to_string(), ConversionOptions::default()).await.unwrap();
+            assert_eq!(runner.get_job_status(&first).await.unwrap().queue_position, Some(1));
+            assert_eq!(runner.get_job_status(&second).await.unwrap().queue_position, Some(2));
+
+            let rejected = runner.start_conversion(URL.to_string(), ConversionOptions::default()).await;
+            assert!(matches!(rejected, Err(StartError::Busy(_))));
+            assert_eq!(runner.store.list().await.unwrap().len(), 2);
+
+            assert_eq!(wait_until_finished(runner, &first).await.status, JobStatus::Completed);
+            assert_eq!(wait_until_finished(runner, &second).await.status, JobStatus::Completed);
+            assert_eq!(downloader.max_concurrent(), 1);
+        }
+
+        const PLAYLIST_URL: &str = "https://www.youtube.com/playlist?list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI";
+
+        fn three_track_playlist() -> PlaylistListing {
+            use crate::domain::entities::playlist::PlaylistEntry;
+
+            PlaylistListing {
+                title: Some("Road Trip".to_string()),
+                entries: ["first", "second", "third"]
+                    .map(|title| PlaylistEntry {
+                        video_id: format!("{title:_<11}"),
+                        title: Some(title.to_string()),
+                        duration_secs: Some(213),
+                    })
+                    .to_vec(),
+            }
+        }
+
+        fn playlist_options() -> ConversionOptions {
+            ConversionOptions {
+                whole_playlist: true,
+                owner_id: Some("alice".to_string()),
+                ..ConversionOptions::default()
+            }
+        }
+
+        fn single_video_options() -> ConversionOptions {
+            ConversionOptions {
+                whole_playlist: false,
+                ..playlist_options()
+            }
+        }
+
+        #[tokio::test]
+        async fn test_playlist_converts_every_track_and_tolerates_failures() {
+            // One strategy, so the second track fails on its only attempt
+            let downloader = FakeDownloader::new([
+                FakeOutcome::Succeed,
+                FakeOutcome::Fail("ERROR: [youtube] second_____: Video unavailable".to_string()),
+            ])
+            .with_playlist(three_track_playlist());
+            let harness = test_runner_with(downloader, |runner| {
+                runner.with_strategies(vec![Strategy::new("only", &[])])
+            });
+            let runner = &harness.runner;
+            let job_id = runner.start_conversion(PLAYLIST_URL.to_string(), playlist_options()).await.unwrap();
+
+            let response = wait_until_finished(runner, &job_id).await;
+            assert_eq!(response.status, JobStatus::Completed);
+            assert_eq!(response.message, "Converted 2 of 3 tracks");
+            let playlist = response.playlist.unwrap();
+            assert_eq!(playlist.title.as_deref(), Some("Road Trip"));
+            let statuses: Vec<JobStatus> = playlist.tracks.iter().map(|track| track.status).collect();
+            assert_eq!(statuses, [JobStatus::Completed, JobStatus::Failed, JobStatus::Completed]);
+            assert!(playlist.tracks[1].message.contains("unavailable"));
+
+            let archive = runner.get_playlist_archive(&job_id).await.unwrap();
+            let names: Vec<&str> = archive.tracks.iter().map(|track| track.name.as_str()).collect();
+            assert_eq!(names, ["01 - first.mp3", "03 - third.mp3"]);
+            assert!(archive.m3u().contains("03 - third.mp3"));
+
+            // Tracks are downloadable on their own as well
+            let third = &playlist.tracks[2].job_id;
+            assert_eq!(tokio::fs::read(runner.get_audio_file(third).await.unwrap().path).await.unwrap(), fixture_mp3());
+            assert!(runner.check_access(third, Some("alice")).await.is_ok());
+            assert!(matches!(runner.check_access(third, Some("bob")).await, Err(AccessError::Forbidden)));
+        }
+
+        #[tokio::test]
+        async fn test_playlist_is_trimmed_to_the_owners_quota() {
+            use crate::domain::services::quota::Quota;
+
+            let quotas = QuotaConfig {
+                default: Quota {
+                    conversions_per_day: Some(3),
+                    ..Quota::default()
+                },
+                ..QuotaConfig::default()
+            };
+            let harness = test_runner_with(FakeDownloader::default().with_playlist(three_track_playlist()), |runner| {
+                runner.with_quotas(quotas)
+            });
+            let runner = &harness.runner;
+            // One of three conversions already used leaves room for two tracks
+            let single = runner.start_conversion(URL.to_string(), single_video_options()).await.unwrap();
+            wait_until_finished(runner, &single).await;
+            let job_id = runner.start_conversion(PLAYLIST_URL.to_string(), playlist_options()).await.unwrap();
+
+            let response = wait_until_finished(runner, &job_id).await;
+            let titles: Vec<_> = response.playlist.unwrap().tracks.into_iter().filter_map(|track| track.title).collect();
+            assert_eq!(titles, ["first", "second"]);
+            assert_eq!(runner.usage("alice", "authenticated").await.unwrap().conversions.used, 3);
+
+            // With the quota used up the next playlist converts nothing
+            let job_id = runner.start_conversion(PLAYLIST_URL.to_string(), playlist_options()).await.unwrap();
+            let response = wait_until_finished(runner, &job_id).await;
+            assert_eq!(response.status, JobStatus::Failed);
+            assert!(response.message.starts_with("You have used all 3 of today's conversions"), "{}", response.message);
+        }
+
+        #[tokio::test]
+        async fn test_playlist_without_working_tracks_fails() {
+            let downloader = FakeDownloader::always_failing(BOT_CHECK, 3).with_playlist(three_track_playlist());
+            let harness = test_runner_with(downloader, |runner| {
+                runner.with_strategies(vec![Strategy::new("only", &[])])
+            });
+            let runner = &harness.runner;
+            let job_id = runner.start_conversion(PLAYLIST_URL.to_string(), playlist_options()).await.unwrap();
+
+            let response = wait_until_finished(runner, &job_id).await;
+            assert_eq!(response.status, JobStatus::Failed);
+            assert_eq!(response.message, "None of the playlist's videos could be converted.");
+            assert_eq!(
+                runner.get_playlist_archive(&job_id).await.unwrap_err().to_string(),
+                "Conversion not completed yet"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_playlist_that_cannot_be_listed_fails() {
+            let harness = test_runner(FakeDownloader::default());
+            let runner = &harness.runner;
+            let job_id = runner.start_conversion(PLAYLIST_URL.to_string(), playlist_options()).await.unwrap();
+
+            let response = wait_until_finished(runner, &job_id).await;
+            assert_eq!(response.status, JobStatus::Failed);
+            assert!(response.message.starts_with("This playlist could not be read."));
+            assert!(response.playlist.unwrap().tracks.is_empty());
+            assert!(harness.downloader.attempts().is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_cancel_playlist_cancels_its_tracks() {
+            let downloader = FakeDownloader::default()
+                .with_delay(Duration::from_secs(30))
+                .with_playlist(three_track_playlist());
+            let harness = test_runner(downloader);
+            let (runner, downloader) = (&harness.runner, &harness.downloader);
+            let job_id = runner.start_conversion(PLAYLIST_URL.to_string(), playlist_options()).await.unwrap();
+
+            for _ in 0..500 {
+                if !downloader.attempts().is_empty() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            runner.cancel(&job_id).await.unwrap();
+
+            let response = wait_until_finished(runner, &job_id).await;
+            assert_eq!(response.status, JobStatus::Cancelled);
+            let tracks = response.playlist.unwrap().tracks;
+            assert_eq!(tracks.len(), 3);
+            for track in &tracks {
+                assert_eq!(track.status, JobStatus::Cancelled, "{}", track.job_id);
+            }
+
+            for _ in 0..500 {
+                if runner.lock_running().is_empty() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            assert!(runner.lock_running().is_empty(), "worker should have moved on");
+            assert_eq!(downloader.attempts().len(), 1, "later tracks must not start");
+            let mut leftovers = tokio::fs::read_dir(harness.work_dir.path()).await.unwrap();
+            assert!(leftovers.next_entry().await.unwrap().is_none(), "job directories should be deleted");
+        }
+
+        #[tokio::test]
+        async fn test_identical_conversion_is_served_from_cache() {
+            let cache_dir = tempfile::tempdir().unwrap();
+            let cache = Arc::new(ConversionCache::open(cache_dir.path(), 1024 * 1024).await.unwrap());
+            let metadata = VideoMetadata {
+                title: Some("Rick Astley - Never Gonna Give You Up".to_string()),
+                ..VideoMetadata::default()
+            };
+            let harness = test_runner_with(FakeDownloader::default().with_metadata(metadata), |runner| {
+                runner.with_cache(cache.clone())
+            });
+            let (runner, downloader) = (&harness.runner, &harness.downloader);
+
+            let first = runner.start_conversion(URL.to_string(), ConversionOptions::default()).await.unwrap();
+            assert_eq!(wait_until_finished(runner, &first).await.status, JobStatus::Completed);
+            assert_eq!(cache.stats().entries, 1);
+
+            // Another link to the same video completes without a download
+            let second = runner
+                .start_conversion("https://youtu.be/dQw4w9WgXcQ".to_string(), ConversionOptions::default())
+                .await
+                .unwrap();
+            let response = runner.get_job_status(&second).await.unwrap();
+            assert_eq!(response.status, JobStatus::Completed);
+            assert_eq!(response.tags.and_then(|tags| tags.title).as_deref(), Some("Never Gonna Give You Up"));
+            assert_eq!(downloader.attempts().len(), 1);
+            assert_eq!(tokio::fs::read(runner.get_audio_file(&second).await.unwrap().path).await.unwrap(), fixture_mp3());
+
+            // Each job owns its copy, so expiring the first keeps the second intact
+            let first_dir = runner.store.get(&first).await.unwrap().unwrap().work_dir;
+            tokio::fs::remove_dir_all(first_dir).await.unwrap();
+            assert!(runner.get_audio_file(&second).await.is_ok());
+
+            // Other settings or the user's own tags mean a new conversion
+            let flac = ConversionOptions {
+                format: AudioFormat::Flac,
+                ..ConversionOptions::default()
+            };
+            let retagged = ConversionOptions {
+                tags: TrackTags {
+                    artist: Some("Somebody Else".to_string()),
+                    ..TrackTags::default()
+                },
+                ..ConversionOptions::default()
+            };
+            for options in [flac, retagged] {
+                let job_id = runner.start_conversion(URL.to_string(), options).await.unwrap();
+                assert_eq!(wait_until_finished(runner, &job_id).await.status, JobStatus::Completed);
+            }
+            assert_eq!(downloader.attempts().len(), 3);
+            assert_eq!(cache.stats().entries, 2);
+        }
     }
 }
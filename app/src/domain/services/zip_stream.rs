@@ -0,0 +1,476 @@
+use std::io;
+use std::path::PathBuf;
+
+use futures::Stream;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+
+/// Size of the chunks file contents are read and sent in.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Number of chunks buffered ahead of a slow client.
+const CHUNKS_IN_FLIGHT: usize = 4;
+
+/// General purpose flags: sizes follow the data (bit 3), names are UTF-8 (bit 11).
+const FLAGS: u16 = 0x0808;
+
+/// Version 2.0, the oldest that supports data descriptors.
+const VERSION: u16 = 20;
+
+/// Version 4.5, needed by entries with ZIP64 sizes or offsets.
+const VERSION_ZIP64: u16 = 45;
+
+/// MS-DOS date of every entry, 1980-01-01; the archive has no meaningful dates.
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Placeholder for a 32-bit size or offset kept in a ZIP64 field instead.
+const ZIP64_MARKER: u32 = u32::MAX;
+
+/// Placeholder for an entry count kept in the ZIP64 end record instead.
+const ZIP64_COUNT_MARKER: u16 = u16::MAX;
+
+/// Where the contents of a [`ZipEntry`] come from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZipSource {
+    File(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+/// One file of the archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipEntry {
+    /// Path inside the archive; must be unique.
+    pub name: String,
+    pub source: ZipSource,
+}
+
+/// Streams an uncompressed ZIP archive of `entries` without buffering it.
+///
+/// Audio is already compressed, so entries are only stored. Sizes and CRCs
+/// follow each entry in a data descriptor, which lets files be sent while
+/// they are read. Archives past 4 GiB, such as long lossless playlists, use
+/// ZIP64 fields where the 32-bit ones overflow. Dropping the stream stops
+/// reading. If a file cannot be read the stream ends with the error and the
+/// archive is incomplete.
+pub fn zip_stream(entries: Vec<ZipEntry>) -> impl Stream<Item = io::Result<Vec<u8>>> + Send + 'static {
+    zip_stream_with(entries, ZipWriter::default())
+}
+
+fn zip_stream_with(
+    entries: Vec<ZipEntry>,
+    writer: ZipWriter,
+) -> impl Stream<Item = io::Result<Vec<u8>>> + Send + 'static {
+    let (tx, rx) = mpsc::channel(CHUNKS_IN_FLIGHT);
+    tokio::spawn(async move {
+        if let Err(e) = write_archive(entries, writer, &tx).await {
+            // Nobody is listening if the client went away, which is fine
+            let _ = tx.send(Err(e)).await;
+        }
+    });
+
+    futures::stream::unfold(rx, |mut rx| async move {
+        let chunk = rx.recv().await?;
+        Some((chunk, rx))
+    })
+}
+
+async fn write_archive(
+    entries: Vec<ZipEntry>,
+    mut writer: ZipWriter,
+    tx: &mpsc::Sender<io::Result<Vec<u8>>>,
+) -> io::Result<()> {
+    let send = |chunk: Vec<u8>| async move {
+        tx.send(Ok(chunk))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client went away"))
+    };
+
+    for entry in entries {
+        let mut crc = crc32fast::Hasher::new();
+        let mut size = 0u64;
+        match entry.source {
+            ZipSource::Bytes(bytes) => {
+                send(writer.start_entry(&entry.name, bytes.len() as u64)?).await?;
+                crc.update(&bytes);
+                size = bytes.len() as u64;
+                send(bytes).await?;
+            }
+            ZipSource::File(path) => {
+                let mut file = tokio::fs::File::open(&path).await?;
+                let expected = file.metadata().await?.len();
+                send(writer.start_entry(&entry.name, expected)?).await?;
+                loop {
+                    let mut chunk = vec![0; CHUNK_SIZE];
+                    let read = file.read(&mut chunk).await?;
+                    if read == 0 {
+                        break;
+                    }
+                    chunk.truncate(read);
+                    crc.update(&chunk);
+                    size += read as u64;
+                    send(chunk).await?;
+                }
+            }
+        }
+
+        send(writer.finish_entry(crc.finalize(), size)?).await?;
+    }
+    send(writer.finish()?).await
+}
+
+/// The entry whose contents are being sent.
+#[derive(Debug)]
+struct OpenEntry {
+    name: String,
+    /// Where its local header starts.
+    offset: u64,
+    /// Whether its sizes are 64-bit in the local header and data descriptor.
+    zip64: bool,
+}
+
+/// Produces the headers of a stored ZIP archive around contents sent by the caller.
+#[derive(Debug)]
+struct ZipWriter {
+    /// Bytes of the archive produced so far.
+    offset: u64,
+    central_directory: Vec<u8>,
+    entries: u64,
+    current: Option<OpenEntry>,
+    /// Sizes and offsets from which ZIP64 fields are used; lowered in tests.
+    zip64_from: u64,
+}
+
+impl Default for ZipWriter {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            central_directory: Vec::new(),
+            entries: 0,
+            current: None,
+            zip64_from: u64::from(ZIP64_MARKER),
+        }
+    }
+}
+
+impl ZipWriter {
+    /// The local header of a new entry of about `expected_size` bytes, to be
+    /// followed by its contents.
+    fn start_entry(&mut self, name: &str, expected_size: u64) -> io::Result<Vec<u8>> {
+        let name_len = u16::try_from(name.len()).map_err(|_| too_large("file name"))?;
+        // The data descriptor's layout is fixed by the local header, so an
+        // entry that might not fit 32 bits is made ZIP64 up front
+        let zip64 = expected_size >= self.zip64_from;
+        self.current = Some(OpenEntry {
+            name: name.to_string(),
+            offset: self.offset,
+            zip64,
+        });
+
+        let mut header = Vec::with_capacity(50 + name.len());
+        put_u32(&mut header, 0x0403_4b50);
+        put_u16(&mut header, if zip64 { VERSION_ZIP64 } else { VERSION });
+        put_u16(&mut header, FLAGS);
+        put_u16(&mut header, 0); // stored
+        put_u16(&mut header, 0); // time
+        put_u16(&mut header, DOS_DATE);
+        // CRC and sizes are in the data descriptor
+        put_u32(&mut header, 0);
+        let size = if zip64 { ZIP64_MARKER } else { 0 };
+        put_u32(&mut header, size);
+        put_u32(&mut header, size);
+        put_u16(&mut header, name_len);
+        put_u16(&mut header, if zip64 { 20 } else { 0 }); // extra field
+        header.extend_from_slice(name.as_bytes());
+        if zip64 {
+            put_u16(&mut header, 0x0001);
+            put_u16(&mut header, 16);
+            put_u64(&mut header, 0);
+            put_u64(&mut header, 0);
+        }
+        Ok(self.emit(header))
+    }
+
+    /// The data descriptor closing the current entry after `size` bytes.
+    fn finish_entry(&mut self, crc: u32, size: u64) -> io::Result<Vec<u8>> {
+        let entry = self.current.take().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no ZIP entry was started")
+        })?;
+        if !entry.zip64 && size >= u64::from(ZIP64_MARKER) {
+            // Grew past its expected size while being read
+            return Err(too_large(&entry.name));
+        }
+        self.entries += 1;
+
+        // Sizes and the offset that do not fit go to the ZIP64 extra field
+        let mut extra = Vec::new();
+        let size_field = if size >= self.zip64_from {
+            put_u64(&mut extra, size);
+            put_u64(&mut extra, size);
+            ZIP64_MARKER
+        } else {
+            size as u32
+        };
+        let offset_field = if entry.offset >= self.zip64_from {
+            put_u64(&mut extra, entry.offset);
+            ZIP64_MARKER
+        } else {
+            entry.offset as u32
+        };
+
+        let cd = &mut self.central_directory;
+        put_u32(cd, 0x0201_4b50);
+        let version = if extra.is_empty() && !entry.zip64 { VERSION } else { VERSION_ZIP64 };
+        put_u16(cd, version); // made by
+        put_u16(cd, version); // needed
+        put_u16(cd, FLAGS);
+        put_u16(cd, 0); // stored
+        put_u16(cd, 0); // time
+        put_u16(cd, DOS_DATE);
+        put_u32(cd, crc);
+        put_u32(cd, size_field);
+        put_u32(cd, size_field);
+        put_u16(cd, entry.name.len() as u16);
+        put_u16(cd, if extra.is_empty() { 0 } else { extra.len() as u16 + 4 });
+        put_u16(cd, 0); // comment
+        put_u16(cd, 0); // disk
+        put_u16(cd, 0); // internal attributes
+        put_u32(cd, 0); // external attributes
+        put_u32(cd, offset_field);
+        cd.extend_from_slice(entry.name.as_bytes());
+        if !extra.is_empty() {
+            put_u16(cd, 0x0001);
+            put_u16(cd, extra.len() as u16);
+            cd.extend_from_slice(&extra);
+        }
+
+        let mut descriptor = Vec::with_capacity(24);
+        put_u32(&mut descriptor, 0x0807_4b50);
+        put_u32(&mut descriptor, crc);
+        if entry.zip64 {
+            put_u64(&mut descriptor, size);
+            put_u64(&mut descriptor, size);
+        } else {
+            put_u32(&mut descriptor, size as u32);
+            put_u32(&mut descriptor, size as u32);
+        }
+        self.offset += size;
+        Ok(self.emit(descriptor))
+    }
+
+    /// The central directory and its end records, closing the archive.
+    fn finish(&mut self) -> io::Result<Vec<u8>> {
+        let cd_offset = self.offset;
+        let cd_size = self.central_directory.len() as u64;
+        let zip64 = self.entries >= u64::from(ZIP64_COUNT_MARKER)
+            || cd_offset >= self.zip64_from
+            || cd_size >= self.zip64_from;
+
+        let mut end = std::mem::take(&mut self.central_directory);
+        if zip64 {
+            let record_offset = cd_offset + cd_size;
+            put_u32(&mut end, 0x0606_4b50);
+            put_u64(&mut end, 44); // size of the rest of the record
+            put_u16(&mut end, VERSION_ZIP64); // made by
+            put_u16(&mut end, VERSION_ZIP64); // needed
+            put_u32(&mut end, 0); // this disk
+            put_u32(&mut end, 0); // disk with the central directory
+            put_u64(&mut end, self.entries);
+            put_u64(&mut end, self.entries);
+            put_u64(&mut end, cd_size);
+            put_u64(&mut end, cd_offset);
+
+            put_u32(&mut end, 0x0706_4b50);
+            put_u32(&mut end, 0); // disk with the ZIP64 end record
+            put_u64(&mut end, record_offset);
+            put_u32(&mut end, 1); // disks
+        }
+
+        let entries = if zip64 { ZIP64_COUNT_MARKER } else { self.entries as u16 };
+        put_u32(&mut end, 0x0605_4b50);
+        put_u16(&mut end, 0); // this disk
+        put_u16(&mut end, 0); // disk with the central directory
+        put_u16(&mut end, entries);
+        put_u16(&mut end, entries);
+        put_u32(&mut end, if zip64 { ZIP64_MARKER } else { cd_size as u32 });
+        put_u32(&mut end, if zip64 { ZIP64_MARKER } else { cd_offset as u32 });
+        put_u16(&mut end, 0); // comment
+        Ok(self.emit(end))
+    }
+
+    fn emit(&mut self, bytes: Vec<u8>) -> Vec<u8> {
+        self.offset += bytes.len() as u64;
+        bytes
+    }
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(buffer: &mut Vec<u8>, value: u64) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn too_large(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("{what} is too large for a ZIP archive"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn u16_at(bytes: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]])
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    async fn collect(entries: Vec<ZipEntry>) -> io::Result<Vec<u8>> {
+        let mut archive = Vec::new();
+        let mut stream = std::pin::pin!(zip_stream(entries));
+        while let Some(chunk) = stream.next().await {
+            archive.extend(chunk?);
+        }
+        Ok(archive)
+    }
+
+    /// Reads every entry back through the central directory.
+    fn read_entries(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(archive, end), 0x0605_4b50);
+        let count = u16_at(archive, end + 10);
+        let mut at = u32_at(archive, end + 16) as usize;
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            assert_eq!(u32_at(archive, at), 0x0201_4b50);
+            let crc = u32_at(archive, at + 16);
+            let size = u32_at(archive, at + 20) as usize;
+            let name_len = u16_at(archive, at + 28) as usize;
+            let offset = u32_at(archive, at + 42) as usize;
+            let name = String::from_utf8(archive[at + 46..at + 46 + name_len].to_vec()).unwrap();
+
+            assert_eq!(u32_at(archive, offset), 0x0403_4b50);
+            let data_at = offset + 30 + u16_at(archive, offset + 26) as usize;
+            let data = archive[data_at..data_at + size].to_vec();
+            assert_eq!(crc32fast::hash(&data), crc);
+            assert_eq!(u32_at(archive, data_at + size), 0x0807_4b50);
+            assert_eq!(u32_at(archive, data_at + size + 4), crc);
+
+            entries.push((name, data));
+            at += 46 + name_len;
+        }
+        entries
+    }
+
+    #[tokio::test]
+    async fn test_archive_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("track.mp3");
+        let big = (0..CHUNK_SIZE * 2 + 17).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        tokio::fs::write(&path, &big).await.unwrap();
+
+        let archive = collect(vec![
+            ZipEntry {
+                name: "01 - Ünïcode.mp3".to_string(),
+                source: ZipSource::File(path),
+            },
+            ZipEntry {
+                name: "playlist.m3u".to_string(),
+                source: ZipSource::Bytes(b"#EXTM3U\n".to_vec()),
+            },
+        ])
+        .await
+        .unwrap();
+
+        let entries = read_entries(&archive);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], ("01 - Ünïcode.mp3".to_string(), big));
+        assert_eq!(entries[1], ("playlist.m3u".to_string(), b"#EXTM3U\n".to_vec()));
+    }
+
+    fn u64_at(bytes: &[u8], at: usize) -> u64 {
+        u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_zip64_fields_replace_overflowing_ones() {
+        let entries = vec![
+            ZipEntry {
+                name: "01 - first.flac".to_string(),
+                source: ZipSource::Bytes(vec![1; 300]),
+            },
+            ZipEntry {
+                name: "02 - second.flac".to_string(),
+                source: ZipSource::Bytes(vec![2; 50]),
+            },
+        ];
+        // As if every size and offset from 100 bytes on overflowed 32 bits
+        let writer = ZipWriter {
+            zip64_from: 100,
+            ..ZipWriter::default()
+        };
+        let mut archive = Vec::new();
+        let mut stream = std::pin::pin!(zip_stream_with(entries, writer));
+        while let Some(chunk) = stream.next().await {
+            archive.extend(chunk.unwrap());
+        }
+
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(&archive, end), 0x0605_4b50);
+        assert_eq!(u16_at(&archive, end + 10), u16::MAX);
+        assert_eq!(u32_at(&archive, end + 16), u32::MAX);
+        let locator = end - 20;
+        assert_eq!(u32_at(&archive, locator), 0x0706_4b50);
+        let record = u64_at(&archive, locator + 8) as usize;
+        assert_eq!(u32_at(&archive, record), 0x0606_4b50);
+        assert_eq!(u64_at(&archive, record + 32), 2);
+        let mut at = u64_at(&archive, record + 48) as usize;
+
+        // The first entry is large, the second starts late
+        let mut found = Vec::new();
+        for _ in 0..2 {
+            assert_eq!(u32_at(&archive, at), 0x0201_4b50);
+            let name_len = u16_at(&archive, at + 28) as usize;
+            let extra_len = u16_at(&archive, at + 30) as usize;
+            let extra = &archive[at + 46 + name_len..at + 46 + name_len + extra_len];
+            assert_eq!(u16_at(extra, 0), 0x0001);
+            let (size, offset) = match (u32_at(&archive, at + 24), u32_at(&archive, at + 42)) {
+                (u32::MAX, u32::MAX) => (u64_at(extra, 4), u64_at(extra, 20)),
+                (u32::MAX, offset) => (u64_at(extra, 4), u64::from(offset)),
+                (size, u32::MAX) => (u64::from(size), u64_at(extra, 4)),
+                (size, offset) => (u64::from(size), u64::from(offset)),
+            };
+            let offset = offset as usize;
+            assert_eq!(u32_at(&archive, offset), 0x0403_4b50);
+            let data_at = offset + 30 + u16_at(&archive, offset + 26) as usize + u16_at(&archive, offset + 28) as usize;
+            found.push(archive[data_at..data_at + size as usize].to_vec());
+            at += 46 + name_len + extra_len;
+        }
+        assert_eq!(found, [vec![1; 300], vec![2; 50]]);
+    }
+
+    #[tokio::test]
+    async fn test_empty_archive() {
+        let archive = collect(Vec::new()).await.unwrap();
+        assert_eq!(archive.len(), 22);
+        assert!(read_entries(&archive).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_missing_file_ends_stream_with_error() {
+        let result = collect(vec![ZipEntry {
+            name: "gone.mp3".to_string(),
+            source: ZipSource::File(PathBuf::from("/nonexistent/gone.mp3")),
+        }])
+        .await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,39 @@
+use app::domain::entities::auth::VerifiedUser;
+use app::domain::services::video_converter::server::check_access;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+/// Checks that the signed-in user may see the job, answering with 404 or
+/// 403 otherwise.
+pub async fn authorize(job_id: &str, user: Option<&VerifiedUser>) -> Result<(), Response> {
+    let user_id = user.map(|user| user.user_id.as_str());
+    check_access(job_id, user_id).await.map_err(|e| {
+        (
+            e.status_code(),
+            [("content-type", "text/plain")],
+            format!("Error: {e}"),
+        )
+            .into_response()
+    })
+}
+
+/// Answers that a job or its files do not exist (anymore).
+pub fn not_found(e: impl std::fmt::Display) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        [("content-type", "text/plain")],
+        format!("Error: {e}"),
+    )
+        .into_response()
+}
+
+/// Checks that the signed-in user's access token has `role`. If not,
+/// returns the 401 or 403 to answer with instead.
+pub fn missing_role(user: Option<&VerifiedUser>, role: &str) -> Option<Response> {
+    let (status, message) = match user {
+        None => (StatusCode::UNAUTHORIZED, "Error: Please sign in"),
+        Some(user) if user.role != role => (StatusCode::FORBIDDEN, "Error: Only operators may see this"),
+        Some(_) => return None,
+    };
+    Some((status, [("content-type", "text/plain")], message).into_response())
+}
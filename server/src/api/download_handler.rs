@@ -1,26 +1,103 @@
-use app::domain::services::video_converter::server::get_mp3_file;
-use axum::extract::Path;
-use axum::response::IntoResponse;
+use std::io::SeekFrom;
+use std::path::Path as FilePath;
 
-pub async fn download_handler(Path(id): Path<String>) -> impl IntoResponse {
-    match get_mp3_file(&id).await {
-        Ok(file_content) => {
-            let filename = format!("attachment; filename=\"{id}.mp3\"");
-            (
-                axum::http::StatusCode::OK,
-                [
-                    ("content-type", "audio/mpeg"),
-                    ("content-disposition", filename.as_str()),
-                ],
-                file_content,
-            )
-                .into_response()
+use app::domain::entities::auth::VerifiedUser;
+use app::domain::entities::file_name::{DownloadQuery, FileNameTemplate};
+use app::domain::services::file_response::{content_disposition, respond, FileResponse, FileVersion};
+use app::domain::services::rate_limit::{ClientIp, Endpoint};
+use app::domain::services::video_converter::server::get_audio_file;
+use axum::body::Body;
+use axum::extract::{Path, Query};
+use axum::http::{header, HeaderMap, HeaderName, Method, StatusCode};
+use axum::response::{AppendHeaders, IntoResponse, Response};
+use axum::Extension;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+use crate::api::access::{authorize, not_found};
+use crate::api::rate_limit::over_limit;
+
+/// Serves a job's audio file for `GET` and `HEAD`, streamed from disk, with
+/// byte ranges and `ETag`/`Last-Modified` validators so players can seek and
+/// browsers can resume or revalidate downloads.
+///
+/// The file is named after the video, or by the template in `?name=`.
+pub async fn download_handler(
+    method: Method,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(query): Query<DownloadQuery>,
+    user: Option<Extension<VerifiedUser>>,
+    ip: Option<Extension<ClientIp>>,
+) -> Response {
+    if let Some(response) = over_limit(Endpoint::Download, user.as_deref(), ip.as_deref()) {
+        return response;
+    }
+    let template = match query.name.as_deref().map(FileNameTemplate::parse) {
+        None => FileNameTemplate::default(),
+        Some(Ok(template)) => template,
+        Some(Err(e)) => {
+            return (StatusCode::BAD_REQUEST, [("content-type", "text/plain")], e.to_string()).into_response();
         }
-        Err(e) => (
-            axum::http::StatusCode::NOT_FOUND,
-            [("content-type", "text/plain")],
-            format!("Error: {e}"),
-        )
-            .into_response(),
+    };
+    if let Err(response) = authorize(&id, user.as_deref()).await {
+        return response;
+    }
+    let file = match get_audio_file(&id).await {
+        Ok(file) => file,
+        Err(e) => return not_found(e),
+    };
+    let content_headers = [
+        (header::CONTENT_TYPE, file.format.mime_type().to_string()),
+        (header::CONTENT_DISPOSITION, content_disposition(&file.file_name(&template))),
+    ];
+    match serve_file(&method, &headers, &file.path, content_headers).await {
+        Ok(response) => response,
+        // Expired and removed since the job was read
+        Err(e) => not_found(e),
     }
 }
+
+async fn serve_file(
+    method: &Method,
+    request_headers: &HeaderMap,
+    path: &FilePath,
+    content_headers: [(HeaderName, String); 2],
+) -> std::io::Result<Response> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let version = FileVersion::from_metadata(&file.metadata().await?);
+
+    let mut headers = vec![
+        (header::ETAG, version.etag()),
+        (header::ACCEPT_RANGES, "bytes".to_string()),
+    ];
+    if let Some(last_modified) = version.last_modified() {
+        headers.push((header::LAST_MODIFIED, last_modified));
+    }
+
+    let (status, start, end) = match respond(method, request_headers, &version) {
+        FileResponse::NotModified => {
+            return Ok((StatusCode::NOT_MODIFIED, AppendHeaders(headers)).into_response());
+        }
+        FileResponse::Unsatisfiable => {
+            headers.push((header::CONTENT_RANGE, format!("bytes */{}", version.len)));
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, AppendHeaders(headers)).into_response());
+        }
+        FileResponse::Full => (StatusCode::OK, 0, version.len.saturating_sub(1)),
+        FileResponse::Partial { start, end } => {
+            headers.push((header::CONTENT_RANGE, format!("bytes {start}-{end}/{}", version.len)));
+            (StatusCode::PARTIAL_CONTENT, start, end)
+        }
+    };
+    let len = if version.len == 0 { 0 } else { end - start + 1 };
+    headers.extend(content_headers);
+    headers.push((header::CONTENT_LENGTH, len.to_string()));
+
+    let body = if method == Method::HEAD {
+        Body::empty()
+    } else {
+        file.seek(SeekFrom::Start(start)).await?;
+        Body::from_stream(ReaderStream::new(file.take(len)))
+    };
+    Ok((status, AppendHeaders(headers), body).into_response())
+}
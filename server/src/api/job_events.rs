@@ -0,0 +1,32 @@
+use std::convert::Infallible;
+
+use app::domain::entities::auth::VerifiedUser;
+use app::domain::services::video_converter::server::job_status_stream;
+use axum::extract::Path;
+use axum::Extension;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use futures::StreamExt;
+
+use crate::api::access::authorize;
+
+/// Server-sent events with the job's `ConvertResponse` as JSON, sent as
+/// `status` events whenever the job changes. The stream ends once the job
+/// has finished.
+pub async fn job_events_handler(
+    Path(id): Path<String>,
+    user: Option<Extension<VerifiedUser>>,
+) -> Response {
+    if let Err(response) = authorize(&id, user.as_deref()).await {
+        return response;
+    }
+    let events = job_status_stream(id).map(|response| {
+        let event = Event::default().event("status");
+        Ok::<_, Infallible>(match event.json_data(&response) {
+            Ok(event) => event,
+            Err(e) => Event::default().event("error").data(e.to_string()),
+        })
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default()).into_response()
+}
@@ -1 +1,6 @@
+pub mod access;
 pub mod download_handler;
+pub mod job_events;
+pub mod playlist_handler;
+pub mod rate_limit;
+pub mod strategy_stats_handler;
@@ -0,0 +1,71 @@
+use app::domain::entities::auth::VerifiedUser;
+use app::domain::services::file_response::content_disposition;
+use app::domain::services::rate_limit::{ClientIp, Endpoint};
+use app::domain::services::video_converter::server::get_playlist_archive;
+use app::domain::services::zip_stream::zip_stream;
+use axum::body::Body;
+use axum::extract::Path;
+use axum::Extension;
+use axum::response::{IntoResponse, Response};
+
+use crate::api::access::{authorize, not_found};
+use crate::api::rate_limit::over_limit;
+
+/// Streams the converted tracks of a playlist job as a ZIP, with an M3U
+/// listing them in playlist order.
+pub async fn playlist_zip_handler(
+    Path(id): Path<String>,
+    user: Option<Extension<VerifiedUser>>,
+    ip: Option<Extension<ClientIp>>,
+) -> Response {
+    if let Some(response) = over_limit(Endpoint::Download, user.as_deref(), ip.as_deref()) {
+        return response;
+    }
+    if let Err(response) = authorize(&id, user.as_deref()).await {
+        return response;
+    }
+    match get_playlist_archive(&id).await {
+        Ok(archive) => {
+            let filename = content_disposition(&format!("{}.zip", archive.file_stem()));
+            (
+                axum::http::StatusCode::OK,
+                [
+                    ("content-type", "application/zip"),
+                    ("content-disposition", filename.as_str()),
+                ],
+                Body::from_stream(zip_stream(archive.zip_entries())),
+            )
+                .into_response()
+        }
+        Err(e) => not_found(e),
+    }
+}
+
+/// The M3U of a playlist job, naming the tracks as they appear in its ZIP.
+pub async fn playlist_m3u_handler(
+    Path(id): Path<String>,
+    user: Option<Extension<VerifiedUser>>,
+    ip: Option<Extension<ClientIp>>,
+) -> Response {
+    if let Some(response) = over_limit(Endpoint::Download, user.as_deref(), ip.as_deref()) {
+        return response;
+    }
+    if let Err(response) = authorize(&id, user.as_deref()).await {
+        return response;
+    }
+    match get_playlist_archive(&id).await {
+        Ok(archive) => {
+            let filename = content_disposition(&format!("{}.m3u", archive.file_stem()));
+            (
+                axum::http::StatusCode::OK,
+                [
+                    ("content-type", "audio/x-mpegurl"),
+                    ("content-disposition", filename.as_str()),
+                ],
+                archive.m3u(),
+            )
+                .into_response()
+        }
+        Err(e) => not_found(e),
+    }
+}
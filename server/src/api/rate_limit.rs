@@ -0,0 +1,24 @@
+use app::domain::entities::auth::VerifiedUser;
+use app::domain::services::rate_limit::{rate_limiter, ClientIp, Endpoint};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+
+/// Counts a request against the sender's limits for `endpoint`. Once they
+/// are used up, returns the 429 to answer with instead, carrying
+/// `Retry-After` and the limit hit as JSON.
+pub fn over_limit(
+    endpoint: Endpoint,
+    user: Option<&VerifiedUser>,
+    ip: Option<&ClientIp>,
+) -> Option<Response> {
+    let limited = rate_limiter().check(endpoint, user, ip.map(|ip| ip.0)).err()?;
+    Some(
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, limited.retry_after_secs.to_string())],
+            Json(limited),
+        )
+            .into_response(),
+    )
+}
@@ -0,0 +1,18 @@
+use app::domain::entities::auth::VerifiedUser;
+use app::domain::services::video_converter::server::strategy_report;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+
+use crate::api::access::missing_role;
+
+/// Role claim an access token needs to read the statistics.
+const OPERATOR_ROLE: &str = "operator";
+
+/// Success rates, latencies and skipped strategies, in the order the next
+/// job would attempt them, for operators only.
+pub async fn strategy_stats_handler(user: Option<Extension<VerifiedUser>>) -> Response {
+    if let Some(response) = missing_role(user.as_deref(), OPERATOR_ROLE) {
+        return response;
+    }
+    Json(strategy_report()).into_response()
+}
@@ -0,0 +1,31 @@
+use std::sync::Arc;
+
+use app::domain::services::jwt::JwtVerifier;
+use app::domain::services::session::access_token;
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use leptos::logging::log;
+
+/// Verifies the access token of every request, from the `Authorization:
+/// Bearer` header or the session cookie, and stores who sent it as a
+/// [`VerifiedUser`](app::domain::entities::auth::VerifiedUser) in the request's extensions.
+///
+/// Requests without a valid token pass through anonymously; pages render
+/// for everyone and the server functions and routes that need a user
+/// refuse them. Without a verifier every request is anonymous.
+pub async fn authenticate(
+    State(verifier): State<Option<Arc<JwtVerifier>>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if let (Some(verifier), Some(token)) = (&verifier, access_token(request.headers())) {
+        match verifier.verify(&token) {
+            Ok(user) => {
+                request.extensions_mut().insert(user);
+            }
+            Err(e) => log!("rejected access token for {}: {}", request.uri().path(), e),
+        }
+    }
+    next.run(request).await
+}
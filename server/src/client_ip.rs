@@ -0,0 +1,20 @@
+use std::net::SocketAddr;
+
+use app::domain::services::rate_limit::{client_ip, ClientIp};
+use axum::extract::{ConnectInfo, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+
+/// Stores the address each request came from as a [`ClientIp`] in its
+/// extensions, for rate limiting. `behind_proxy` trusts the last
+/// `X-Forwarded-For` hop instead of the connecting peer.
+pub async fn resolve_client_ip(
+    State(behind_proxy): State<bool>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let ip = client_ip(request.headers(), peer.ip(), behind_proxy);
+    request.extensions_mut().insert(ClientIp(ip));
+    next.run(request).await
+}
@@ -1,16 +1,214 @@
 #![recursion_limit = "256"]
 
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use app::domain::services::conversion_cache::{ConversionCache, DEFAULT_MAX_BYTES};
+use app::domain::services::job_store::{JobStore, MemoryJobStore, SqliteJobStore};
+use app::domain::services::jwt::JwtVerifier;
+use app::domain::services::quota::QuotaConfig;
+use app::domain::services::rate_limit::{install_rate_limiter, RateLimitConfig, RateLimiter};
+use app::domain::services::reaper::{spawn_reaper, ReaperConfig};
+use app::domain::services::strategy_config;
+use app::domain::services::strategy_stats::{StrategyStats, DEFAULT_BENCH_AFTER, DEFAULT_BENCH_FOR};
+use app::domain::services::video_converter::server::{
+    install_runner, JobRunner, RecoveryPolicy, DEFAULT_WORK_DIR,
+};
 use app::*;
-use axum::{routing::get, Router};
+use axum::{middleware, routing::get, Router};
 use leptos::logging::log;
 use leptos::prelude::*;
 use leptos_axum::{generate_route_list, LeptosRoutes};
 
-use crate::api::download_handler;
+use crate::api::{download_handler, job_events, playlist_handler, strategy_stats_handler};
 mod api;
+mod auth;
+mod client_ip;
+
+/// How often a configured strategy file is checked for changes.
+const DEFAULT_STRATEGIES_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Builds the job runner from the environment and recovers jobs left over
+/// from the previous run.
+///
+/// - `YTMP3_DATABASE_PATH`: SQLite file to persist jobs in; jobs are kept in
+///   memory when unset.
+/// - `YTMP3_RECOVERY`: `fail` (default) or `requeue` for jobs that were
+///   running when the server stopped.
+/// - `YTMP3_WORKERS`: number of conversions that run at the same time.
+/// - `YTMP3_MAX_QUEUE`: number of jobs that may wait before new requests
+///   are turned away as "server busy".
+/// - `YTMP3_MAX_PLAYLIST_LEN`: number of videos converted from one playlist.
+/// - `YTMP3_CACHE_DIR`: directory of the conversion cache.
+/// - `YTMP3_CACHE_MAX_MB`: size of the conversion cache; `0` turns it off.
+/// - `YTMP3_STRATEGIES`: `.toml` or `.json` file of download strategies,
+///   see `config/strategies.toml`; the built-in ones are used when unset.
+/// - `YTMP3_STRATEGIES_RELOAD_SECS`: how often the strategy file is checked
+///   for changes (default 10).
+/// - `YTMP3_STRATEGY_BENCH_AFTER`: bot-detection failures in a row after
+///   which a strategy is skipped (default 3).
+/// - `YTMP3_STRATEGY_BENCH_SECS`: how long such a strategy is skipped
+///   (default 600).
+/// - `YTMP3_QUOTAS`: `.toml` or `.json` file of per-user quotas by role,
+///   see `config/quotas.toml`; the quotas in that file are used when unset.
+async fn init_job_runner() {
+    let store: Arc<dyn JobStore> = match std::env::var("YTMP3_DATABASE_PATH") {
+        Ok(path) => {
+            log!("persisting jobs to {}", path);
+            Arc::new(SqliteJobStore::open(&path).expect("failed to open job database"))
+        }
+        Err(_) => Arc::new(MemoryJobStore::default()),
+    };
+    let policy = std::env::var("YTMP3_RECOVERY")
+        .ok()
+        .map(|value| value.parse::<RecoveryPolicy>().expect("invalid YTMP3_RECOVERY"))
+        .unwrap_or_default();
+
+    let mut runner = JobRunner::default().with_store(store);
+    if let Some(workers) = env_usize("YTMP3_WORKERS") {
+        runner = runner.with_workers(workers);
+    }
+    if let Some(max_queue_len) = env_usize("YTMP3_MAX_QUEUE") {
+        runner = runner.with_max_queue_len(max_queue_len);
+    }
+    if let Some(max_playlist_len) = env_usize("YTMP3_MAX_PLAYLIST_LEN") {
+        runner = runner.with_max_playlist_len(max_playlist_len);
+    }
+    if let Some(cache) = open_cache().await {
+        runner = runner.with_cache(Arc::new(cache));
+    }
+    runner = runner.with_strategy_stats(StrategyStats::new(
+        env_usize("YTMP3_STRATEGY_BENCH_AFTER").map_or(DEFAULT_BENCH_AFTER, |n| n as u32),
+        env_secs("YTMP3_STRATEGY_BENCH_SECS").unwrap_or(DEFAULT_BENCH_FOR),
+    ));
+    if let Ok(path) = std::env::var("YTMP3_STRATEGIES") {
+        let path = PathBuf::from(path);
+        let strategies = strategy_config::load(&path)
+            .await
+            .unwrap_or_else(|e| panic!("invalid YTMP3_STRATEGIES: {e}"));
+        log!("loaded {} download strategies from {}", strategies.len(), path.display());
+        runner = runner.with_strategies(strategies);
+        let interval = env_secs("YTMP3_STRATEGIES_RELOAD_SECS")
+            .unwrap_or(DEFAULT_STRATEGIES_RELOAD_INTERVAL);
+        strategy_config::spawn_reloader(path, runner.strategies().clone(), interval);
+    }
+    if let Ok(path) = std::env::var("YTMP3_QUOTAS") {
+        let quotas = QuotaConfig::load(Path::new(&path))
+            .await
+            .unwrap_or_else(|e| panic!("invalid YTMP3_QUOTAS: {e}"));
+        runner = runner.with_quotas(quotas);
+    }
+    if let Err(e) = runner.recover(policy).await {
+        log!("failed to recover jobs: {}", e);
+    }
+    spawn_reaper(runner.clone(), reaper_config());
+    install_runner(runner).expect("job runner already installed");
+}
+
+/// Opens the conversion cache configured in the environment, if enabled.
+async fn open_cache() -> Option<ConversionCache> {
+    let max_bytes = env_usize("YTMP3_CACHE_MAX_MB")
+        .map_or(DEFAULT_MAX_BYTES, |mb| mb as u64 * 1024 * 1024);
+    if max_bytes == 0 {
+        return None;
+    }
+    let dir = std::env::var("YTMP3_CACHE_DIR")
+        .map_or_else(|_| Path::new(DEFAULT_WORK_DIR).join("cache"), PathBuf::from);
+    match ConversionCache::open(&dir, max_bytes).await {
+        Ok(cache) => {
+            log!("caching up to {} MiB of conversions in {}", max_bytes / 1024 / 1024, dir.display());
+            Some(cache)
+        }
+        Err(e) => {
+            log!("conversion cache disabled, {} is not usable: {}", dir.display(), e);
+            None
+        }
+    }
+}
+
+/// Reads reaper TTLs from the environment, in seconds:
+/// `YTMP3_COMPLETED_TTL_SECS`, `YTMP3_FAILED_TTL_SECS`,
+/// `YTMP3_ABANDONED_TTL_SECS`, `YTMP3_FORGET_AFTER_SECS` and
+/// `YTMP3_REAP_INTERVAL_SECS`.
+fn reaper_config() -> ReaperConfig {
+    let defaults = ReaperConfig::default();
+    ReaperConfig {
+        completed_ttl: env_secs("YTMP3_COMPLETED_TTL_SECS").unwrap_or(defaults.completed_ttl),
+        failed_ttl: env_secs("YTMP3_FAILED_TTL_SECS").unwrap_or(defaults.failed_ttl),
+        abandoned_ttl: env_secs("YTMP3_ABANDONED_TTL_SECS").unwrap_or(defaults.abandoned_ttl),
+        forget_after: env_secs("YTMP3_FORGET_AFTER_SECS").unwrap_or(defaults.forget_after),
+        interval: env_secs("YTMP3_REAP_INTERVAL_SECS").unwrap_or(defaults.interval),
+    }
+}
+
+/// Installs the rate limits of the convert and download endpoints.
+///
+/// - `YTMP3_RATE_LIMITS`: `.toml` or `.json` file of limits per IP address,
+///   account and role, see `config/rate_limits.toml`; the limits in that
+///   file are used when unset.
+async fn init_rate_limiter() {
+    let config = match std::env::var("YTMP3_RATE_LIMITS") {
+        Ok(path) => RateLimitConfig::load(Path::new(&path))
+            .await
+            .unwrap_or_else(|e| panic!("invalid YTMP3_RATE_LIMITS: {e}")),
+        Err(_) => RateLimitConfig::default(),
+    };
+    install_rate_limiter(RateLimiter::new(config)).expect("rate limiter already installed");
+}
+
+/// Builds the verifier for access tokens from the environment.
+///
+/// - `YTMP3_JWT_SECRET`: the Supabase project's JWT secret, for
+///   HMAC-signed tokens.
+/// - `YTMP3_JWKS_PATH`: a JSON Web Key Set file with the project's signing
+///   keys, used instead of the secret when set.
+/// - `YTMP3_JWT_AUDIENCE`: audience tokens must be issued for (default
+///   `authenticated`).
+///
+/// Without either key nobody can sign in.
+fn jwt_verifier() -> Option<Arc<JwtVerifier>> {
+    let verifier = if let Ok(path) = std::env::var("YTMP3_JWKS_PATH") {
+        let json = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("cannot read YTMP3_JWKS_PATH {path}: {e}"));
+        JwtVerifier::from_jwks(&json).unwrap_or_else(|e| panic!("invalid YTMP3_JWKS_PATH: {e}"))
+    } else if let Ok(secret) = std::env::var("YTMP3_JWT_SECRET") {
+        JwtVerifier::from_secret(secret.as_bytes())
+    } else {
+        log!("neither YTMP3_JWKS_PATH nor YTMP3_JWT_SECRET is set, every request is anonymous");
+        return None;
+    };
+    let verifier = match std::env::var("YTMP3_JWT_AUDIENCE") {
+        Ok(audience) => verifier.with_audience(audience),
+        Err(_) => verifier,
+    };
+    Some(Arc::new(verifier))
+}
+
+/// Whether a flag is set to `1` or `true`. `YTMP3_BEHIND_PROXY` makes rate
+/// limits use the client address a reverse proxy adds to `X-Forwarded-For`.
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|value| matches!(value.as_str(), "1" | "true"))
+}
+
+fn env_secs(name: &str) -> Option<Duration> {
+    env_usize(name).map(|secs| Duration::from_secs(secs as u64))
+}
+
+fn env_usize(name: &str) -> Option<usize> {
+    let value = std::env::var(name).ok()?;
+    let number = value
+        .parse()
+        .unwrap_or_else(|_| panic!("{name} must be a non-negative number, got `{value}`"));
+    Some(number)
+}
 
 #[tokio::main]
 async fn main() {
+    init_job_runner().await;
+    init_rate_limiter().await;
+
     let conf = get_configuration(None).unwrap();
     let addr = conf.leptos_options.site_addr;
     let leptos_options = conf.leptos_options;
@@ -26,14 +224,24 @@ async fn main() {
             "/api/download/{id}",
             get(download_handler::download_handler),
         )
+        .route("/api/jobs/{id}/events", get(job_events::job_events_handler))
+        .route("/api/playlists/{id}/zip", get(playlist_handler::playlist_zip_handler))
+        .route("/api/playlists/{id}/m3u", get(playlist_handler::playlist_m3u_handler))
+        // Only for access tokens with the `operator` role
+        .route("/api/stats/strategies", get(strategy_stats_handler::strategy_stats_handler))
         .fallback(leptos_axum::file_and_error_handler(shell))
-        .with_state(leptos_options);
+        .with_state(leptos_options)
+        .layer(middleware::from_fn_with_state(jwt_verifier(), auth::authenticate))
+        .layer(middleware::from_fn_with_state(
+            env_flag("YTMP3_BEHIND_PROXY"),
+            client_ip::resolve_client_ip,
+        ));
 
     // run our app with hyper
     // `axum::Server` is a re-export of `hyper::Server`
     log!("listening on http://{}", &addr);
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app.into_make_service())
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 }